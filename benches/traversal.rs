@@ -0,0 +1,105 @@
+//! Benchmarks for the traversal of the dotfiles directory and the computation of the link states,
+//! which together make up most of the work done by `dotfiles status` and `dotfiles link`.
+//! Run them using `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dotfiles::{
+    cli::GlobalArgs,
+    commands::status::{get_dotfiles_entries, get_dotfiles_entry_state},
+    config::{AppConfig, Mapping},
+    files::symlink,
+};
+use std::{fs, path::PathBuf};
+use tempfile::TempDir;
+
+/// A synthetic home directory containing a dotfiles directory and the parsed config mapping its files.
+struct Fixture {
+    // keeps the temporary directory alive until the benchmark is done:
+    _tmp: TempDir,
+    home_dir: PathBuf,
+    global_args: GlobalArgs,
+    config: AppConfig,
+}
+
+/// creates a dotfiles directory with `dirs` directories containing 10 files each,
+/// next to the same number of top-level files.
+/// Every other file is mapped and every other mapping is linked into the home directory,
+/// so all common states show up during the benchmarks.
+fn create_fixture(dirs: usize) -> Fixture {
+    let tmp = tempfile::tempdir().unwrap();
+    let home_dir = tmp.path().join("home");
+    let dotfiles_root = tmp.path().join("dotfiles");
+    let mut mappings = vec![];
+
+    for dir in 0..dirs {
+        let dir_path = PathBuf::from(format!(".config/app-{}", dir));
+        fs::create_dir_all(dotfiles_root.join(&dir_path)).unwrap();
+        fs::create_dir_all(home_dir.join(&dir_path)).unwrap();
+        let top_level = PathBuf::from(format!(".file-{}", dir));
+        let files = (0..10)
+            .map(|file| dir_path.join(format!("file-{}", file)))
+            .chain(std::iter::once(top_level));
+
+        for (index, file) in files.enumerate() {
+            fs::write(dotfiles_root.join(&file), "").unwrap();
+            if index % 2 == 0 {
+                if index % 4 == 0 {
+                    symlink(dotfiles_root.join(&file), home_dir.join(&file)).unwrap();
+                }
+                mappings.push(Mapping::new(file));
+            }
+        }
+    }
+
+    Fixture {
+        _tmp: tmp,
+        home_dir,
+        global_args: GlobalArgs {
+            dotfiles_root,
+            ..Default::default()
+        },
+        config: AppConfig {
+            mappings,
+            ..Default::default()
+        },
+    }
+}
+
+fn bench_traversal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("traversal");
+    // a single run on the large tree takes in the order of seconds:
+    group.sample_size(10);
+    for (name, dirs) in &[("small", 10), ("large", 500)] {
+        let fixture = create_fixture(*dirs);
+
+        group.bench_with_input(
+            BenchmarkId::new("get_dotfiles_entries", name),
+            &fixture,
+            |b, fixture| {
+                b.iter(|| get_dotfiles_entries(&fixture.global_args, &fixture.config).unwrap())
+            },
+        );
+
+        group.bench_with_input(BenchmarkId::new("status", name), &fixture, |b, fixture| {
+            b.iter(|| {
+                let entries = get_dotfiles_entries(&fixture.global_args, &fixture.config).unwrap();
+                entries
+                    .iter()
+                    .map(|entry| {
+                        get_dotfiles_entry_state(
+                            &fixture.global_args,
+                            &fixture.config,
+                            entry,
+                            &fixture.home_dir,
+                        )
+                        .unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_traversal);
+criterion_main!(benches);
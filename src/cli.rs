@@ -1,11 +1,44 @@
 use crate::{
-    commands::{add, status},
+    commands::{
+        add, clone, completions, diff_config, doctor, dump, env, init, link, open, orphans, prune,
+        reconcile, remove, repair_config, rewrite_links, rollback, shellenv, status, suggest,
+        unlink,
+    },
+    config::{normalize_mapping, AppConfig, ConfigSettings, ConflictStrategy},
+    files::{
+        discover_dotfiles_root, get_config_file_path, get_cwd, get_home_dir,
+        get_settings_file_path, resolve_user_path, DEFAULT_HOME_SCAN_DEPTH,
+    },
+    last_status::resolve_entry_number,
+    settings::Settings,
     AppError,
 };
 use clap::{App, AppSettings, Arg, ArgMatches};
-use std::path::PathBuf;
+use dirs::config_dir;
+use gethostname::gethostname;
+use serde::Serialize;
+use std::{
+    io::{stdin, IsTerminal},
+    path::{Path, PathBuf},
+};
 
 const ARG_DOTFILES_ROOT: &str = "dotfiles-root";
+const ARG_CONFIG: &str = "config";
+const ARG_PROFILE: &str = "profile";
+const ARG_TRACE_FILE: &str = "trace-file";
+const ARG_TRACE_REDACT_HOME: &str = "trace-redact-home";
+const ARG_MAX_DEPTH: &str = "max-depth";
+const ARG_NO_COLOR: &str = "no-color";
+const ARG_COLLECT_ERRORS: &str = "collect-errors";
+const ARG_ON_CONFLICT: &str = "on-conflict";
+const ARG_BACKUP: &str = "backup";
+pub const ARG_ONLY: &str = "only";
+const ARG_JSON: &str = "json";
+const ARG_JSON_PRETTY: &str = "json-pretty";
+const ARG_DRY_RUN: &str = "dry-run";
+const ARG_YES: &str = "yes";
+const ARG_ASSUME_YES_FOR: &str = "assume-yes-for";
+const ARG_VERBOSE: &str = "verbose";
 
 /// returns a new clap APP CLI interface used for this app
 pub fn build_cli<'a, 'b>() -> App<'a, 'b> {
@@ -18,41 +51,526 @@ pub fn build_cli<'a, 'b>() -> App<'a, 'b> {
         .arg(
             Arg::with_name(ARG_DOTFILES_ROOT)
                 .short("r")
-                .required(true)
                 .takes_value(true)
-                .help("the absolute path of the dotfiles repository root directory")
+                .help("the path of the dotfiles repository root directory, which may start with `~` or be relative to the current directory. Falls back to `dotfiles_root` in ~/.config/dotfiles/settings.toml or the directory ~/.config/dotfiles/config.toml is linked to.")
                 .env("DOTFILES_ROOT"),
         )
+        .arg(
+            Arg::with_name(ARG_CONFIG)
+                .short("c")
+                .long(ARG_CONFIG)
+                .takes_value(true)
+                .value_name("FILE")
+                .help("the absolute path of the dotfiles config file. Defaults to .config/dotfiles/config.toml in the dotfiles repository.")
+                .env("DOTFILES_CONFIG"),
+        )
+        .arg(
+            Arg::with_name(ARG_PROFILE)
+                .long(ARG_PROFILE)
+                .takes_value(true)
+                .value_name("PROFILE")
+                .help("the profile whose mappings are used in addition to the mappings of your config, eg. `laptop`")
+                .env("DOTFILES_PROFILE"),
+        )
+        .arg(
+            Arg::with_name(ARG_VERBOSE)
+                .short("v")
+                .long(ARG_VERBOSE)
+                .multiple(true)
+                .global(true)
+                .help("prints the decisions taken on the way to stderr, eg. why a path is skipped. Use -vv for more details."),
+        )
+        .arg(
+            Arg::with_name(ARG_TRACE_FILE)
+                .long(ARG_TRACE_FILE)
+                .takes_value(true)
+                .value_name("FILE")
+                .global(true)
+                .help("writes a trace of all operations of this run to the given file as JSON lines. Useful for bug reports."),
+        )
+        .arg(
+            Arg::with_name(ARG_TRACE_REDACT_HOME)
+                .long(ARG_TRACE_REDACT_HOME)
+                .requires(ARG_TRACE_FILE)
+                .global(true)
+                .help("replaces the path of your home directory with `~` in the trace file"),
+        )
+        .arg(
+            Arg::with_name(ARG_NO_COLOR)
+                .long(ARG_NO_COLOR)
+                .global(true)
+                .help("disables colored output. Setting the `NO_COLOR` environment variable has the same effect."),
+        )
+        .arg(
+            Arg::with_name(ARG_COLLECT_ERRORS)
+                .long(ARG_COLLECT_ERRORS)
+                .global(true)
+                .help("reports all problems of an invalid config at once instead of stopping at the first one"),
+        )
+        .arg(
+            Arg::with_name(ARG_JSON)
+                .long(ARG_JSON)
+                .global(true)
+                .help("prints machine-readable JSON instead of human-readable text, including errors. The JSON is compact, one document per line."),
+        )
+        .arg(
+            Arg::with_name(ARG_JSON_PRETTY)
+                .long(ARG_JSON_PRETTY)
+                .global(true)
+                .help("like --json, but indents the printed JSON to read it by eye"),
+        )
+        .arg(
+            Arg::with_name(ARG_DRY_RUN)
+                .long(ARG_DRY_RUN)
+                .global(true)
+                .help("prints the changes a command would apply without applying them"),
+        )
+        .arg(
+            Arg::with_name(ARG_YES)
+                .long(ARG_YES)
+                .short("y")
+                .global(true)
+                .help("answers all questions with their default answer without asking, eg. in scripts without a terminal"),
+        )
+        .arg(
+            Arg::with_name(ARG_ASSUME_YES_FOR)
+                .long(ARG_ASSUME_YES_FOR)
+                .takes_value(true)
+                .use_delimiter(true)
+                .value_name("OPERATIONS")
+                .possible_values(&Operation::NAMES)
+                .global(true)
+                .help("confirms changes of the given comma-separated types without asking, but still asks for all others"),
+        )
         .subcommand(status::get_subcommand())
         .subcommand(add::get_subcommand())
+        .subcommand(remove::get_subcommand())
+        .subcommand(link::get_subcommand())
+        .subcommand(unlink::get_subcommand())
+        .subcommand(rewrite_links::get_subcommand())
+        .subcommand(rollback::get_subcommand())
+        .subcommand(reconcile::get_subcommand())
+        .subcommand(suggest::get_subcommand())
+        .subcommand(open::get_subcommand())
+        .subcommand(shellenv::get_subcommand())
+        .subcommand(orphans::get_subcommand())
+        .subcommand(prune::get_subcommand())
+        .subcommand(dump::get_subcommand())
+        .subcommand(diff_config::get_subcommand())
+        .subcommand(repair_config::get_subcommand())
+        .subcommand(clone::get_subcommand())
+        .subcommand(init::get_subcommand())
+        .subcommand(env::get_subcommand())
+        .subcommand(doctor::get_subcommand())
+        .subcommand(completions::get_subcommand())
+}
+
+/// Describes the type of a change the user is asked to confirm, see `--assume-yes-for`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// creating or deleting a symlink.
+    Symlink,
+    /// changing the mappings of the config file, including creating it.
+    Mapping,
+    /// moving a file or directory, eg. into the dotfiles directory or to a backup.
+    Move,
+    /// deleting a file or directory.
+    Delete,
+}
+impl Operation {
+    /// the names of all operations as used in CLI arguments.
+    pub const NAMES: [&'static str; 4] = ["symlink", "mapping", "move", "delete"];
+
+    /// returns the operation with the given name, see `Operation::NAMES`.
+    pub fn from_name(name: &str) -> Option<Operation> {
+        match name {
+            "symlink" => Some(Operation::Symlink),
+            "mapping" => Some(Operation::Mapping),
+            "move" => Some(Operation::Move),
+            "delete" => Some(Operation::Delete),
+            _ => None,
+        }
+    }
 }
 
 /// Contains all global cli options which are independent of the chosen sub-command
+#[derive(Default)]
 pub struct GlobalArgs {
     pub dotfiles_root: PathBuf,
+    /// the path of the config file, if it overrides the default location, see `get_config_file_path`.
+    pub config_file: Option<PathBuf>,
+    /// the name of the profile whose mappings are merged into the mappings of the config, if any.
+    pub profile: Option<String>,
+    /// the file to write the trace of this run to, if any.
+    pub trace_file: Option<PathBuf>,
+    /// true if the home directory should be redacted in the trace file.
+    pub trace_redact_home: bool,
+    /// the name of the current host, used to resolve host-specific files in the dotfiles directory.
+    pub hostname: Option<String>,
+    /// true if the output should not be colored.
+    pub no_color: bool,
+    /// true if all validation errors of the config should be reported instead of only the first one.
+    pub collect_errors: bool,
+    /// true if the output should be machine-readable JSON.
+    pub json: bool,
+    /// true if printed JSON should be indented, see `to_json`.
+    pub json_pretty: bool,
+    /// true if changes should only be printed instead of applied.
+    pub dry_run: bool,
+    /// true if questions should be answered with their default answer instead of asking the user.
+    pub yes: bool,
+    /// the types of changes which are confirmed without asking the user.
+    pub assume_yes_for: Vec<Operation>,
+    /// the per-user settings read from the settings file.
+    pub settings: Settings,
+    /// the number of `-v` flags, see `log`.
+    pub verbosity: u8,
 }
 impl<'a> GlobalArgs {
     /// returns a new global options struct based on the parsed CLI arguments
     pub fn from_cli_args(arg_matches: &'a ArgMatches) -> Result<GlobalArgs, AppError> {
-        // unwrap is OK here, this attributes are marked as required:
-        let dotfiles_root = arg_matches.value_of(ARG_DOTFILES_ROOT).unwrap();
+        GlobalArgs::from_cli_args_with_root(arg_matches, None)
+    }
 
-        let dotfiles_root_path = PathBuf::from(dotfiles_root);
-        if !dotfiles_root_path.is_absolute() {
+    /// returns a new global options struct based on the parsed CLI arguments.
+    /// If provided, `dotfiles_root` is used instead of resolving the dotfiles directory,
+    /// eg. for commands creating it such as `clone`.
+    pub fn from_cli_args_with_root(
+        arg_matches: &'a ArgMatches,
+        dotfiles_root: Option<PathBuf>,
+    ) -> Result<GlobalArgs, AppError> {
+        let settings_path = get_settings_file_path()?;
+        let settings = Settings::from_file(&settings_path)?;
+        let dotfiles_root = match (dotfiles_root, arg_matches.value_of(ARG_DOTFILES_ROOT)) {
+            (Some(dotfiles_root), _) => dotfiles_root,
+            // values of environment variables are not expanded by the shell:
+            (None, Some(dotfiles_root)) => {
+                resolve_user_path(Path::new(dotfiles_root), get_home_dir, get_cwd)?
+            }
+            (None, None) => resolve_dotfiles_root(&settings, &settings_path)?,
+        };
+
+        if !dotfiles_root.is_absolute() {
             return Err(AppError::CliInvalidArgValue(
                 String::from(ARG_DOTFILES_ROOT),
-                format!("The path to your dotfiles directory provided via -r or environment variable must be absolute. Provided value: {}", dotfiles_root),
+                format!("The path to your dotfiles directory provided via settings file must be absolute. Provided value: {}", dotfiles_root.display()),
             ));
         }
-        if !dotfiles_root_path.is_dir() {
-            return Err(AppError::CliInvalidArgValue(
+        let config_file = arg_matches.value_of(ARG_CONFIG).map(PathBuf::from);
+        if let Some(config_file) = &config_file {
+            if !config_file.is_absolute() || config_file.file_name().is_none() {
+                return Err(AppError::CliInvalidArgValue(
+                    String::from(ARG_CONFIG),
+                    format!("The path to your dotfiles config file provided via -c or environment variable must be an absolute file path. Provided value: {}", config_file.display()),
+                ));
+            }
+        }
+        let mut global_args = GlobalArgs {
+            dotfiles_root,
+            config_file,
+            profile: arg_matches.value_of(ARG_PROFILE).map(String::from),
+            trace_file: arg_matches.value_of(ARG_TRACE_FILE).map(PathBuf::from),
+            trace_redact_home: arg_matches.is_present(ARG_TRACE_REDACT_HOME),
+            hostname: gethostname().into_string().ok(),
+            no_color: arg_matches.is_present(ARG_NO_COLOR),
+            collect_errors: arg_matches.is_present(ARG_COLLECT_ERRORS),
+            json: is_json(arg_matches),
+            json_pretty: is_json_pretty(arg_matches),
+            dry_run: arg_matches.is_present(ARG_DRY_RUN),
+            yes: arg_matches.is_present(ARG_YES),
+            assume_yes_for: arg_matches
+                .values_of(ARG_ASSUME_YES_FOR)
+                .map(|names| names.filter_map(Operation::from_name).collect())
+                .unwrap_or_default(),
+            settings,
+            verbosity: verbosity(arg_matches),
+        };
+        // the config file does not exist yet if it is about to be created, eg. by `init`:
+        if let Ok(config_file) = get_config_file_path(&global_args) {
+            global_args.apply_config_settings(
+                &ConfigSettings::from_config_file(&config_file),
+                std::env::var_os("NO_COLOR").is_some(),
+            );
+        }
+
+        Ok(global_args)
+    }
+
+    /// falls back to the given `[settings]` of the dotfiles config for all values which have not been provided
+    /// via CLI or environment variable, see `ConfigSettings`. `no_color_env` is true if `NO_COLOR` is set.
+    pub fn apply_config_settings(&mut self, settings: &ConfigSettings, no_color_env: bool) {
+        if self.profile.is_none() {
+            self.profile = settings.default_profile.clone();
+        }
+        if !self.no_color && !no_color_env {
+            self.no_color = settings.color == Some(false);
+        }
+    }
+
+    /// asks the user the given yes/no question and returns the answer.
+    /// With `--yes`, the default answer is returned without asking.
+    pub fn confirm<S: AsRef<str>>(&self, question: S, default: bool) -> Result<bool, AppError> {
+        if self.yes {
+            return Ok(default);
+        }
+        require_terminal(question.as_ref())?;
+        Ok(promptly::prompt_default(question, default).unwrap_or(false))
+    }
+
+    /// asks the user to confirm changes of the given types and returns the answer, see `confirm`.
+    /// The changes are confirmed without asking if all of their types are listed in `--assume-yes-for`.
+    pub fn confirm_operations<S: AsRef<str>>(
+        &self,
+        operations: &[Operation],
+        question: S,
+        default: bool,
+    ) -> Result<bool, AppError> {
+        if !operations.is_empty()
+            && operations
+                .iter()
+                .all(|operation| self.assume_yes_for.contains(operation))
+        {
+            return Ok(true);
+        }
+        self.confirm(question, default)
+    }
+
+    /// makes sure the dotfiles root is an existing directory.
+    /// This is not validated when parsing the arguments, because commands bootstrapping a fresh
+    /// dotfiles directory should be able to create it. All other commands call this before accessing it.
+    pub fn require_dotfiles_root(&self) -> Result<(), AppError> {
+        if self.dotfiles_root.is_dir() {
+            Ok(())
+        } else {
+            Err(AppError::CliInvalidArgValue(
                 String::from(ARG_DOTFILES_ROOT),
-                format!("{} is not a valid directory", dotfiles_root),
-            ));
+                format!("{} is not a valid directory", self.dotfiles_root.display()),
+            ))
         }
+    }
+}
+
+/// resolves the dotfiles root if it was provided neither via `-r` nor `DOTFILES_ROOT`:
+/// uses `dotfiles_root` of the settings file first and tries to discover it based on the linked config otherwise.
+/// Nothing is created or prompted for, so this is cheap to call repeatedly, eg. from a shell prompt.
+fn resolve_dotfiles_root(settings: &Settings, settings_path: &Path) -> Result<PathBuf, AppError> {
+    if let Some(dotfiles_root) = &settings.dotfiles_root {
+        return Ok(dotfiles_root.to_owned());
+    }
+
+    let home = get_home_dir()?;
+    let config = config_dir().ok_or(AppError::FsUserLocation(String::from("config directory")))?;
+    discover_dotfiles_root(&home, &config).ok_or_else(|| {
+        AppError::CliInvalidArgValue(
+            String::from(ARG_DOTFILES_ROOT),
+            format!(
+                "No dotfiles directory found. Provide it via -r, DOTFILES_ROOT or `dotfiles_root` in {}",
+                settings_path.display()
+            ),
+        )
+    })
+}
+
+/// returns the `--max-depth` argument shared by all commands scanning the home directory.
+pub fn max_depth_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_MAX_DEPTH)
+        .long(ARG_MAX_DEPTH)
+        .takes_value(true)
+        .value_name("DEPTH")
+        .help(
+            "the maximum number of directory levels to scan in your home directory. Defaults to 3.",
+        )
+}
+
+/// returns the value of the `--max-depth` argument or its default if it was not provided.
+pub fn max_depth_from_args(args: &ArgMatches) -> Result<usize, AppError> {
+    match args.value_of(ARG_MAX_DEPTH) {
+        None => Ok(DEFAULT_HOME_SCAN_DEPTH),
+        Some(value) => value.parse::<usize>().map_err(|_| {
+            AppError::CliInvalidArgValue(
+                ARG_MAX_DEPTH.to_string(),
+                format!("{} is not a valid depth", value),
+            )
+        }),
+    }
+}
+
+/// returns true if the output should be machine-readable JSON, see `--json` and `--json-pretty`.
+/// Also used to print errors before the global args could be parsed.
+pub fn is_json(arg_matches: &ArgMatches) -> bool {
+    arg_matches.is_present(ARG_JSON) || arg_matches.is_present(ARG_JSON_PRETTY)
+}
+
+/// returns true if printed JSON should be indented, see `--json-pretty`.
+/// Used by commands reporting problems resolving the global args, see `is_json`.
+pub fn is_json_pretty(arg_matches: &ArgMatches) -> bool {
+    arg_matches.is_present(ARG_JSON_PRETTY)
+}
+
+/// serializes the given value to JSON to be printed, indented if `pretty` is true, see `--json-pretty`.
+/// All commands printing JSON use this to format their output the same way.
+pub fn to_json<T: Serialize + ?Sized>(value: &T, pretty: bool) -> serde_json::Result<String> {
+    if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+/// returns the number of `-v` flags, see `log`.
+/// Also used to log while resolving the global args.
+pub fn verbosity(arg_matches: &ArgMatches) -> u8 {
+    arg_matches.occurrences_of(ARG_VERBOSE).min(u8::MAX as u64) as u8
+}
+
+/// makes sure the user can be asked the given question.
+/// Scripts without a terminal would otherwise block forever waiting for an answer.
+pub fn require_terminal(question: &str) -> Result<(), AppError> {
+    if stdin().is_terminal() {
+        Ok(())
+    } else {
+        Err(AppError::CliNotInteractive(question.to_string()))
+    }
+}
+
+/// returns the `--on-conflict` argument shared by all commands creating symlinks.
+pub fn on_conflict_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_ON_CONFLICT)
+        .long(ARG_ON_CONFLICT)
+        .takes_value(true)
+        .value_name("STRATEGY")
+        .possible_values(&ConflictStrategy::NAMES)
+        .help("what to do if a file which is not a symlink blocks the creation of a symlink. Defaults to `on_conflict` in your dotfiles config or `prompt`.")
+}
+
+/// returns the `--backup` argument, a shorthand for `--on-conflict backup`.
+pub fn backup_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_BACKUP)
+        .long(ARG_BACKUP)
+        .conflicts_with(ARG_ON_CONFLICT)
+        .help("moves files blocking the creation of a symlink to <path>.bak.<timestamp>, same as `--on-conflict backup`")
+}
+
+/// returns the value of the `--on-conflict` argument or `backup` for `--backup`, if it was provided.
+pub fn on_conflict_from_args(args: &ArgMatches) -> Result<Option<ConflictStrategy>, AppError> {
+    if args.is_present(ARG_BACKUP) {
+        return Ok(Some(ConflictStrategy::Backup));
+    }
+    match args.value_of(ARG_ON_CONFLICT) {
+        None => Ok(None),
+        Some(value) => ConflictStrategy::from_name(value).map(Some).ok_or_else(|| {
+            AppError::CliInvalidArgValue(
+                ARG_ON_CONFLICT.to_string(),
+                format!("unknown conflict strategy {}", value),
+            )
+        }),
+    }
+}
+
+/// returns the `--only` argument shared by all commands which can be scoped to a subtree of mappings.
+pub fn only_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ARG_ONLY)
+        .long(ARG_ONLY)
+        .takes_value(true)
+        .value_name("PREFIX")
+        .help("only considers mapped paths starting with the given path relative to your dotfiles directory, eg. `.config/nvim`")
+}
+
+/// returns the normalized value of the `--only` argument, if it was provided.
+pub fn only_from_args(args: &ArgMatches) -> Option<PathBuf> {
+    args.value_of(ARG_ONLY)
+        .map(|value| normalize_mapping(PathBuf::from(value)))
+}
+
+/// returns the given `--only` prefix or the mapped path it refers to if it is the number of an entry
+/// of the last `status --number`, see `resolve_entry_number`.
+pub fn resolve_only_number(
+    only: Option<PathBuf>,
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+) -> Result<Option<PathBuf>, AppError> {
+    let value = match only.as_ref().and_then(|only| only.to_str()) {
+        Some(value) => value,
+        None => return Ok(only),
+    };
+    Ok(resolve_entry_number(value, ARG_ONLY, global_args, config)?.or(only))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_cli, is_json, to_json, verbosity, GlobalArgs, Operation};
+    use crate::config::ConfigSettings;
+    use serde_json::json;
+
+    #[test]
+    fn apply_config_settings_only_fills_values_not_provided_otherwise() {
+        let settings = ConfigSettings {
+            default_profile: Some("work".to_string()),
+            color: Some(false),
+            backup_on_conflict: None,
+        };
+        let apply = |global_args: GlobalArgs, no_color_env: bool| {
+            let mut global_args = global_args;
+            global_args.apply_config_settings(&settings, no_color_env);
+            (global_args.profile, global_args.no_color)
+        };
+
+        assert_eq!(
+            apply(GlobalArgs::default(), false),
+            (Some("work".to_string()), true)
+        );
+        let from_cli = GlobalArgs {
+            profile: Some("home".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(apply(from_cli, false), (Some("home".to_string()), true));
+        // `NO_COLOR` decides on its own, `colored` reads it itself:
+        assert_eq!(
+            apply(GlobalArgs::default(), true),
+            (Some("work".to_string()), false)
+        );
+    }
+
+    #[test]
+    fn confirm_operations_only_confirms_assumed_operations() {
+        let global_args = GlobalArgs {
+            assume_yes_for: vec![Operation::Symlink, Operation::Mapping],
+            // answers all other questions with their default instead of asking:
+            yes: true,
+            ..Default::default()
+        };
+        let confirm = |operations: &[Operation]| {
+            global_args
+                .confirm_operations(operations, "Continue?", false)
+                .unwrap()
+        };
+
+        assert!(confirm(&[Operation::Symlink, Operation::Mapping]));
+        assert!(!confirm(&[Operation::Symlink, Operation::Move]));
+        assert!(!confirm(&[]));
+    }
+
+    #[test]
+    fn verbosity_counts_flags_before_and_after_the_sub_command() {
+        let matches = build_cli().get_matches_from(vec!["dotfiles", "-vv", "status"]);
+        assert_eq!(verbosity(&matches), 2);
+        let matches = build_cli().get_matches_from(vec!["dotfiles", "status", "-v"]);
+        assert_eq!(verbosity(&matches), 1);
+
+        let matches = build_cli().get_matches_from(vec!["dotfiles", "status"]);
+        assert_eq!(verbosity(&matches), 0);
+    }
+
+    #[test]
+    fn json_pretty_implies_json_and_indents_it() {
+        let matches = build_cli().get_matches_from(vec!["dotfiles", "status", "--json-pretty"]);
+        assert!(is_json(&matches));
 
-        Ok(GlobalArgs {
-            dotfiles_root: PathBuf::from(dotfiles_root),
-        })
+        let value = json!({ "path": ".vimrc" });
+        assert_eq!(to_json(&value, false).unwrap(), r#"{"path":".vimrc"}"#);
+        assert_eq!(
+            to_json(&value, true).unwrap(),
+            "{\n  \"path\": \".vimrc\"\n}"
+        );
     }
 }
@@ -1,13 +1,34 @@
-use super::CommandResult;
+use super::{
+    link::{link_mappings, LinkOptions},
+    print_planned_changes,
+    suggest::is_untracked,
+    CommandResult,
+};
 use crate::{
     cli::GlobalArgs,
-    config::AppConfig,
+    cli::{backup_arg, on_conflict_arg, on_conflict_from_args, require_terminal, Operation},
+    config::{
+        find_mapped_ancestor, find_mapped_descendant, normalize_mapping, AppConfig,
+        ConflictStrategy, Manage, Mapping,
+    },
     errors::AppError,
-    files::{create_symlink_for, get_cwd, get_home_dir, normalize_paths},
+    files::{
+        create_parent_dir, create_symlink_for, get_backup_path, get_cwd, get_home_dir,
+        get_shadowing_ancestor, move_path, normalize_paths, remove_empty_dirs, remove_path,
+        resolve_symlink_target, scan_home_dir,
+    },
+    ignore::{expand_glob, is_glob},
+    log, trace,
 };
 use clap::{App, Arg, ArgMatches, SubCommand};
-use fs_extra::{dir, file};
-use std::{fmt::Display, fs, path::PathBuf};
+use serde_json::{json, Value};
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
 
 pub const CMD_IDENTIFIER: &str = "add";
 const CMD_ABOUT: &str = r#"
@@ -20,14 +41,34 @@ If the file or folder is located in in your home directory, it will:
 1) add the path to the mappings in the dotfiles configuration file.
 2) move the file or folder from your home directory to your dotfiles directory.
 3) create a symlink to this path at the appropriate location in your home directory.
+
+Glob patterns such as `.config/*` are replaced by the existing paths they match, each of them is added as its own
+mapping. Quote the pattern to keep your shell from expanding it.
+
+With --expand, a directory is not added as a whole. Instead, each file it currently contains is added
+as its own mapping, so files created in this directory later on are not part of your dotfiles.
+
+With --interactive, the untracked dotfiles of your home directory and the entries of `~/.config`
+are listed instead, and the selected ones are added.
+
+With --link-now, all other mappings which are not linked yet are linked afterwards as well, like `link` does.
 "#;
+const ARG_PATH: &str = "path";
+const ARG_PATHS_FROM: &str = "paths-from";
+const ARG_ONCE: &str = "once";
+const ARG_CONFIRM_EACH: &str = "confirm-each";
+const ARG_EXPAND: &str = "expand";
+const ARG_INTERACTIVE: &str = "interactive";
+const ARG_LINK_NOW: &str = "link-now";
 
 /// Describes a single required IO change to be done. Used to display a list of changes
 /// to the user to sign of.
+#[derive(Debug)]
 enum RequiredChanges {
-    AddMapping(PathBuf),
+    AddMapping(Mapping),
     CreateSymlink(PathBuf, PathBuf),
     MoveFile(PathBuf, PathBuf),
+    RemovePath(PathBuf),
 }
 /// Describes a list of steps which can be skipped
 type SkippingChanges = Vec<&'static str>;
@@ -46,6 +87,12 @@ pub enum Error {
     /// Another mapping exists which is a child of the given path.
     /// Consists of the given path and existing nested path.
     ExistingChild(PathBuf, PathBuf),
+    /// A parent directory of the given path in the home directory is a symlink into the dotfiles directory,
+    /// see `files::get_shadowing_ancestor`. Consists of the given path and the symlinked parent.
+    ShadowedByParentLink(PathBuf, PathBuf),
+    /// The given path is the dotfiles directory itself or one of its parents, eg. the home directory.
+    /// Consists of the given path and the dotfiles directory.
+    ContainsDotfilesRoot(PathBuf, PathBuf),
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -62,115 +109,629 @@ impl Display for Error {
             ),
             Error::ExistingParent(path, parent) => write!(
                 f,
-                "Cannot add this path: The given path {} is a parent of the existing mapping {}. Nested mappings are not supported.",
+                "Cannot add this path: The given path {} is a parent of the existing mapping {}. Set `allow_nested = true` to allow nested mappings.",
                 path.display(),
                 parent.display(),
             ),
             Error::ExistingChild(path, child) => write!(
                 f,
-                "Cannot add this path: The existing mapping {} is a child of the given path {}. Nested mappings are not supported.",
+                "Cannot add this path: The existing mapping {} is a child of the given path {}. Set `allow_nested = true` to allow nested mappings.",
                 child.display(),
                 path.display()
             ),
+            Error::ShadowedByParentLink(path, parent) => write!(
+                f,
+                "Cannot add this path: {} is a symlink into your dotfiles directory, {} would be linked inside your dotfiles directory.",
+                parent.display(),
+                path.display()
+            ),
+            Error::ContainsDotfilesRoot(path, dotfiles_root) if path == dotfiles_root => write!(
+                f,
+                "Cannot add this path: {} is your dotfiles directory itself.",
+                path.display()
+            ),
+            Error::ContainsDotfilesRoot(path, dotfiles_root) => write!(
+                f,
+                "Cannot add this path: {} contains your dotfiles directory {}, which would be moved into itself.",
+                path.display(),
+                dotfiles_root.display()
+            ),
         }
     }
 }
 
 /// returns the clap definition for the status sub-command
 pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
-    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT).arg(
-        Arg::with_name("path")
-            .help("the path to the directory or file to add.")
-            .required(true),
-    )
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(
+            Arg::with_name(ARG_PATH)
+                .help("the path to the directory or file to add.")
+                .required_unless_one(&[ARG_PATHS_FROM, ARG_INTERACTIVE]),
+        )
+        .arg(
+            Arg::with_name(ARG_PATHS_FROM)
+                .long(ARG_PATHS_FROM)
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with(ARG_PATH)
+                .help("reads the paths to add from the given file, one per line. Use `-` to read from stdin. Brace patterns such as `.config/{nvim,fish}` are expanded."),
+        )
+        .arg(
+            Arg::with_name(ARG_ONCE)
+                .long(ARG_ONCE)
+                .help("links the given paths once, but lets `link` and `status` ignore them afterwards"),
+        )
+        .arg(on_conflict_arg())
+        .arg(backup_arg())
+        .arg(
+            Arg::with_name(ARG_CONFIRM_EACH)
+                .long(ARG_CONFIRM_EACH)
+                .help("asks for confirmation of every single change instead of the whole batch"),
+        )
+        .arg(
+            Arg::with_name(ARG_EXPAND)
+                .long(ARG_EXPAND)
+                .help("adds each file of the given directories as its own mapping instead of the directory itself"),
+        )
+        .arg(
+            Arg::with_name(ARG_INTERACTIVE)
+                .long(ARG_INTERACTIVE)
+                .short("i")
+                .conflicts_with_all(&[ARG_PATH, ARG_PATHS_FROM])
+                .help("lists the untracked dotfiles of your home directory and adds the selected ones"),
+        )
+        .arg(
+            Arg::with_name(ARG_LINK_NOW)
+                .long(ARG_LINK_NOW)
+                .help("links all other mappings which are not linked yet after adding the given paths, like `link` does"),
+        )
 }
 
 struct AddCommandArgs {
-    /// The path to add to the dotfiles. If accessed outside of this struct,
-    /// it is guaranteed to be absolute and existing.
-    path: PathBuf,
+    /// The paths to add to the dotfiles. If accessed outside of this struct,
+    /// they are guaranteed to be absolute and existing. Empty if `interactive` is true.
+    paths: Vec<PathBuf>,
+    /// if true, the paths to add are selected by the user, see `select_paths`.
+    interactive: bool,
+    /// if true, directories are replaced by the files they contain, see `expand_directory`.
+    expand: bool,
+    /// how the added mappings are handled after they have been added.
+    manage: Manage,
+    /// if true, every change is confirmed individually.
+    confirm_each: bool,
+    /// the conflict strategy, if provided via CLI.
+    on_conflict: Option<ConflictStrategy>,
+    /// if true, all other unlinked mappings are linked after adding the paths, see `link_remaining`.
+    link_now: bool,
 }
 impl AddCommandArgs {
     fn from_args(args: &ArgMatches) -> Result<AddCommandArgs, AppError> {
-        let path = PathBuf::from(args.value_of("path").unwrap());
-        let cwd = get_cwd()?;
-        // we cannot use canonicalize because we do not want to resolve symlinks here:
-        let abs_path = normalize_paths(&cwd, &path)?;
-        if abs_path.exists() == false {
+        let interactive = args.is_present(ARG_INTERACTIVE);
+        let expand = args.is_present(ARG_EXPAND);
+        let raw_paths = match args.value_of(ARG_PATHS_FROM) {
+            Some(source) => read_paths_from(source)?
+                .iter()
+                .flat_map(|line| expand_braces(line))
+                .map(PathBuf::from)
+                .collect(),
+            None if interactive => vec![],
+            // unwrap is OK here, path is required if neither paths-from nor interactive is provided:
+            None => vec![PathBuf::from(args.value_of(ARG_PATH).unwrap())],
+        };
+
+        let manage = if args.is_present(ARG_ONCE) {
+            Manage::Once
+        } else {
+            Manage::Always
+        };
+
+        Ok(AddCommandArgs {
+            paths: resolve_paths(raw_paths, expand)?,
+            interactive,
+            expand,
+            manage,
+            confirm_each: args.is_present(ARG_CONFIRM_EACH),
+            on_conflict: on_conflict_from_args(args)?,
+            link_now: args.is_present(ARG_LINK_NOW),
+        })
+    }
+}
+
+/// returns the absolute paths of the given existing paths.
+/// Glob patterns are replaced by the paths they match, see `expand_pattern`.
+/// If `expand` is true, directories are replaced by the files they contain.
+fn resolve_paths(raw_paths: Vec<PathBuf>, expand: bool) -> Result<Vec<PathBuf>, AppError> {
+    let mut matched_paths = vec![];
+    for path in raw_paths {
+        if is_glob(&path) {
+            matched_paths.extend(expand_pattern(&path, get_home_dir)?);
+        } else {
+            matched_paths.push(path);
+        }
+    }
+
+    let mut paths = vec![];
+    for path in matched_paths {
+        let abs_path = resolve_input_path(&path, get_cwd)?;
+        if !abs_path.exists() {
             return Err(AppError::CliInvalidArgValue(
-                "path".to_string(),
+                ARG_PATH.to_string(),
                 format!("The given path {} does not exist", abs_path.display()),
             ));
         };
+        if expand && abs_path.is_dir() {
+            paths.extend(expand_directory(&abs_path));
+        } else {
+            paths.push(abs_path);
+        }
+    }
+
+    Ok(paths)
+}
+
+/// returns the existing paths matching the given glob pattern, which is resolved relative to the current directory.
+/// A leading `~` is replaced with the given home directory, because quoted patterns are not expanded by the shell.
+/// Fails if the pattern is invalid or does not match any path.
+fn expand_pattern(
+    pattern: &Path,
+    home_dir: impl FnOnce() -> Result<PathBuf, AppError>,
+) -> Result<Vec<PathBuf>, AppError> {
+    let abs_pattern = if let Ok(rel_pattern) = pattern.strip_prefix("~") {
+        home_dir()?.join(rel_pattern)
+    } else if pattern.is_absolute() {
+        pattern.to_owned()
+    } else {
+        get_cwd()?.join(pattern)
+    };
+    let invalid = |reason: String| {
+        AppError::CliInvalidArgValue(
+            ARG_PATH.to_string(),
+            format!("The pattern {} {}", pattern.display(), reason),
+        )
+    };
+    let matches =
+        expand_glob(&abs_pattern).map_err(|reason| invalid(format!("is invalid: {}", reason)))?;
+    if matches.is_empty() {
+        return Err(invalid("does not match any path".to_string()));
+    }
+
+    Ok(matches)
+}
+
+/// returns the paths offered by `--interactive`, relative to the home directory and sorted by name:
+/// all untracked top-level dotfiles and entries of `.config`, except for the dotfiles directory.
+fn get_interactive_candidates(
+    config: &AppConfig,
+    home_dir: &Path,
+    dotfiles_root: &Path,
+) -> Vec<PathBuf> {
+    scan_home_dir(home_dir, 2, &[dotfiles_root])
+        .into_iter()
+        .filter(|path| match path.parent() {
+            // the entries of `.config` are offered instead of `.config` itself:
+            Some(parent) if parent == Path::new("") => {
+                path.to_string_lossy().starts_with('.') && path != Path::new(".config")
+            }
+            Some(parent) => parent == Path::new(".config"),
+            None => false,
+        })
+        .filter(|path| is_untracked(config, home_dir, path))
+        .filter(|path| !dotfiles_root.starts_with(home_dir.join(path)))
+        .collect()
+}
+
+/// lists the given candidates and asks the user which of them should be added.
+/// Returns the absolute paths of the selected candidates.
+fn select_paths(candidates: &[PathBuf], home_dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let question = "Which paths should be added? (eg. `1 3 5-7`, leave empty to cancel)";
+    // there is no default selection, so --yes cannot answer this question:
+    require_terminal(question)?;
+    for (i, candidate) in candidates.iter().enumerate() {
+        println!("{:>3}) {}", i + 1, candidate.display());
+    }
+    loop {
+        let input = promptly::prompt_opt::<String, _>(question)
+            .map_err(|err| AppError::FsOther(format!("Failed to read selection: {}", err)))?
+            .unwrap_or_default();
+        match parse_selection(&input, candidates.len()) {
+            Ok(selection) => {
+                return Ok(selection
+                    .into_iter()
+                    .map(|i| home_dir.join(&candidates[i]))
+                    .collect())
+            }
+            Err(reason) => println!("{}", reason),
+        }
+    }
+}
+
+/// parses a selection such as `1 3,5-7` of the numbers printed by `select_paths`.
+/// Returns the zero-based indices of the selected entries in ascending order, without duplicates.
+fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>, String> {
+    let mut selection = vec![];
+    for part in input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+    {
+        let (start, end) = part.split_once('-').unwrap_or((part, part));
+        let parse = |number: &str| match number.trim().parse::<usize>() {
+            Ok(number) if (1..=count).contains(&number) => Ok(number),
+            _ => Err(format!(
+                "Invalid selection `{}`: expected numbers between 1 and {}.",
+                part, count
+            )),
+        };
+        selection.extend(parse(start)? - 1..parse(end)?);
+    }
+    selection.sort_unstable();
+    selection.dedup();
+
+    Ok(selection)
+}
+
+/// returns the absolute path of the given path without resolving symlinks.
+/// The current directory is only looked up using `cwd` if the path is relative,
+/// so absolute paths can be added even if the current directory is not available (eg. deleted).
+pub fn resolve_input_path(
+    path: &Path,
+    cwd: impl FnOnce() -> Result<PathBuf, AppError>,
+) -> Result<PathBuf, AppError> {
+    // we cannot use canonicalize because we do not want to resolve symlinks here:
+    if path.is_absolute() {
+        normalize_paths(Path::new("/"), path)
+    } else {
+        normalize_paths(&cwd()?, path)
+    }
+}
 
-        return Ok(AddCommandArgs { path: abs_path });
+/// returns all non-empty lines of the given file or stdin if `source` equals `-`.
+/// Lines starting with `#` are treated as comments and ignored.
+fn read_paths_from(source: &str) -> Result<Vec<String>, AppError> {
+    let content = if source == "-" {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content).map(|_| content)
+    } else {
+        fs::read_to_string(source)
     }
+    .map_err(|err| AppError::FsOther(format!("Could not read paths from {}: {}", source, err)))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// expands brace patterns the same way a shell does: `.config/{nvim,fish}` => `.config/nvim`, `.config/fish`.
+/// Patterns may be nested. Braces without a comma or without a closing brace are kept as they are.
+fn expand_braces(input: &str) -> Vec<String> {
+    for (open, _) in input.match_indices('{') {
+        let mut depth = 0;
+        let mut separators = vec![];
+        let mut close = None;
+        for (i, c) in input[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + i);
+                        break;
+                    }
+                }
+                ',' if depth == 1 => separators.push(open + i),
+                _ => (),
+            }
+        }
+
+        if let (Some(close), false) = (close, separators.is_empty()) {
+            let (prefix, suffix) = (&input[..open], &input[close + 1..]);
+            let mut bounds = vec![open];
+            bounds.extend(separators);
+            bounds.push(close);
+
+            // each alternative may contain further patterns, as well as the suffix:
+            return bounds
+                .windows(2)
+                .flat_map(|bound| {
+                    let alternative = &input[bound[0] + 1..bound[1]];
+                    expand_braces(&format!("{}{}{}", prefix, alternative, suffix))
+                })
+                .collect();
+        }
+    }
+
+    vec![input.to_string()]
+}
+
+/// returns the absolute paths of all files in the given directory and its sub-directories, sorted by name.
+/// Symlinks are neither followed nor returned, empty directories are ignored.
+fn expand_directory(dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .min_depth(1)
+        .follow_links(false)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect()
 }
 
 /// command handler for the `add` sub-command
 /// see `dotfiles add -h` for an overview.
 pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
-    let AddCommandArgs { path } = AddCommandArgs::from_args(args)?;
-    let mut config = AppConfig::from_config_file(global_args)?;
+    let AddCommandArgs {
+        paths,
+        interactive,
+        expand,
+        manage,
+        confirm_each,
+        on_conflict,
+        link_now,
+    } = AddCommandArgs::from_args(args)?;
+    let mut config = AppConfig::from_config_file(global_args, true)?;
+    let on_conflict = config.get_conflict_strategy(on_conflict);
     let home_dir = get_home_dir()?;
+    let backup_dir = global_args.settings.get_backup_dir(&home_dir);
+    let paths = if interactive {
+        let candidates = get_interactive_candidates(&config, &home_dir, &global_args.dotfiles_root);
+        if candidates.is_empty() {
+            println!("Could not find any untracked dotfiles. Have a good time!");
+            return Ok(());
+        }
+        resolve_paths(select_paths(&candidates, &home_dir)?, expand)?
+    } else {
+        paths
+    };
 
-    let (changes, skipped) =
-        get_required_changes(&config, &global_args.dotfiles_root, &home_dir, &path)
-            .map_err(|err| AppError::CmdAddError(err))?;
+    // every path is planned against the mappings planned for the previous paths,
+    // so the same batch cannot contain duplicated or nested mappings:
+    let mut planned_config = config.clone();
+    let mut changes = vec![];
+    let mut skipped = vec![];
+    for path in &paths {
+        let plan = |on_conflict| {
+            get_required_changes(
+                &planned_config,
+                &global_args.dotfiles_root,
+                &home_dir,
+                path,
+                manage,
+                on_conflict,
+                backup_dir.as_deref(),
+            )
+        };
+        let (path_changes, path_skipped) = match plan(on_conflict) {
+            // the user is only asked about paths which actually conflict:
+            Err(Error::BothPathsExist(_, home_path))
+                if on_conflict == ConflictStrategy::Prompt
+                    && confirm_backup(global_args, &home_path)? =>
+            {
+                plan(ConflictStrategy::Backup)
+            }
+            planned => planned,
+        }
+        .map_err(AppError::CmdAddError)?;
+        for change in &path_changes {
+            if let RequiredChanges::AddMapping(mapping) = change {
+                planned_config.add_mapping(mapping.to_owned());
+            }
+        }
+        trace::event(
+            "planned_changes",
+            json!({
+                "path": path,
+                "changes": format!("{:?}", path_changes),
+                "skipped": path_skipped,
+            }),
+        );
+        changes.extend(path_changes);
+        skipped.extend(path_skipped);
+    }
 
+    // nothing is asked or changed in a dry run:
+    if global_args.dry_run {
+        let planned = changes
+            .iter()
+            .map(|change| (describe_change(change), change_to_json(change)))
+            .collect();
+        return print_planned_changes(global_args, planned);
+    }
     if !skipped.is_empty() {
         println!("Following steps can be skipped:");
         for skip in skipped {
             println!("- {}", skip);
         }
     }
-    if !changes.is_empty() {
+    if changes.is_empty() {
+        println!("Nothing left to be done. Have a good time!");
+    } else if confirm_each {
+        confirm_and_apply_each(&changes, &mut config, global_args)?;
+    } else {
         println!("Following things will be done:");
         for change in &changes {
-            let line = match change {
-                RequiredChanges::AddMapping(path) => {
-                    format!("adding {} to mappings in config file", path.display())
-                }
-                RequiredChanges::CreateSymlink(from, to) => {
-                    format!("creating symlink {} -> {}", from.display(), to.display())
-                }
-                RequiredChanges::MoveFile(from, to) => {
-                    format!("moving {} -> {}", from.display(), to.display())
-                }
-            };
-            println!("- {}", line);
+            println!("- {}", describe_change(change));
         }
 
-        if promptly::prompt_default("Continue?", true).unwrap_or(false) {
-            apply_changes(&changes, &mut config, global_args)?;
+        let operations: Vec<Operation> = changes.iter().map(get_operation).collect();
+        if !global_args.confirm_operations(&operations, "Continue?", true)? {
+            return Ok(());
         }
-    } else {
-        println!("Nothing left to be done. Have a good time!");
+        apply_changes(&changes, &mut config, global_args)?;
+    }
+
+    if link_now {
+        let linked = link_remaining(global_args, &config, &home_dir, on_conflict)?;
+        println!("Linked {} additional mapping(s).", linked);
     }
 
     Ok(())
 }
 
+/// links all mappings of the given config which are not linked yet, see `link::link_mappings`.
+/// Must be called after the added paths have been linked, so only the additionally created symlinks are counted.
+fn link_remaining(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    on_conflict: ConflictStrategy,
+) -> Result<usize, AppError> {
+    let options = LinkOptions {
+        on_conflict,
+        ..Default::default()
+    };
+
+    link_mappings(global_args, config, home_dir, options)
+}
+
+/// returns a human readable description of the given change.
+fn describe_change(change: &RequiredChanges) -> String {
+    match change {
+        RequiredChanges::AddMapping(mapping) => {
+            format!(
+                "adding {} to mappings in config file",
+                mapping.path.display()
+            )
+        }
+        RequiredChanges::CreateSymlink(from, to) => {
+            format!("creating symlink {} -> {}", from.display(), to.display())
+        }
+        RequiredChanges::MoveFile(from, to) => {
+            format!("moving {} -> {}", from.display(), to.display())
+        }
+        RequiredChanges::RemovePath(path) => format!("deleting {}", path.display()),
+    }
+}
+
+/// returns the type of the given change, see `--assume-yes-for`.
+fn get_operation(change: &RequiredChanges) -> Operation {
+    match change {
+        RequiredChanges::AddMapping(_) => Operation::Mapping,
+        RequiredChanges::CreateSymlink(_, _) => Operation::Symlink,
+        RequiredChanges::MoveFile(_, _) => Operation::Move,
+        RequiredChanges::RemovePath(_) => Operation::Delete,
+    }
+}
+
+/// returns the machine-readable descriptor of the given change, see `--dry-run`.
+fn change_to_json(change: &RequiredChanges) -> Value {
+    match change {
+        RequiredChanges::AddMapping(mapping) => {
+            json!({ "action": "add_mapping", "path": mapping.path })
+        }
+        RequiredChanges::CreateSymlink(from, to) => {
+            json!({ "action": "create_symlink", "link": from, "target": to })
+        }
+        RequiredChanges::MoveFile(from, to) => json!({ "action": "move", "from": from, "to": to }),
+        RequiredChanges::RemovePath(path) => json!({ "action": "remove", "path": path }),
+    }
+}
+
+/// asks for confirmation of every single change and applies it right away if confirmed.
+/// If moving or deleting a file is skipped, creating the symlink at its location is skipped as well,
+/// because the file would still block it. Prints the applied and skipped changes at the end.
+fn confirm_and_apply_each(
+    changes: &[RequiredChanges],
+    config: &mut AppConfig,
+    global_args: &GlobalArgs,
+) -> Result<(), AppError> {
+    let mut applied = vec![];
+    let mut skipped: Vec<&RequiredChanges> = vec![];
+    let mut created_dirs = vec![];
+    for change in changes {
+        let blocked = skipped.iter().any(|skipped| match (skipped, change) {
+            (RequiredChanges::MoveFile(kept, _), RequiredChanges::CreateSymlink(link, _))
+            | (RequiredChanges::RemovePath(kept), RequiredChanges::CreateSymlink(link, _)) => {
+                kept == link
+            }
+            _ => false,
+        });
+        let question = format!("{}?", describe_change(change));
+        if !blocked && global_args.confirm_operations(&[get_operation(change)], question, true)? {
+            if let Err(err) = apply_change(change, config, &mut created_dirs) {
+                remove_empty_dirs(&created_dirs);
+                return Err(err);
+            }
+            applied.push(change);
+        } else {
+            skipped.push(change);
+        }
+    }
+
+    write_added_mappings(applied.iter().copied(), config, global_args)?;
+
+    for (title, changes) in &[("Applied", applied), ("Skipped", skipped)] {
+        if !changes.is_empty() {
+            println!("{} changes:", title);
+            for change in changes {
+                println!("- {}", describe_change(change));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// asks the user whether the given existing file in the home directory should be backed up to add its path,
+/// see `--on-conflict prompt`. Nothing is asked in a dry run, which plans the backup instead.
+fn confirm_backup(global_args: &GlobalArgs, home_path: &Path) -> Result<bool, AppError> {
+    if global_args.dry_run {
+        return Ok(true);
+    }
+    let question = format!(
+        "{} exists and differs from its file in your dotfiles. Move it to a backup and create the symlink?",
+        home_path.display()
+    );
+    // backing up the existing file moves it:
+    global_args.confirm_operations(&[Operation::Move], question, false)
+}
+
 fn get_required_changes(
     config: &AppConfig,
-    dotfiles_root: &PathBuf,
-    home_dir: &PathBuf,
-    path: &PathBuf,
+    dotfiles_root: &Path,
+    home_dir: &Path,
+    path: &Path,
+    manage: Manage,
+    on_conflict: ConflictStrategy,
+    backup_dir: Option<&Path>,
 ) -> Result<(Vec<RequiredChanges>, SkippingChanges), Error> {
-    let is_in_dotfiles = path.starts_with(&dotfiles_root);
+    // adding the dotfiles directory or one of its parents would link it into itself:
+    if dotfiles_root.starts_with(path) {
+        return Err(Error::ContainsDotfilesRoot(
+            path.to_owned(),
+            dotfiles_root.to_owned(),
+        ));
+    }
+    let is_in_dotfiles = path.starts_with(dotfiles_root);
     // this variable is true if the path points exclusively into home dir, but not dotfiles dir.
     // Often though, the dotfiles dir is a subdirectory of the home dir:
-    let is_in_home_dir = path.starts_with(&home_dir) && !is_in_dotfiles;
+    let is_in_home_dir = path.starts_with(home_dir) && !is_in_dotfiles;
 
-    // the relative path which will be stored in config.mappings:
-    let mappings_path = if is_in_dotfiles {
-        Ok(path.strip_prefix(&dotfiles_root).unwrap())
-    } else if is_in_home_dir {
-        Ok(path.strip_prefix(&home_dir).unwrap())
-    } else {
-        Err(Error::OutsideValidDir(path.clone()))
-    }?
-    .to_owned();
+    // the relative path which will be stored in config.mappings.
+    // It is normalized the same way as the mappings read from the config to compare them reliably:
+    let mappings_path = normalize_mapping(
+        if is_in_dotfiles {
+            Ok(path.strip_prefix(dotfiles_root).unwrap())
+        } else if is_in_home_dir {
+            Ok(path.strip_prefix(home_dir).unwrap())
+        } else {
+            Err(Error::OutsideValidDir(path.to_owned()))
+        }?
+        .to_owned(),
+    );
+    log::debug(format_args!(
+        "{} is inside your {} directory, its mapping is {}",
+        path.display(),
+        if is_in_dotfiles { "dotfiles" } else { "home" },
+        mappings_path.display()
+    ));
+
+    if let Some(ancestor) = get_shadowing_ancestor(home_dir, dotfiles_root, &mappings_path) {
+        return Err(Error::ShadowedByParentLink(mappings_path, ancestor));
+    }
 
     // the absolute paths into the home dir and dotfiles dir:
     let homedir_path = home_dir.join(&mappings_path);
@@ -179,41 +740,82 @@ fn get_required_changes(
     let mut changes: Vec<RequiredChanges> = Vec::new();
     let mut skipped: SkippingChanges = Vec::new();
 
-    if config.mappings.contains(&mappings_path) {
+    if config.get_mapping(&mappings_path).is_some() {
+        log::debug(format_args!(
+            "{} is mapped already",
+            mappings_path.display()
+        ));
         skipped.push("This path is already mapped, no need to update config.");
     } else {
-        // make sure we do not end up with nested mappings:
-        for mapping in &config.mappings {
-            if mapping.starts_with(&mappings_path) {
+        // make sure we do not end up with nested mappings, unless they are allowed:
+        if !config.allows_nested_mappings() {
+            let mapped_paths = config.get_sorted_mapping_paths();
+            if let Some(child) = find_mapped_descendant(&mapped_paths, &mappings_path) {
                 return Err(Error::ExistingParent(
                     mappings_path.to_owned(),
-                    mapping.to_owned(),
+                    child.to_owned(),
                 ));
-            } else if mappings_path.starts_with(&mapping) {
+            }
+            if let Some(parent) = find_mapped_ancestor(&mapped_paths, &mappings_path) {
                 return Err(Error::ExistingChild(
                     mappings_path.to_owned(),
-                    mapping.to_owned(),
+                    parent.to_owned(),
                 ));
             }
         }
-        changes.push(RequiredChanges::AddMapping(mappings_path.to_owned()));
+        changes.push(RequiredChanges::AddMapping(Mapping {
+            manage,
+            ..Mapping::new(mappings_path.to_owned())
+        }));
     };
 
     // special case: file exists in both home and dotfiles dir:
-    // either they are already correctly linked or this operation is invalid:
+    // either they are already correctly linked or the file in the home dir blocks the symlink:
     if homedir_path.exists() && dotfiles_path.exists() {
         let meta = fs::symlink_metadata(&homedir_path).unwrap();
-        if meta.file_type().is_symlink() && fs::read_link(&homedir_path).unwrap() == dotfiles_path {
+        if meta.file_type().is_symlink()
+            && resolve_symlink_target(&homedir_path).unwrap() == dotfiles_path
+        {
+            log::debug(format_args!(
+                "{} is a symlink to {} already",
+                homedir_path.display(),
+                dotfiles_path.display()
+            ));
             skipped.push("no symlink will be created, paths are already linked.");
         } else {
-            return Err(Error::BothPathsExist(
-                dotfiles_path.clone(),
+            log::debug(format_args!(
+                "both {} and {} exist, resolving the conflict with strategy {:?}",
+                homedir_path.display(),
+                dotfiles_path.display(),
+                on_conflict
+            ));
+            // the caller asks the user before resolving a prompt, see `confirm_backup`:
+            let blocking_change = match on_conflict {
+                ConflictStrategy::Skip | ConflictStrategy::Prompt => {
+                    return Err(Error::BothPathsExist(
+                        dotfiles_path.clone(),
+                        homedir_path.clone(),
+                    ))
+                }
+                ConflictStrategy::Backup => RequiredChanges::MoveFile(
+                    homedir_path.clone(),
+                    get_backup_path(&homedir_path, home_dir, backup_dir),
+                ),
+                ConflictStrategy::Overwrite => RequiredChanges::RemovePath(homedir_path.clone()),
+            };
+            changes.push(blocking_change);
+            changes.push(RequiredChanges::CreateSymlink(
                 homedir_path.clone(),
+                dotfiles_path.clone(),
             ));
         }
     } else {
         // exists in home dir, but not in dotfiles dir => move files to dotfiles dir:
         if homedir_path.exists() {
+            log::debug(format_args!(
+                "{} only exists in your home directory and is moved",
+                homedir_path.display()
+            ));
             changes.push(RequiredChanges::MoveFile(
                 homedir_path.clone(),
                 dotfiles_path.clone(),
@@ -229,44 +831,555 @@ fn get_required_changes(
     Ok((changes, skipped))
 }
 
+/// applies the given changes in order and stops at the first failing change.
+/// Directories created by the applied changes are removed again on failure if they are still empty.
 fn apply_changes(
     changes: &Vec<RequiredChanges>,
     config: &mut AppConfig,
     global_args: &GlobalArgs,
 ) -> Result<(), AppError> {
+    let mut created_dirs = vec![];
     for change in changes {
-        match change {
-            RequiredChanges::AddMapping(path) => {
-                config.add_mapping(path.to_owned());
-                config.to_config_file(global_args)?;
-            }
-            RequiredChanges::CreateSymlink(from, to) => create_symlink_for(&from, &to)?,
-            RequiredChanges::MoveFile(from, to) => {
-                if from.is_dir() {
-                    let mut options = dir::CopyOptions::new();
-                    options.copy_inside = true;
-                    dir::move_dir(&from, &to, &options).map_err(|err| {
-                        AppError::FsOther(format!(
-                            "failed to move directory {} -> {}: {}",
-                            from.display(),
-                            to.display(),
-                            err
-                        ))
-                    })?;
-                } else {
-                    let options = file::CopyOptions::new();
-                    file::move_file(&from, &to, &options).map_err(|err| {
-                        AppError::FsOther(format!(
-                            "failed to move file {} -> {}: {}",
-                            from.display(),
-                            to.display(),
-                            err
-                        ))
-                    })?;
-                }
-            }
+        if let Err(err) = apply_change(change, config, &mut created_dirs) {
+            remove_empty_dirs(&created_dirs);
+            return Err(err);
         }
     }
 
+    write_added_mappings(changes.iter(), config, global_args)
+}
+
+/// writes the config once after all changes of a batch have been applied, if any of them added a mapping.
+/// Nothing is written if a change failed, so the config never contains some of the mappings of a batch only.
+fn write_added_mappings<'a, I: IntoIterator<Item = &'a RequiredChanges>>(
+    applied: I,
+    config: &AppConfig,
+    global_args: &GlobalArgs,
+) -> Result<(), AppError> {
+    let mut applied = applied.into_iter();
+    if applied.any(|change| matches!(change, RequiredChanges::AddMapping(_))) {
+        config.to_config_file(global_args)?;
+    }
+
     Ok(())
 }
+
+/// applies a single change. Directories created on the way are added to `created_dirs`.
+/// Added mappings are only added to the given config, which has to be written afterwards.
+fn apply_change(
+    change: &RequiredChanges,
+    config: &mut AppConfig,
+    created_dirs: &mut Vec<PathBuf>,
+) -> Result<(), AppError> {
+    match change {
+        // the config is written once for all changes, see `write_added_mappings`:
+        RequiredChanges::AddMapping(mapping) => {
+            config.add_mapping(mapping.to_owned());
+            Ok(())
+        }
+        // expanded directories may not exist yet at the destination:
+        RequiredChanges::CreateSymlink(from, to) => {
+            created_dirs.extend(create_parent_dir(from)?);
+            create_symlink_for(from, to)
+        }
+        RequiredChanges::MoveFile(from, to) => {
+            created_dirs.extend(create_parent_dir(to)?);
+            move_path(from, to)
+        }
+        RequiredChanges::RemovePath(path) => remove_path(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_changes, expand_braces, expand_directory, expand_pattern, get_interactive_candidates,
+        get_required_changes, link_remaining, parse_selection, resolve_input_path, Error,
+        RequiredChanges,
+    };
+    use crate::{
+        cli::GlobalArgs,
+        config::{AppConfig, ConflictStrategy, Manage, Mapping},
+        errors::AppError,
+        files::symlink,
+    };
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    fn missing_cwd() -> Result<PathBuf, AppError> {
+        Err(AppError::FsUserLocation("current directory".to_string()))
+    }
+
+    #[test]
+    fn apply_changes_writes_the_config_once_for_all_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dotfiles = tmp.path().join("dotfiles");
+        let home = tmp.path().join("home");
+        fs::create_dir_all(&dotfiles).unwrap();
+        fs::create_dir_all(&home).unwrap();
+        let config_path = dotfiles.join(".config/dotfiles/config.toml");
+        let global_args = GlobalArgs {
+            dotfiles_root: dotfiles.clone(),
+            config_file: Some(config_path.clone()),
+            ..Default::default()
+        };
+        let changes_for = |file: &str| {
+            vec![
+                RequiredChanges::MoveFile(home.join(file), dotfiles.join(file)),
+                RequiredChanges::CreateSymlink(home.join(file), dotfiles.join(file)),
+                RequiredChanges::AddMapping(Mapping::new(PathBuf::from(file))),
+            ]
+        };
+        let files = [".vimrc", ".bashrc", ".zshrc"];
+        for file in &files[..2] {
+            fs::write(home.join(file), "").unwrap();
+        }
+
+        // the last move fails because its source does not exist, so no mapping is written:
+        let changes: Vec<_> = files.iter().flat_map(|file| changes_for(file)).collect();
+        let result = apply_changes(&changes, &mut AppConfig::default(), &global_args);
+        assert!(result.is_err());
+        assert!(!config_path.exists());
+
+        let files = [".profile", ".inputrc"];
+        for file in &files {
+            fs::write(home.join(file), "").unwrap();
+        }
+        let changes: Vec<_> = files.iter().flat_map(|file| changes_for(file)).collect();
+        apply_changes(&changes, &mut AppConfig::default(), &global_args).unwrap();
+        let config = AppConfig::from_path(&config_path, false).unwrap();
+        assert_eq!(
+            config.mappings,
+            vec![
+                Mapping::new(PathBuf::from(".profile")),
+                Mapping::new(PathBuf::from(".inputrc"))
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_changes_removes_created_dirs_on_failure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dotfiles = tmp.path().join("dotfiles");
+        let home = tmp.path().join("home");
+        fs::create_dir_all(dotfiles.join(".config")).unwrap();
+        fs::create_dir_all(home.join(".config/app/nested")).unwrap();
+        fs::write(home.join(".config/app/nested/a"), "").unwrap();
+
+        // the second move fails because its source does not exist:
+        let changes = vec![
+            RequiredChanges::MoveFile(
+                home.join(".config/app/nested/a"),
+                dotfiles.join(".config/app/nested/a"),
+            ),
+            RequiredChanges::MoveFile(
+                home.join(".config/missing/b"),
+                dotfiles.join(".config/missing/deep/b"),
+            ),
+        ];
+        let result = apply_changes(&changes, &mut AppConfig::default(), &GlobalArgs::default());
+
+        assert!(result.is_err());
+        assert!(!dotfiles.join(".config/missing").exists());
+        // directories which are not empty anymore are kept:
+        assert!(dotfiles.join(".config/app/nested/a").exists());
+        assert!(dotfiles.join(".config").exists());
+    }
+
+    #[test]
+    fn resolve_input_path_does_not_require_cwd_for_absolute_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        fs::write(dir.join(".vimrc"), "").unwrap();
+
+        assert_eq!(
+            resolve_input_path(&dir.join(".vimrc"), missing_cwd).unwrap(),
+            dir.join(".vimrc")
+        );
+        assert!(resolve_input_path(&PathBuf::from(".vimrc"), missing_cwd).is_err());
+        assert_eq!(
+            resolve_input_path(&PathBuf::from(".vimrc"), || Ok(dir.clone())).unwrap(),
+            dir.join(".vimrc")
+        );
+    }
+
+    #[test]
+    fn get_required_changes_refuses_the_dotfiles_root_and_its_parents() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let dotfiles_root = home.join("projects/dotfiles");
+        fs::create_dir_all(&dotfiles_root).unwrap();
+        let config = AppConfig::default();
+        let plan = |path: &Path| {
+            get_required_changes(
+                &config,
+                &dotfiles_root,
+                home,
+                path,
+                Manage::Always,
+                ConflictStrategy::Skip,
+                None,
+            )
+        };
+
+        for path in [dotfiles_root.as_path(), &home.join("projects"), home] {
+            assert!(
+                matches!(plan(path), Err(Error::ContainsDotfilesRoot(given, _)) if given == path),
+                "{} was not refused",
+                path.display()
+            );
+        }
+        // siblings sharing a name prefix are not affected:
+        assert!(plan(&home.join("projects/dotfiles-old")).is_ok());
+    }
+
+    #[test]
+    fn get_required_changes_resolves_blocking_home_files() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let dotfiles_root = home.join("dotfiles");
+        fs::create_dir_all(&dotfiles_root).unwrap();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        fs::write(home.join(".vimrc"), "").unwrap();
+        let config = AppConfig::default();
+        let plan = |strategy| {
+            get_required_changes(
+                &config,
+                &dotfiles_root,
+                home,
+                &dotfiles_root.join(".vimrc"),
+                Manage::Always,
+                strategy,
+                None,
+            )
+        };
+
+        assert!(plan(ConflictStrategy::Skip).is_err());
+        // prompts are resolved by asking the user before planning again:
+        assert!(matches!(
+            plan(ConflictStrategy::Prompt),
+            Err(Error::BothPathsExist(_, path)) if path == home.join(".vimrc")
+        ));
+
+        let (changes, _) = plan(ConflictStrategy::Backup).unwrap();
+        assert!(
+            matches!(&changes[..], [RequiredChanges::AddMapping(_), RequiredChanges::MoveFile(from, to), RequiredChanges::CreateSymlink(_, _)]
+                if *from == home.join(".vimrc") && to.to_string_lossy().contains(".vimrc.bak.")),
+            "got {:?}",
+            changes
+        );
+
+        let (changes, _) = plan(ConflictStrategy::Overwrite).unwrap();
+        assert!(
+            matches!(&changes[..], [RequiredChanges::AddMapping(_), RequiredChanges::RemovePath(path), RequiredChanges::CreateSymlink(_, _)]
+                if *path == home.join(".vimrc")),
+            "got {:?}",
+            changes
+        );
+    }
+
+    #[test]
+    fn expand_directory_returns_nested_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::create_dir_all(dir.join("themes/dark")).unwrap();
+        fs::create_dir_all(dir.join("empty")).unwrap();
+        fs::write(dir.join("settings.json"), "").unwrap();
+        fs::write(dir.join("themes/dark/colors.json"), "").unwrap();
+        symlink(dir.join("settings.json"), dir.join("link.json")).unwrap();
+
+        assert_eq!(
+            expand_directory(dir),
+            vec![
+                dir.join("settings.json"),
+                dir.join("themes/dark/colors.json")
+            ]
+        );
+    }
+
+    #[test]
+    fn get_required_changes_rejects_expanded_files_of_mapped_directories() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let dotfiles_root = home.join("dotfiles");
+        fs::create_dir_all(home.join(".config/app")).unwrap();
+        fs::write(home.join(".config/app/settings.json"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".config/app"))],
+            ..Default::default()
+        };
+
+        let result = get_required_changes(
+            &config,
+            &dotfiles_root,
+            home,
+            &home.join(".config/app/settings.json"),
+            Manage::Always,
+            ConflictStrategy::Skip,
+            None,
+        );
+
+        assert!(matches!(result, Err(Error::ExistingChild(_, _))));
+    }
+
+    #[test]
+    fn link_remaining_only_counts_mappings_linked_in_addition() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dotfiles = tmp.path().join("dotfiles");
+        let home = tmp.path().join("home");
+        fs::create_dir_all(&dotfiles).unwrap();
+        fs::create_dir_all(&home).unwrap();
+        fs::write(dotfiles.join(".vimrc"), "").unwrap();
+        fs::write(home.join(".bashrc"), "").unwrap();
+        let global_args = GlobalArgs {
+            dotfiles_root: dotfiles.clone(),
+            config_file: Some(dotfiles.join(".config/dotfiles/config.toml")),
+            ..Default::default()
+        };
+        let mut config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".vimrc"))],
+            ..Default::default()
+        };
+
+        let changes = vec![
+            RequiredChanges::MoveFile(home.join(".bashrc"), dotfiles.join(".bashrc")),
+            RequiredChanges::CreateSymlink(home.join(".bashrc"), dotfiles.join(".bashrc")),
+            RequiredChanges::AddMapping(Mapping::new(PathBuf::from(".bashrc"))),
+        ];
+        apply_changes(&changes, &mut config, &global_args).unwrap();
+        let linked = link_remaining(&global_args, &config, &home, ConflictStrategy::Skip).unwrap();
+
+        assert_eq!(linked, 1);
+        assert_eq!(
+            fs::read_link(home.join(".vimrc")).unwrap(),
+            dotfiles.join(".vimrc")
+        );
+    }
+
+    #[test]
+    fn expand_pattern_fails_if_nothing_matches() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join(".config/nvim")).unwrap();
+
+        let home_dir = || unreachable!("only patterns starting with ~ use the home directory");
+        assert_eq!(
+            expand_pattern(&tmp.path().join(".config/*"), home_dir).unwrap(),
+            vec![tmp.path().join(".config/nvim")]
+        );
+        assert!(matches!(
+            expand_pattern(&tmp.path().join(".local/*"), home_dir),
+            Err(AppError::CliInvalidArgValue(_, _))
+        ));
+    }
+
+    #[test]
+    fn expand_pattern_resolves_a_leading_tilde_to_the_home_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join(".config/nvim")).unwrap();
+        let home_dir = || Ok(tmp.path().to_path_buf());
+
+        assert_eq!(
+            expand_pattern(Path::new("~/.config/*"), home_dir).unwrap(),
+            vec![tmp.path().join(".config/nvim")]
+        );
+    }
+
+    #[test]
+    fn get_required_changes_rejects_paths_shadowed_by_symlinked_parents() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let dotfiles_root = home.join("dotfiles");
+        fs::create_dir_all(dotfiles_root.join("config/app")).unwrap();
+        symlink(dotfiles_root.join("config"), home.join(".config")).unwrap();
+        let config = AppConfig::default();
+
+        let result = get_required_changes(
+            &config,
+            &dotfiles_root,
+            home,
+            &dotfiles_root.join(".config/app"),
+            Manage::Always,
+            ConflictStrategy::Skip,
+            None,
+        );
+
+        assert!(matches!(result, Err(Error::ShadowedByParentLink(_, _))));
+    }
+
+    #[test]
+    fn get_required_changes_accepts_nested_mappings_if_allowed() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let dotfiles_root = home.join("dotfiles");
+        fs::create_dir_all(dotfiles_root.join(".config/app")).unwrap();
+        fs::write(dotfiles_root.join(".config/app/settings.json"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".config/app"))],
+            allow_nested: Some(true),
+            ..Default::default()
+        };
+
+        let (changes, _) = get_required_changes(
+            &config,
+            &dotfiles_root,
+            home,
+            &dotfiles_root.join(".config/app/settings.json"),
+            Manage::Always,
+            ConflictStrategy::Skip,
+            None,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            changes.first(),
+            Some(RequiredChanges::AddMapping(mapping)) if mapping.path == Path::new(".config/app/settings.json")
+        ));
+    }
+
+    #[test]
+    fn get_required_changes_normalizes_cur_dir_prefixed_paths() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        let dotfiles_root = home.join("dotfiles");
+        fs::create_dir_all(dotfiles_root.join(".config/app")).unwrap();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".config/app")),
+                Mapping::new(PathBuf::from(".vimrc")),
+            ],
+            ..Default::default()
+        };
+        let required_changes = |path: &str| {
+            get_required_changes(
+                &config,
+                &dotfiles_root,
+                home,
+                &dotfiles_root.join(path),
+                Manage::Always,
+                ConflictStrategy::Skip,
+                None,
+            )
+        };
+
+        assert!(matches!(
+            required_changes("./.config/app/settings.json"),
+            Err(Error::ExistingChild(_, _))
+        ));
+        let (changes, _) = required_changes("./.vimrc").unwrap();
+        assert!(!changes
+            .iter()
+            .any(|change| matches!(change, RequiredChanges::AddMapping(_))));
+    }
+
+    #[test]
+    fn get_required_changes_does_not_treat_sibling_directories_as_nested() {
+        let tmp = tempfile::tempdir().unwrap();
+        // the dotfiles root shares a string prefix with the home dir, but is not inside it:
+        let home = tmp.path().join("me");
+        let dotfiles_root = tmp.path().join("me-backup");
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(&dotfiles_root).unwrap();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        fs::write(home.join(".bashrc"), "").unwrap();
+        let config = AppConfig::default();
+        let required_changes = |path: &Path| {
+            get_required_changes(
+                &config,
+                &dotfiles_root,
+                &home,
+                path,
+                Manage::Always,
+                ConflictStrategy::Skip,
+                None,
+            )
+        };
+
+        let (changes, _) = required_changes(&dotfiles_root.join(".vimrc")).unwrap();
+        assert!(matches!(
+            changes.first(),
+            Some(RequiredChanges::AddMapping(mapping)) if mapping.path == Path::new(".vimrc")
+        ));
+        let (changes, _) = required_changes(&home.join(".bashrc")).unwrap();
+        assert!(changes.iter().any(|change| matches!(
+            change,
+            RequiredChanges::AddMapping(mapping) if mapping.path == Path::new(".bashrc")
+        )));
+        assert!(matches!(
+            required_changes(&tmp.path().join("me-backup2/.vimrc")),
+            Err(Error::OutsideValidDir(_))
+        ));
+    }
+
+    #[test]
+    fn get_interactive_candidates_lists_untracked_dotfiles_and_config_entries() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        for dir in [
+            ".config/nvim",
+            ".config/fish",
+            ".vim/colors",
+            "dotfiles",
+            "Documents",
+        ] {
+            fs::create_dir_all(home.join(dir)).unwrap();
+        }
+        fs::write(home.join(".bashrc"), "").unwrap();
+        fs::write(home.join(".vimrc"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".config/fish"))],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            get_interactive_candidates(&config, home, &home.join("dotfiles")),
+            vec![
+                PathBuf::from(".bashrc"),
+                PathBuf::from(".config/nvim"),
+                PathBuf::from(".vim"),
+                PathBuf::from(".vimrc"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_selection_supports_numbers_and_ranges() {
+        assert_eq!(parse_selection("3, 1 5-6 1", 6), Ok(vec![0, 2, 4, 5]));
+        assert_eq!(parse_selection("  ", 6), Ok(vec![]));
+        assert!(parse_selection("0", 6).is_err());
+        assert!(parse_selection("2-7", 6).is_err());
+        assert!(parse_selection("a", 6).is_err());
+    }
+
+    #[test]
+    fn expand_braces_expands_all_alternatives() {
+        assert_eq!(
+            expand_braces(".config/{nvim,fish,tmux}"),
+            vec![".config/nvim", ".config/fish", ".config/tmux"]
+        );
+    }
+
+    #[test]
+    fn expand_braces_expands_nested_and_multiple_patterns() {
+        assert_eq!(
+            expand_braces("{.config/{a,b},.c}/{x,y}"),
+            vec![
+                ".config/a/x",
+                ".config/a/y",
+                ".config/b/x",
+                ".config/b/y",
+                ".c/x",
+                ".c/y"
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_braces_keeps_literal_braces() {
+        assert_eq!(expand_braces(".config/{nvim}"), vec![".config/{nvim}"]);
+        assert_eq!(expand_braces(".config/{a,b"), vec![".config/{a,b"]);
+        assert_eq!(expand_braces(".vimrc"), vec![".vimrc"]);
+    }
+}
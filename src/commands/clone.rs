@@ -0,0 +1,160 @@
+use super::{
+    link::{link_mappings, LinkOptions},
+    print_planned_changes, CommandResult,
+};
+use crate::{
+    cli::{backup_arg, on_conflict_arg, on_conflict_from_args, GlobalArgs},
+    config::AppConfig,
+    errors::AppError,
+    files::{get_cwd, get_home_dir, get_settings_file_path},
+    git::run_git,
+    settings::Settings,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::json;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const CMD_IDENTIFIER: &str = "clone";
+const CMD_ABOUT: &str = r#"
+Sets up a new machine in one step: clones the given remote dotfiles repository using `git clone`
+and creates the symlinks of all its mappings the same way as `link` does.
+The repository is cloned to ~/dotfiles unless another directory is provided. The directory must not
+exist yet or be empty. Use --save to remember the directory as `dotfiles_root` in your settings file,
+so following commands find it without -r.
+"#;
+const ARG_URL: &str = "URL";
+const ARG_DIRECTORY: &str = "DIRECTORY";
+const ARG_SAVE: &str = "save";
+/// the directory in the home directory the repository is cloned to by default.
+const DEFAULT_DIRECTORY: &str = "dotfiles";
+
+/// returns the clap definition for the clone sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(
+            Arg::with_name(ARG_URL)
+                .required(true)
+                .help("the URL of the remote dotfiles repository to clone"),
+        )
+        .arg(Arg::with_name(ARG_DIRECTORY).help(
+            "the directory to clone the repository to. Defaults to `dotfiles` in your home directory.",
+        ))
+        .arg(
+            Arg::with_name(ARG_SAVE)
+                .long(ARG_SAVE)
+                .help("stores the cloned directory as `dotfiles_root` in your settings file"),
+        )
+        .arg(on_conflict_arg())
+        .arg(backup_arg())
+}
+
+/// returns the absolute directory the repository should be cloned to, which is used as dotfiles root.
+pub fn get_target_dir(args: &ArgMatches) -> Result<PathBuf, AppError> {
+    match args.value_of(ARG_DIRECTORY) {
+        Some(dir) if Path::new(dir).is_absolute() => Ok(PathBuf::from(dir)),
+        Some(dir) => Ok(get_cwd()?.join(dir)),
+        None => Ok(get_home_dir()?.join(DEFAULT_DIRECTORY)),
+    }
+}
+
+struct CloneCommandArgs {
+    url: String,
+    save: bool,
+}
+impl CloneCommandArgs {
+    fn from_args(args: &ArgMatches) -> CloneCommandArgs {
+        CloneCommandArgs {
+            // unwrap is OK here, the argument is required:
+            url: args.value_of(ARG_URL).unwrap().to_string(),
+            save: args.is_present(ARG_SAVE),
+        }
+    }
+}
+
+/// command handler for the `clone` sub-command
+/// see `dotfiles clone -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let CloneCommandArgs { url, save } = CloneCommandArgs::from_args(args);
+    let target_dir = &global_args.dotfiles_root;
+    if !is_empty_or_missing(target_dir) {
+        return Err(AppError::CliInvalidArgValue(
+            ARG_DIRECTORY.to_string(),
+            format!("{} already exists and is not empty", target_dir.display()),
+        ));
+    }
+
+    // the mappings are only known after cloning, so a dry run cannot preview linking them:
+    if global_args.dry_run {
+        let planned = vec![(
+            format!("cloning {} into {}", url, target_dir.display()),
+            json!({ "action": "clone", "url": url, "path": target_dir }),
+        )];
+        return print_planned_changes(global_args, planned);
+    }
+    // safe, the target directory is always absolute:
+    let parent_dir = target_dir.parent().unwrap();
+    fs::create_dir_all(parent_dir).map_err(|err| {
+        AppError::FsOther(format!(
+            "failed to create {}: {}",
+            parent_dir.display(),
+            err
+        ))
+    })?;
+    println!("Cloning {} into {}...", url, target_dir.display());
+    run_git(
+        parent_dir,
+        &["clone", "--", &url, &target_dir.display().to_string()],
+    )?;
+
+    if save {
+        let settings_path = get_settings_file_path()?;
+        Settings::save_dotfiles_root(&settings_path, target_dir)?;
+        println!(
+            "Saved {} as your dotfiles directory in {}",
+            target_dir.display(),
+            settings_path.display()
+        );
+    }
+
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let options = LinkOptions {
+        on_conflict: config.get_conflict_strategy(on_conflict_from_args(args)?),
+        ..Default::default()
+    };
+    let linked = link_mappings(global_args, &config, &get_home_dir()?, options)?;
+    println!("Linked {} mapping(s). Have a good time!", linked);
+
+    Ok(())
+}
+
+/// returns true if the given directory does not exist or does not contain any entries.
+fn is_empty_or_missing(dir: &Path) -> bool {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(_) => !dir.exists(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_empty_or_missing;
+    use std::fs;
+
+    #[test]
+    fn is_empty_or_missing_rejects_non_empty_directories_and_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("dotfiles");
+        assert!(is_empty_or_missing(&dir));
+
+        fs::create_dir(&dir).unwrap();
+        assert!(is_empty_or_missing(&dir));
+
+        fs::write(dir.join(".vimrc"), "").unwrap();
+        assert!(!is_empty_or_missing(&dir));
+        assert!(!is_empty_or_missing(&dir.join(".vimrc")));
+    }
+}
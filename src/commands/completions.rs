@@ -0,0 +1,61 @@
+use super::CommandResult;
+use crate::cli::build_cli;
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+use std::io::{self, Write};
+
+pub const CMD_IDENTIFIER: &str = "completions";
+const CMD_ABOUT: &str = r#"
+Prints the tab completion script of all commands and options for the given shell, eg.:
+  bash: dotfiles completions bash > ~/.local/share/bash-completion/completions/dotfiles
+  zsh:  dotfiles completions zsh > ~/.zfunc/_dotfiles
+  fish: dotfiles completions fish > ~/.config/fish/completions/dotfiles.fish
+"#;
+const ARG_SHELL: &str = "shell";
+
+/// returns the clap definition for the completions sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .setting(AppSettings::Hidden)
+        .arg(
+            Arg::with_name(ARG_SHELL)
+                .required(true)
+                .possible_values(&Shell::variants())
+                .help("the shell to print the completion script for"),
+        )
+}
+
+/// command handler for the `completions` sub-command
+/// see `dotfiles completions -h` for an overview.
+/// Like `env`, it does not need the global arguments, so completions work before a dotfiles directory exists.
+pub fn run(args: &ArgMatches) -> CommandResult {
+    // safe, the argument is required and clap only accepts known shells:
+    let shell = args.value_of(ARG_SHELL).unwrap().parse::<Shell>().unwrap();
+    write_completions(shell, &mut io::stdout());
+
+    Ok(())
+}
+
+/// writes the completion script for the given shell to the given output.
+/// It is generated from the same definition the CLI arguments are parsed with, see `build_cli`.
+fn write_completions<W: Write>(shell: Shell, out: &mut W) {
+    build_cli().gen_completions_to("dotfiles", shell, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_completions;
+    use clap::Shell;
+
+    #[test]
+    fn write_completions_covers_sub_commands_and_their_options() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut out = vec![];
+            write_completions(shell, &mut out);
+            let script = String::from_utf8(out).unwrap();
+
+            assert!(script.contains("rewrite-links"), "{}: {}", shell, script);
+            assert!(script.contains("on-conflict"), "{}: {}", shell, script);
+        }
+    }
+}
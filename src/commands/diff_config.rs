@@ -0,0 +1,180 @@
+use super::CommandResult;
+use crate::{
+    cli::{to_json, GlobalArgs},
+    config::{AppConfig, Mapping},
+    errors::AppError,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use colored::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+pub const CMD_IDENTIFIER: &str = "diff-config";
+const CMD_ABOUT: &str = r#"
+Compares the mappings of your dotfiles config with the ones of another config file, eg. the config of
+another machine. Prints the mappings only found in your config, the ones only found in the other config
+and the ones found in both. Mappings are compared by their path, common mappings using different options
+are marked. Both configs are parsed and validated before comparing them.
+"#;
+const ARG_OTHER: &str = "OTHER";
+
+/// returns the clap definition for the diff-config sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT).arg(
+        Arg::with_name(ARG_OTHER)
+            .required(true)
+            .help("the path of the config file to compare your config with"),
+    )
+}
+
+struct DiffConfigCommandArgs {
+    other: PathBuf,
+}
+impl DiffConfigCommandArgs {
+    fn from_args(args: &ArgMatches) -> DiffConfigCommandArgs {
+        DiffConfigCommandArgs {
+            // unwrap is OK here, the argument is required:
+            other: PathBuf::from(args.value_of(ARG_OTHER).unwrap()),
+        }
+    }
+}
+
+/// The paths of the mappings of two configs, split by the config they are found in.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct ConfigDiff {
+    /// mapped only by the current config.
+    only_current: Vec<PathBuf>,
+    /// mapped only by the other config.
+    only_other: Vec<PathBuf>,
+    /// mapped by both configs.
+    common: Vec<PathBuf>,
+    /// the subset of `common` whose options differ between both configs.
+    different_options: Vec<PathBuf>,
+}
+
+/// command handler for the `diff-config` sub-command
+/// see `dotfiles diff-config -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let DiffConfigCommandArgs { other } = DiffConfigCommandArgs::from_args(args);
+    let current_config = AppConfig::from_config_file(global_args, false)?;
+    let other_config = AppConfig::from_path(&other, global_args.collect_errors)?;
+    let diff = diff_mappings(&current_config.mappings, &other_config.mappings);
+
+    if global_args.json {
+        let output = to_json(&diff, global_args.json_pretty)
+            .map_err(|err| AppError::FsOther(format!("Failed to serialize diff: {}", err)))?;
+        println!("{}", output);
+        return Ok(());
+    }
+
+    print_paths("Only in your config:", &diff.only_current, |_| {
+        "-".red().to_string()
+    });
+    print_paths(
+        &format!("Only in {}:", other.display()),
+        &diff.only_other,
+        |_| "+".green().to_string(),
+    );
+    print_paths("In both configs:", &diff.common, |path| {
+        if diff.different_options.iter().any(|other| other == path) {
+            "~".yellow().to_string()
+        } else {
+            " ".to_string()
+        }
+    });
+    if !diff.different_options.is_empty() {
+        println!("Mappings marked with ~ use different options.");
+    }
+
+    Ok(())
+}
+
+/// prints the given title and paths, each prefixed with the marker returned by `marker`.
+/// Nothing is printed if there are no paths.
+fn print_paths(title: &str, paths: &[PathBuf], marker: impl Fn(&Path) -> String) {
+    if paths.is_empty() {
+        return;
+    }
+    println!("{}", title.bold());
+    for path in paths {
+        println!("{} {}", marker(path), path.display());
+    }
+}
+
+/// compares the given mappings by their paths. All returned lists are sorted by path.
+fn diff_mappings(current: &[Mapping], other: &[Mapping]) -> ConfigDiff {
+    let find = |mappings: &[Mapping], path: &Path| {
+        mappings
+            .iter()
+            .find(|mapping| mapping.path == path)
+            .cloned()
+    };
+    let mut diff = ConfigDiff {
+        only_current: vec![],
+        only_other: vec![],
+        common: vec![],
+        different_options: vec![],
+    };
+
+    for mapping in current {
+        match find(other, &mapping.path) {
+            Some(other_mapping) => {
+                diff.common.push(mapping.path.clone());
+                if other_mapping != *mapping {
+                    diff.different_options.push(mapping.path.clone());
+                }
+            }
+            None => diff.only_current.push(mapping.path.clone()),
+        }
+    }
+    diff.only_other = other
+        .iter()
+        .filter(|mapping| find(current, &mapping.path).is_none())
+        .map(|mapping| mapping.path.clone())
+        .collect();
+
+    for paths in [
+        &mut diff.only_current,
+        &mut diff.only_other,
+        &mut diff.common,
+        &mut diff.different_options,
+    ] {
+        paths.sort();
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_mappings, ConfigDiff};
+    use crate::config::{Manage, Mapping};
+    use std::path::PathBuf;
+
+    #[test]
+    fn diff_mappings_splits_paths_by_config() {
+        let current = vec![
+            Mapping::new(PathBuf::from(".vimrc")),
+            Mapping::new(PathBuf::from(".bashrc")),
+            Mapping::new(PathBuf::from(".config/nvim")),
+        ];
+        let other = vec![
+            Mapping::new(PathBuf::from(".zshrc")),
+            Mapping {
+                manage: Manage::Once,
+                ..Mapping::new(PathBuf::from(".vimrc"))
+            },
+            Mapping::new(PathBuf::from(".config/nvim")),
+        ];
+
+        assert_eq!(
+            diff_mappings(&current, &other),
+            ConfigDiff {
+                only_current: vec![PathBuf::from(".bashrc")],
+                only_other: vec![PathBuf::from(".zshrc")],
+                common: vec![PathBuf::from(".config/nvim"), PathBuf::from(".vimrc")],
+                different_options: vec![PathBuf::from(".vimrc")],
+            }
+        );
+    }
+}
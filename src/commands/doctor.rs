@@ -0,0 +1,279 @@
+use super::{
+    status::{explain_state, get_entry_states, LinkState},
+    CommandResult,
+};
+use crate::{
+    cli::GlobalArgs,
+    config::AppConfig,
+    errors::AppError,
+    files::{get_config_file_path, get_home_dir},
+};
+use clap::{App, ArgMatches, SubCommand};
+use colored::*;
+use dirs::config_dir;
+use std::path::PathBuf;
+
+pub const CMD_IDENTIFIER: &str = "doctor";
+const CMD_ABOUT: &str = r#"
+Diagnoses your whole setup and prints each check as PASS, FAIL or WARN together with a hint how to fix it:
+- your home and config directory can be resolved
+- the dotfiles directory exists
+- the dotfiles config exists and is valid
+- every mapping exists in the dotfiles directory
+- no mapping is blocked by a conflicting file
+- every mapping is linked (WARN only)
+Checks depending on a failed check are skipped. Exits with status 1 if any check fails.
+"#;
+
+/// returns the clap definition for the doctor sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT)
+}
+
+/// The result of a single check of `doctor`.
+#[derive(Debug)]
+struct Check {
+    /// what has been checked, eg. `dotfiles directory`.
+    title: &'static str,
+    /// the checked value if it passed, otherwise the problem and a hint how to fix it.
+    result: Result<String, (String, String)>,
+    /// if false, a failure is only reported as warning and does not change the exit status.
+    critical: bool,
+}
+impl Check {
+    fn pass<S: Into<String>>(title: &'static str, value: S) -> Check {
+        Check {
+            title,
+            result: Ok(value.into()),
+            critical: true,
+        }
+    }
+
+    fn fail<S: Into<String>, H: Into<String>>(title: &'static str, problem: S, hint: H) -> Check {
+        Check {
+            title,
+            result: Err((problem.into(), hint.into())),
+            critical: true,
+        }
+    }
+}
+
+/// command handler for the `doctor` sub-command
+/// see `dotfiles doctor -h` for an overview.
+/// Like `env`, it receives the CLI arguments of the app, because failing to resolve the global arguments
+/// is one of the problems it reports.
+pub fn run(cli_args: &ArgMatches) -> CommandResult {
+    let checks = get_checks(GlobalArgs::from_cli_args(cli_args));
+    for check in &checks {
+        match (&check.result, check.critical) {
+            (Ok(value), _) => println!("{} {}: {}", "PASS".green(), check.title, value),
+            (Err((problem, hint)), critical) => {
+                let label = if critical {
+                    "FAIL".red()
+                } else {
+                    "WARN".yellow()
+                };
+                println!("{} {}: {}", label, check.title, problem);
+                println!("     {}", hint.dimmed());
+            }
+        }
+    }
+
+    match checks
+        .iter()
+        .any(|check| check.critical && check.result.is_err())
+    {
+        true => Err(AppError::ExitStatus(1)),
+        false => Ok(()),
+    }
+}
+
+/// runs all checks based on the given global arguments, which may have failed to resolve.
+/// Stops at the first failed check other checks depend on.
+fn get_checks(global_args: Result<GlobalArgs, AppError>) -> Vec<Check> {
+    let mut checks = vec![];
+    let home_dir = match get_home_dir() {
+        Ok(home_dir) => {
+            checks.push(Check::pass(
+                "home directory",
+                home_dir.display().to_string(),
+            ));
+            home_dir
+        }
+        Err(err) => {
+            checks.push(Check::fail(
+                "home directory",
+                err.to_string(),
+                "Make sure the HOME environment variable is set.",
+            ));
+            return checks;
+        }
+    };
+    checks.push(match config_dir() {
+        Some(dir) => Check::pass("config directory", dir.display().to_string()),
+        None => Check::fail(
+            "config directory",
+            "could not be resolved",
+            "Make sure the XDG_CONFIG_HOME or HOME environment variable is set.",
+        ),
+    });
+
+    let global_args = match global_args.and_then(|args| args.require_dotfiles_root().map(|_| args))
+    {
+        Ok(global_args) => {
+            checks.push(Check::pass(
+                "dotfiles directory",
+                global_args.dotfiles_root.display().to_string(),
+            ));
+            global_args
+        }
+        Err(err) => {
+            checks.push(Check::fail(
+                "dotfiles directory",
+                err.to_string(),
+                "Provide an existing directory via -r or DOTFILES_ROOT, or run `dotfiles init` or `dotfiles clone` to create one.",
+            ));
+            return checks;
+        }
+    };
+
+    let config = match load_config(&global_args) {
+        Ok((path, config)) => {
+            checks.push(Check::pass("dotfiles config", path.display().to_string()));
+            config
+        }
+        Err(check) => {
+            checks.push(check);
+            return checks;
+        }
+    };
+
+    let states: Vec<(PathBuf, LinkState)> =
+        match get_entry_states(&global_args, &config, &home_dir, None) {
+            Ok((entries, states)) => entries
+                .into_iter()
+                .map(|(path, _)| path)
+                .zip(states)
+                .collect(),
+            Err(err) => {
+                checks.push(Check::fail(
+                    "mappings",
+                    err.to_string(),
+                    "Make sure your dotfiles and home directory are readable.",
+                ));
+                return checks;
+            }
+        };
+    checks.push(check_states("mappings exist", &states, "invalid", true));
+    checks.push(check_states("no conflicts", &states, "conflict", true));
+    checks.push(check_states("mappings linked", &states, "unlinked", false));
+
+    checks
+}
+
+/// reads and validates the config file, reporting all problems at once.
+/// Returns the path of the config file and the config or the failed check.
+fn load_config(global_args: &GlobalArgs) -> Result<(PathBuf, AppConfig), Check> {
+    let title = "dotfiles config";
+    let path = get_config_file_path(global_args)
+        .map_err(|err| Check::fail(title, err.to_string(), "Check your -c argument."))?;
+    if !path.exists() {
+        return Err(Check::fail(
+            title,
+            format!("{} does not exist", path.display()),
+            "Run `dotfiles init` to create an empty config or `dotfiles add <PATH>` to add your first dotfile.",
+        ));
+    }
+    let config = AppConfig::from_path_with_profile(&path, true, global_args.profile.as_deref())
+        .map_err(|err| {
+            Check::fail(
+                title,
+                err.to_string(),
+                "Fix the reported problems by hand or run `dotfiles repair-config` if the config_version is invalid.",
+            )
+        })?;
+
+    Ok((path, config))
+}
+
+/// checks that none of the given entries is in the state with the given name.
+/// The hint is the remediation of the state, see `explain_state`.
+fn check_states(
+    title: &'static str,
+    states: &[(PathBuf, LinkState)],
+    state_name: &str,
+    critical: bool,
+) -> Check {
+    let paths: Vec<String> = states
+        .iter()
+        .filter(|(_, state)| state.is_any_of(&[state_name]))
+        .map(|(path, _)| path.display().to_string())
+        .collect();
+    let check = if paths.is_empty() {
+        Check::pass(title, format!("no {} entries", state_name))
+    } else {
+        // safe, all states have an explanation:
+        let (_, remediation) = explain_state(state_name).unwrap();
+        Check::fail(
+            title,
+            format!("{} {}: {}", paths.len(), state_name, paths.join(", ")),
+            remediation,
+        )
+    };
+
+    Check { critical, ..check }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_checks;
+    use crate::{cli::GlobalArgs, errors::AppError};
+    use std::fs;
+
+    #[test]
+    fn get_checks_skips_checks_depending_on_failed_ones() {
+        let checks = get_checks(Err(AppError::CliMissingCommand));
+        let last = checks.last().unwrap();
+        assert_eq!(last.title, "dotfiles directory");
+        assert!(last.result.is_err());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().to_owned(),
+            ..Default::default()
+        };
+        let checks = get_checks(Ok(global_args));
+        let last = checks.last().unwrap();
+        assert_eq!(last.title, "dotfiles config");
+        assert!(last.result.is_err());
+    }
+
+    #[test]
+    fn get_checks_reports_invalid_mappings_and_conflicts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_dir = tmp.path().join(".config/dotfiles");
+        fs::create_dir_all(&config_dir).unwrap();
+        let config_path = config_dir.join("config.toml");
+        fs::write(
+            &config_path,
+            "config_version = 2\nmappings = [\".missing\"]\n",
+        )
+        .unwrap();
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().to_owned(),
+            config_file: Some(config_path),
+            ..Default::default()
+        };
+
+        let checks = get_checks(Ok(global_args));
+        let check = |title: &str| checks.iter().find(|check| check.title == title).unwrap();
+        assert!(check("dotfiles config").result.is_ok());
+        assert!(check("mappings exist").critical);
+        assert!(matches!(
+            &check("mappings exist").result,
+            Err((problem, _)) if problem == "1 invalid: .missing"
+        ));
+        assert!(check("no conflicts").result.is_ok());
+        assert!(!check("mappings linked").critical);
+    }
+}
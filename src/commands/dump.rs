@@ -0,0 +1,148 @@
+use super::{
+    status::{
+        explain_state, get_dotfiles_source, get_entry_states, is_dir, EntryMetadata, LinkState,
+        PROBLEM_STATES,
+    },
+    CommandResult,
+};
+use crate::{
+    cli::{to_json, GlobalArgs},
+    config::AppConfig,
+    errors::AppError,
+    files::{count_files, get_config_file_path, get_home_dir},
+};
+use clap::{App, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+pub const CMD_IDENTIFIER: &str = "dump";
+const CMD_ABOUT: &str = r#"
+Prints a snapshot of your dotfiles as a single JSON document, meant to be consumed by other tools.
+The document contains the loaded configuration, every entry of your dotfiles directory with its state
+and metadata, and a list of problems: all invalid, conflicting and unmapped entries.
+"#;
+
+/// returns the clap definition for the dump sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT)
+}
+
+/// command handler for the `dump` sub-command
+/// see `dotfiles dump -h` for an overview.
+pub fn run(_args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let home_dir = get_home_dir()?;
+    let (entries, states) = get_entry_states(global_args, &config, &home_dir, None)?;
+    let entries: Vec<(PathBuf, LinkState)> = entries
+        .into_iter()
+        .map(|(path, _)| path)
+        .zip(states)
+        .collect();
+
+    let document = json!({
+        "dotfiles_root": global_args.dotfiles_root,
+        "home_dir": home_dir,
+        "hostname": global_args.hostname,
+        "config_file": get_config_file_path(global_args)?,
+        "config": config,
+        "entries": entries
+            .iter()
+            .map(|(path, state)| entry_to_json(global_args, path, state))
+            .collect::<Vec<_>>(),
+        "problems": get_problems(&entries),
+    });
+    let output = to_json(&document, global_args.json_pretty)
+        .map_err(|err| AppError::FsOther(format!("Failed to serialize dump: {}", err)))?;
+    println!("{}", output);
+
+    Ok(())
+}
+
+/// returns the JSON representation of the given entry of the dotfiles directory and its state.
+fn entry_to_json(global_args: &GlobalArgs, path: &Path, state: &LinkState) -> Value {
+    let source = get_dotfiles_source(global_args, path);
+    let metadata = EntryMetadata::from_path(&source).map(|metadata| {
+        json!({
+            "kind": metadata.kind,
+            "size": metadata.size,
+            "modified": metadata
+                .modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+            // the number of files for directories, see `status --count-dir-contents`:
+            "file_count": is_dir(&source).then(|| count_files(&source)),
+        })
+    });
+
+    json!({
+        "path": path,
+        "state": state.name(),
+        "state_path": state.path(),
+        "source": source,
+        "metadata": metadata,
+    })
+}
+
+/// returns all entries in a state which needs the attention of the user, including how to resolve it.
+fn get_problems(entries: &[(PathBuf, LinkState)]) -> Vec<Value> {
+    entries
+        .iter()
+        .filter(|(_, state)| PROBLEM_STATES.contains(&state.name()))
+        .map(|(path, state)| {
+            // safe, all problem states are explained:
+            let (meaning, remediation) = explain_state(state.name()).unwrap();
+            json!({
+                "path": path,
+                "state": state.name(),
+                "message": meaning,
+                "remediation": remediation,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_problems;
+    use crate::commands::status::LinkState;
+    use std::path::PathBuf;
+
+    #[test]
+    fn get_problems_lists_invalid_conflicting_and_unmapped_entries() {
+        let entries = vec![
+            (PathBuf::from(".a"), LinkState::Linked),
+            (
+                PathBuf::from(".b"),
+                LinkState::Invalid(PathBuf::from("/d/.b")),
+            ),
+            (
+                PathBuf::from(".c"),
+                LinkState::ConflictNoLink(PathBuf::from("/h/.c")),
+            ),
+            (PathBuf::from(".d"), LinkState::Unmapped),
+            (PathBuf::from(".e"), LinkState::Unlinked),
+        ];
+
+        let problems: Vec<(String, String)> = get_problems(&entries)
+            .iter()
+            .map(|problem| {
+                (
+                    problem["path"].as_str().unwrap().to_string(),
+                    problem["state"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            problems,
+            vec![
+                (".b".to_string(), "invalid".to_string()),
+                (".c".to_string(), "conflict".to_string()),
+                (".d".to_string(), "unmapped".to_string()),
+            ]
+        );
+    }
+}
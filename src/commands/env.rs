@@ -0,0 +1,190 @@
+use super::CommandResult;
+use crate::{
+    cli::{is_json, is_json_pretty, to_json, GlobalArgs},
+    errors::AppError,
+    files::{create_symlink_for, get_config_file_path, get_home_dir, get_settings_file_path},
+};
+use clap::{App, ArgMatches, SubCommand};
+use colored::*;
+use dirs::config_dir;
+use serde_json::{json, Map, Value};
+use std::{env, fs, path::Path, process};
+
+pub const CMD_IDENTIFIER: &str = "env";
+const CMD_ABOUT: &str = r#"
+Prints what dotfiles resolved on this machine: the platform, your home and config directory, the settings file,
+the dotfiles directory and config file and whether symlinks can be created, which is probed by creating
+a temporary symlink. Problems resolving a value are printed instead of stopping at the first one.
+Exits with status 1 if any value could not be resolved. This is the first thing to run if something does not work.
+"#;
+
+/// returns the clap definition for the env sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT)
+}
+
+/// A single resolved value of the environment, either its description or the reason why it could not be resolved.
+type Check = (&'static str, Result<String, String>);
+
+/// command handler for the `env` sub-command
+/// see `dotfiles env -h` for an overview.
+/// In contrast to other commands, it receives the CLI arguments of the app instead of the parsed global arguments,
+/// because failing to resolve them is one of the problems it reports.
+pub fn run(cli_args: &ArgMatches) -> CommandResult {
+    let checks = get_checks(GlobalArgs::from_cli_args(cli_args));
+    let failed = checks.iter().any(|(_, result)| result.is_err());
+
+    if is_json(cli_args) {
+        let output: Map<String, Value> = checks
+            .into_iter()
+            .map(|(name, result)| {
+                let value = match result {
+                    Ok(value) => json!({ "value": value }),
+                    Err(error) => json!({ "error": error }),
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        let output = to_json(&output, is_json_pretty(cli_args))
+            .map_err(|err| AppError::FsOther(format!("Failed to serialize env: {}", err)))?;
+        println!("{}", output);
+    } else {
+        for (name, result) in checks {
+            match result {
+                Ok(value) => println!("{:<16} {}", name, value),
+                Err(error) => println!("{:<16} {}", name, error.red()),
+            }
+        }
+    }
+
+    match failed {
+        true => Err(AppError::ExitStatus(1)),
+        false => Ok(()),
+    }
+}
+
+/// resolves all values printed by `env` based on the given global arguments, which may have failed to resolve.
+fn get_checks(global_args: Result<GlobalArgs, AppError>) -> Vec<Check> {
+    let mut checks: Vec<Check> = vec![
+        (
+            "platform",
+            Ok(format!("{} {}", env::consts::OS, env::consts::ARCH)),
+        ),
+        (
+            "home_dir",
+            get_home_dir()
+                .map(|dir| describe_dir(&dir))
+                .map_err(|err| err.to_string()),
+        ),
+        (
+            "config_dir",
+            config_dir().map(|dir| describe_dir(&dir)).ok_or_else(|| {
+                AppError::FsUserLocation("config directory".to_string()).to_string()
+            }),
+        ),
+        (
+            "settings_file",
+            get_settings_file_path()
+                .map(|path| describe_file(&path))
+                .map_err(|err| err.to_string()),
+        ),
+    ];
+    match global_args {
+        Ok(global_args) => {
+            checks.push((
+                "dotfiles_root",
+                Ok(describe_dir(&global_args.dotfiles_root)),
+            ));
+            checks.push((
+                "config_file",
+                get_config_file_path(&global_args)
+                    .map(|path| describe_file(&path))
+                    .map_err(|err| err.to_string()),
+            ));
+            checks.push((
+                "hostname",
+                global_args
+                    .hostname
+                    .ok_or_else(|| "the hostname is not valid unicode".to_string()),
+            ));
+        }
+        Err(err) => checks.push(("dotfiles_root", Err(err.to_string()))),
+    }
+    checks.push((
+        "symlinks",
+        probe_symlink(&env::temp_dir())
+            .map(|_| "can be created".to_string())
+            .map_err(|err| err.to_string()),
+    ));
+
+    checks
+}
+
+/// returns the given directory and whether it exists.
+fn describe_dir(dir: &Path) -> String {
+    match dir.is_dir() {
+        true => dir.display().to_string(),
+        false => format!("{} (does not exist)", dir.display()),
+    }
+}
+
+/// returns the given path and whether it exists.
+fn describe_file(path: &Path) -> String {
+    match path.exists() {
+        true => path.display().to_string(),
+        false => format!("{} (does not exist)", path.display()),
+    }
+}
+
+/// creates a symlink in the given directory and removes it again to find out if symlinks can be created,
+/// eg. on Windows without developer mode. The symlink points to the directory itself.
+fn probe_symlink(dir: &Path) -> Result<(), AppError> {
+    let link = dir.join(format!("dotfiles-symlink-probe-{}", process::id()));
+    create_symlink_for(&link, dir)?;
+    fs::remove_file(&link).map_err(|err| {
+        AppError::FsOther(format!(
+            "Failed to remove the probe symlink {}: {}",
+            link.display(),
+            err
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_checks, probe_symlink};
+    use crate::{cli::GlobalArgs, errors::AppError};
+    use std::fs;
+
+    #[test]
+    fn probe_symlink_leaves_nothing_behind() {
+        let tmp = tempfile::tempdir().unwrap();
+        probe_symlink(tmp.path()).unwrap();
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+
+        assert!(probe_symlink(&tmp.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn get_checks_reports_unresolved_dotfiles_root() {
+        let checks = get_checks(Err(AppError::CliMissingCommand));
+        let names: Vec<_> = checks.iter().map(|(name, _)| *name).collect();
+        assert!(names.contains(&"platform"));
+        assert!(!names.contains(&"config_file"));
+        assert!(matches!(
+            checks.iter().find(|(name, _)| *name == "dotfiles_root"),
+            Some((_, Err(_)))
+        ));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().to_owned(),
+            ..Default::default()
+        };
+        let checks = get_checks(Ok(global_args));
+        assert!(matches!(
+            checks.iter().find(|(name, _)| *name == "dotfiles_root"),
+            Some((_, Ok(root))) if *root == tmp.path().display().to_string()
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+use super::{print_planned_changes, CommandResult};
+use crate::{
+    cli::GlobalArgs,
+    config::AppConfig,
+    errors::AppError,
+    files::{get_config_file_path, get_cwd, get_settings_file_path},
+    git::run_git,
+    settings::Settings,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::json;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const CMD_IDENTIFIER: &str = "init";
+const CMD_ABOUT: &str = r#"
+Scaffolds a new dotfiles repository: creates the dotfiles directory if it does not exist yet and writes
+an empty dotfiles config file to it. Use --git to initialize a git repository in it as well.
+The dotfiles directory is the given directory or, if none is given, the one provided via -r, environment
+variable or settings file. An existing config file is never overwritten. Use --set-default to remember the
+directory as `dotfiles_root` in your settings file, so following commands find it without -r.
+"#;
+const ARG_DIRECTORY: &str = "DIRECTORY";
+const ARG_GIT: &str = "git";
+const ARG_SET_DEFAULT: &str = "set-default";
+
+/// returns the clap definition for the init sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(Arg::with_name(ARG_DIRECTORY).help(
+            "the dotfiles directory to scaffold. Defaults to the dotfiles directory provided via -r, environment variable or settings file.",
+        ))
+        .arg(
+            Arg::with_name(ARG_GIT)
+                .long(ARG_GIT)
+                .help("runs `git init` in the dotfiles directory, unless it already is a git repository"),
+        )
+        .arg(
+            Arg::with_name(ARG_SET_DEFAULT)
+                .long(ARG_SET_DEFAULT)
+                .help("stores the dotfiles directory as `dotfiles_root` in your settings file"),
+        )
+}
+
+/// returns the absolute directory to scaffold if one is provided, which is used as dotfiles root.
+pub fn get_target_dir(args: &ArgMatches) -> Result<Option<PathBuf>, AppError> {
+    match args.value_of(ARG_DIRECTORY) {
+        Some(dir) if Path::new(dir).is_absolute() => Ok(Some(PathBuf::from(dir))),
+        Some(dir) => Ok(Some(get_cwd()?.join(dir))),
+        None => Ok(None),
+    }
+}
+
+struct InitCommandArgs {
+    /// if true, a git repository is initialized in the dotfiles directory.
+    git: bool,
+    /// if true, the dotfiles directory is stored in the settings file, see `Settings::save_dotfiles_root`.
+    set_default: bool,
+}
+impl InitCommandArgs {
+    fn from_args(args: &ArgMatches) -> InitCommandArgs {
+        InitCommandArgs {
+            git: args.is_present(ARG_GIT),
+            set_default: args.is_present(ARG_SET_DEFAULT),
+        }
+    }
+}
+
+/// command handler for the `init` sub-command
+/// see `dotfiles init -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let InitCommandArgs { git, set_default } = InitCommandArgs::from_args(args);
+    let root = &global_args.dotfiles_root;
+    let config_path = get_config_file_path(global_args)?;
+    if config_path.exists() {
+        return Err(AppError::CliInvalidArgValue(
+            ARG_DIRECTORY.to_string(),
+            format!(
+                "{} already contains a dotfiles config at {}. Use `dotfiles status` to see its mappings.",
+                root.display(),
+                config_path.display()
+            ),
+        ));
+    }
+    let init_git = git && !root.join(".git").exists();
+    let settings_path = match set_default {
+        true => Some(get_settings_file_path()?),
+        false => None,
+    };
+
+    if global_args.dry_run {
+        let mut planned = vec![];
+        if !root.exists() {
+            planned.push((
+                format!("creating directory {}", root.display()),
+                json!({ "action": "create_dir", "path": root }),
+            ));
+        }
+        planned.push((
+            format!("writing an empty config file {}", config_path.display()),
+            json!({ "action": "write_config", "path": config_path }),
+        ));
+        if init_git {
+            planned.push((
+                format!("initializing a git repository in {}", root.display()),
+                json!({ "action": "git_init", "path": root }),
+            ));
+        }
+        if let Some(settings_path) = &settings_path {
+            planned.push((
+                format!(
+                    "saving {} as your dotfiles directory in {}",
+                    root.display(),
+                    settings_path.display()
+                ),
+                json!({ "action": "save_settings", "path": settings_path, "dotfiles_root": root }),
+            ));
+        }
+        return print_planned_changes(global_args, planned);
+    }
+
+    fs::create_dir_all(root).map_err(|err| {
+        AppError::FsOther(format!("failed to create {}: {}", root.display(), err))
+    })?;
+    AppConfig::default().to_config_file(global_args)?;
+    println!("Created {}", config_path.display());
+    if init_git {
+        run_git(root, &["init"])?;
+        println!("Initialized a git repository in {}", root.display());
+    }
+    if let Some(settings_path) = settings_path {
+        Settings::save_dotfiles_root(&settings_path, root)?;
+        println!(
+            "Saved {} as your dotfiles directory in {}",
+            root.display(),
+            settings_path.display()
+        );
+    }
+    println!("Use `dotfiles add <PATH>` to add your first dotfile. Have a good time!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use crate::{cli::GlobalArgs, config::AppConfig, files::get_config_file_path};
+    use clap::ArgMatches;
+
+    #[test]
+    fn run_creates_an_empty_config_but_never_overwrites_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().join("dotfiles"),
+            config_file: Some(tmp.path().join("dotfiles/.config/dotfiles/config.toml")),
+            ..Default::default()
+        };
+        let args = ArgMatches::default();
+
+        run(&args, &global_args).unwrap();
+        let config_path = get_config_file_path(&global_args).unwrap();
+        let config = AppConfig::from_path(&config_path, false).unwrap();
+        assert!(config.mappings.is_empty());
+
+        assert!(run(&args, &global_args).is_err());
+    }
+}
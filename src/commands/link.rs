@@ -0,0 +1,610 @@
+use super::{print_planned_changes, status, CommandResult};
+use crate::{
+    cli::{
+        backup_arg, on_conflict_arg, on_conflict_from_args, only_arg, only_from_args,
+        resolve_only_number, GlobalArgs, Operation,
+    },
+    config::{AppConfig, ConflictStrategy, Mapping},
+    errors::AppError,
+    files::{
+        copy_permissions, create_parent_dir, create_symlink_for, get_backup_path, get_home_dir,
+        get_shadowing_ancestor, move_path, remove_path, write_file,
+    },
+    template::render_mapping,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use colored::*;
+use serde_json::json;
+use status::{
+    get_dotfiles_entries, get_dotfiles_entry_state, get_dotfiles_source, is_in_scope,
+    DotfilesEntryState, LinkState,
+};
+use std::path::{Path, PathBuf};
+
+pub const CMD_IDENTIFIER: &str = "link";
+const CMD_ABOUT: &str = r#"
+Creates the symlinks for all mappings in your dotfiles configuration which are not linked yet.
+Mappings which do not exist in your dotfiles directory are skipped to avoid creating broken symlinks,
+unless --allow-missing is provided.
+If a file or directory which is not a symlink already exists at the location in your home directory,
+the behavior is controlled via --on-conflict or `on_conflict` in your dotfiles config:
+skip     : leave the existing file untouched and report it.
+backup   : move the existing file to <path>.bak.<timestamp> and create the symlink.
+overwrite: delete the existing file and create the symlink.
+prompt   : (default) ask whether to back up the existing file and create the symlink.
+Mappings with `mode = "copy"` are copied instead of symlinked, rendering them as template if
+`template = true` is set. Stale copies are replaced with the current version. A copy is stale if its
+content differs, changed permissions alone do not update it.
+"#;
+const ARG_ALLOW_MISSING: &str = "allow-missing";
+
+/// returns the clap definition for the link sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(on_conflict_arg())
+        .arg(backup_arg())
+        .arg(only_arg())
+        .arg(
+            Arg::with_name(ARG_ALLOW_MISSING)
+                .long(ARG_ALLOW_MISSING)
+                .help(
+                "creates symlinks for mappings which do not exist in your dotfiles directory yet",
+            ),
+        )
+}
+
+/// Options controlling how `link_mappings` creates symlinks.
+#[derive(Debug, Clone, Copy)]
+pub struct LinkOptions<'a> {
+    /// what to do if a file blocks the creation of a symlink
+    pub on_conflict: ConflictStrategy,
+    /// if true, symlinks to mappings which do not exist in the dotfiles directory are created as well
+    pub allow_missing: bool,
+    /// if set, only mappings in the subtree starting at this path are linked
+    pub only: Option<&'a Path>,
+}
+impl Default for LinkOptions<'_> {
+    fn default() -> Self {
+        LinkOptions {
+            on_conflict: ConflictStrategy::Skip,
+            allow_missing: false,
+            only: None,
+        }
+    }
+}
+
+struct LinkCommandArgs {
+    /// the conflict strategy, if provided via CLI.
+    on_conflict: Option<ConflictStrategy>,
+    allow_missing: bool,
+    /// the path prefix of the mappings to link, if provided via CLI.
+    only: Option<PathBuf>,
+}
+impl LinkCommandArgs {
+    fn from_args(args: &ArgMatches) -> Result<LinkCommandArgs, AppError> {
+        Ok(LinkCommandArgs {
+            on_conflict: on_conflict_from_args(args)?,
+            allow_missing: args.is_present(ARG_ALLOW_MISSING),
+            only: only_from_args(args),
+        })
+    }
+}
+
+/// command handler for the `link` sub-command
+/// see `dotfiles link -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let LinkCommandArgs {
+        on_conflict,
+        allow_missing,
+        only,
+    } = LinkCommandArgs::from_args(args)?;
+    let config = AppConfig::from_config_file(global_args, false)?;
+    // allows relinking a single entry of the last numbered status, eg. `dotfiles link --only 3`:
+    let only = resolve_only_number(only, global_args, &config)?;
+    let options = LinkOptions {
+        on_conflict: config.get_conflict_strategy(on_conflict),
+        allow_missing,
+        only: only.as_deref(),
+    };
+
+    let linked = link_mappings(global_args, &config, &get_home_dir()?, options)?;
+    if linked == 0 && !global_args.dry_run {
+        println!("Nothing left to be done. Have a good time!");
+    }
+
+    Ok(())
+}
+
+/// Creates the missing symlinks in the given home directory for all mappings of the given config
+/// and prints the action taken per entry. Returns the number of created symlinks.
+/// In a dry run, nothing is changed and the planned actions are printed instead, see `print_planned_changes`.
+pub fn link_mappings(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    options: LinkOptions,
+) -> Result<usize, AppError> {
+    let entries = get_dotfiles_entries(global_args, config)?;
+
+    let backup_dir = global_args.settings.get_backup_dir(home_dir);
+    let mut linked = 0;
+    let mut planned = vec![];
+    for entry in entries.iter().filter(|(path, state)| {
+        (*state == DotfilesEntryState::Mapped || *state == DotfilesEntryState::Invalid)
+            && is_in_scope(path, options.only)
+    }) {
+        let state =
+            get_dotfiles_entry_state(global_args, config, entry, home_dir).map_err(|err| {
+                AppError::FsOther(format!("Failed to read your linked dotfiles: {}", err))
+            })?;
+        let home_path = home_dir.join(&entry.0);
+        let mapping = config.get_mapping(&entry.0);
+        let is_copy = mapping.is_some_and(Mapping::is_copy);
+        let is_reverse = mapping.is_some_and(Mapping::is_reverse);
+        // reverse mappings never use host-specific files:
+        let dotfiles_path = if is_reverse {
+            global_args.dotfiles_root.join(&entry.0)
+        } else {
+            get_dotfiles_source(global_args, &entry.0)
+        };
+        // linking into a directory which is a symlink into the dotfiles directory would
+        // create the link or copy inside the dotfiles directory instead:
+        if let Some(ancestor) =
+            get_shadowing_ancestor(home_dir, &global_args.dotfiles_root, &entry.0)
+                .filter(|_| !is_reverse)
+        {
+            // a dry run only prints the planned changes:
+            if !global_args.dry_run {
+                println!(
+                    "{} {} {} is a symlink into your dotfiles directory",
+                    "SHADOWED".yellow(),
+                    entry.0.display(),
+                    ancestor.display()
+                );
+            }
+            continue;
+        }
+        if global_args.dry_run {
+            let action = get_planned_action(&state, is_reverse, is_copy, options, &home_path);
+            if let Some(action) = action {
+                let description = match action {
+                    "link" if is_reverse => format!(
+                        "creating symlink {} -> {}",
+                        dotfiles_path.display(),
+                        home_path.display()
+                    ),
+                    "link" if is_copy => format!(
+                        "copying {} -> {}",
+                        dotfiles_path.display(),
+                        home_path.display()
+                    ),
+                    "link" => format!(
+                        "creating symlink {} -> {}",
+                        home_path.display(),
+                        dotfiles_path.display()
+                    ),
+                    "update" => format!("updating copy {}", home_path.display()),
+                    "relink" => format!(
+                        "replacing stale symlink {} -> {}",
+                        home_path.display(),
+                        dotfiles_path.display()
+                    ),
+                    "prompt" => format!("asking whether to back up {}", home_path.display()),
+                    "backup" => format!("backing up {} and linking it", home_path.display()),
+                    _ => format!("deleting {} and linking it", home_path.display()),
+                };
+                let descriptor = json!({
+                    "action": action,
+                    "path": entry.0,
+                    "home_path": home_path,
+                    "source": dotfiles_path,
+                });
+                planned.push((description, descriptor));
+            }
+            continue;
+        }
+        let install = || match mapping {
+            Some(mapping) if mapping.is_reverse() => {
+                create_parent_dir(&dotfiles_path)?;
+                create_symlink_for(&dotfiles_path, &home_path)
+            }
+            Some(mapping) if mapping.is_copy() => {
+                let content = render_mapping(global_args, home_dir, mapping, &dotfiles_path)
+                    .map_err(|err| {
+                        AppError::FsOther(format!(
+                            "Failed to render {}: {}",
+                            dotfiles_path.display(),
+                            err
+                        ))
+                    })?;
+                write_file(&home_path, &content)?;
+                // keeps the copies of scripts executable:
+                copy_permissions(&dotfiles_path, &home_path)
+            }
+            _ => create_symlink_for(&home_path, &dotfiles_path),
+        };
+
+        let (action, note) = match state {
+            LinkState::Unlinked => {
+                install()?;
+                ("LINKED  ".green(), String::new())
+            }
+            // copies are generated from the dotfiles directory, so they are replaced without asking.
+            // The copy may also be a symlink left over from a previous symlink mapping,
+            // which must be removed first to not write through it:
+            LinkState::Stale(_) => {
+                remove_path(&home_path)?;
+                install()?;
+                ("UPDATED ".blue(), String::new())
+            }
+            // the symlink points to a path which no longer exists in the dotfiles directory,
+            // so nothing is lost by replacing it:
+            LinkState::StaleLink(_) => {
+                remove_path(&home_path)?;
+                install()?;
+                ("RELINKED".green(), String::new())
+            }
+            // the file of a reverse mapping lives in the home directory, linking it would create a broken symlink:
+            LinkState::Invalid(_) if is_reverse => {
+                println!(
+                    "{} {} {} does not exist",
+                    "MISSING ".purple(),
+                    entry.0.display(),
+                    home_path.display()
+                );
+                continue;
+            }
+            // files blocking the symlink of a reverse mapping are part of the dotfiles directory,
+            // they are never moved or deleted automatically:
+            LinkState::ConflictNoLink(_) if is_reverse => {
+                println!(
+                    "{} {} {} exists and is not a symlink",
+                    "SKIPPED ".yellow(),
+                    entry.0.display(),
+                    dotfiles_path.display()
+                );
+                continue;
+            }
+            // the mapping does not exist in the dotfiles directory, linking it would create a broken symlink.
+            // Copies cannot be created at all:
+            LinkState::Invalid(_) if !options.allow_missing || is_copy => {
+                println!(
+                    "{} {} {} does not exist",
+                    "MISSING ".purple(),
+                    entry.0.display(),
+                    dotfiles_path.display()
+                );
+                continue;
+            }
+            LinkState::Invalid(_) if home_path.symlink_metadata().is_err() => {
+                create_symlink_for(&home_path, &dotfiles_path)?;
+                (
+                    "LINKED  ".green(),
+                    format!("{} does not exist yet", dotfiles_path.display()),
+                )
+            }
+            LinkState::ConflictNoLink(_) => {
+                match resolve_conflict_strategy(global_args, options, &home_path)? {
+                    // a prompt is always resolved to one of the other strategies:
+                    ConflictStrategy::Skip | ConflictStrategy::Prompt => {
+                        println!(
+                            "{} {} {} exists and is not a symlink",
+                            "SKIPPED ".yellow(),
+                            entry.0.display(),
+                            home_path.display()
+                        );
+                        continue;
+                    }
+                    ConflictStrategy::Backup => {
+                        let backup_path =
+                            get_backup_path(&home_path, home_dir, backup_dir.as_deref());
+                        create_parent_dir(&backup_path)?;
+                        move_path(&home_path, &backup_path)?;
+                        install()?;
+                        (
+                            "BACKUP  ".blue(),
+                            format!("moved existing file to {}", backup_path.display()),
+                        )
+                    }
+                    ConflictStrategy::Overwrite => {
+                        remove_path(&home_path)?;
+                        install()?;
+                        (
+                            "REPLACED".red(),
+                            format!("deleted existing file {}", home_path.display()),
+                        )
+                    }
+                }
+            }
+            _ => continue,
+        };
+
+        linked += 1;
+        println!("{} {} {}", action, entry.0.display(), note);
+    }
+
+    if global_args.dry_run {
+        let count = planned.len();
+        print_planned_changes(global_args, planned)?;
+        return Ok(count);
+    }
+    Ok(linked)
+}
+
+/// returns the action `link_mappings` would take for an entry in the given state, if any:
+/// `link`, `update`, `relink` or the conflict strategy applied to a blocking file. Used in a dry run.
+fn get_planned_action(
+    state: &LinkState,
+    is_reverse: bool,
+    is_copy: bool,
+    options: LinkOptions,
+    home_path: &Path,
+) -> Option<&'static str> {
+    match state {
+        LinkState::Unlinked => Some("link"),
+        LinkState::Stale(_) => Some("update"),
+        LinkState::StaleLink(_) => Some("relink"),
+        LinkState::Invalid(_)
+            if !is_reverse
+                && !is_copy
+                && options.allow_missing
+                && home_path.symlink_metadata().is_err() =>
+        {
+            Some("link")
+        }
+        LinkState::ConflictNoLink(_) if !is_reverse => match options.on_conflict {
+            ConflictStrategy::Skip => None,
+            ConflictStrategy::Prompt => Some("prompt"),
+            ConflictStrategy::Backup => Some("backup"),
+            ConflictStrategy::Overwrite => Some("overwrite"),
+        },
+        _ => None,
+    }
+}
+
+/// returns the conflict strategy to apply to the given existing file.
+/// If the user should be prompted, they decide between backing up the file and skipping it.
+fn resolve_conflict_strategy(
+    global_args: &GlobalArgs,
+    options: LinkOptions,
+    home_path: &Path,
+) -> Result<ConflictStrategy, AppError> {
+    Ok(match options.on_conflict {
+        ConflictStrategy::Prompt => {
+            let question = format!(
+                "{} exists and is not a symlink. Move it to a backup and create the symlink?",
+                home_path.display()
+            );
+            // backing up the existing file moves it:
+            if global_args.confirm_operations(&[Operation::Move], question, false)? {
+                ConflictStrategy::Backup
+            } else {
+                ConflictStrategy::Skip
+            }
+        }
+        strategy => strategy,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{link_mappings, LinkOptions};
+    use crate::{
+        cli::GlobalArgs,
+        commands::{
+            setup_test_dirs,
+            status::{get_dotfiles_entry_state, DotfilesEntryState, LinkState},
+        },
+        config::{AppConfig, ConflictStrategy, LinkDirection, LinkMode, Mapping},
+        files::symlink,
+    };
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn link_mappings_skips_mappings_shadowed_by_symlinked_parents() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(dotfiles_root.join("config")).unwrap();
+        fs::create_dir_all(dotfiles_root.join(".config")).unwrap();
+        fs::write(dotfiles_root.join(".config/app.toml"), "").unwrap();
+        // ~/.config is linked to a different directory of the dotfiles:
+        symlink(dotfiles_root.join("config"), home.join(".config")).unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".config/app.toml"))],
+            ..Default::default()
+        };
+
+        let linked = link_mappings(&global_args, &config, &home, LinkOptions::default()).unwrap();
+        assert_eq!(linked, 0);
+        assert!(dotfiles_root
+            .join("config/app.toml")
+            .symlink_metadata()
+            .is_err());
+    }
+
+    #[test]
+    fn link_mappings_renders_and_refreshes_copied_templates() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".fontrc"), "host = {{ hostname }}").unwrap();
+        let global_args = GlobalArgs {
+            hostname: Some("laptop".to_string()),
+            ..global_args
+        };
+        let config = AppConfig {
+            mappings: vec![Mapping {
+                mode: LinkMode::Copy,
+                template: true,
+                ..Mapping::new(PathBuf::from(".fontrc"))
+            }],
+            ..Default::default()
+        };
+        let entry = (PathBuf::from(".fontrc"), DotfilesEntryState::Mapped);
+        let state = || get_dotfiles_entry_state(&global_args, &config, &entry, &home).unwrap();
+
+        assert!(matches!(state(), LinkState::Unlinked));
+        let linked = link_mappings(&global_args, &config, &home, LinkOptions::default()).unwrap();
+        assert_eq!(linked, 1);
+        assert!(!home
+            .join(".fontrc")
+            .symlink_metadata()
+            .unwrap()
+            .is_symlink());
+        assert_eq!(
+            fs::read_to_string(home.join(".fontrc")).unwrap(),
+            "host = laptop"
+        );
+        assert!(matches!(state(), LinkState::Linked));
+
+        fs::write(dotfiles_root.join(".fontrc"), "host = {{ hostname }}!").unwrap();
+        assert!(matches!(state(), LinkState::Stale(_)));
+        link_mappings(&global_args, &config, &home, LinkOptions::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(home.join(".fontrc")).unwrap(),
+            "host = laptop!"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_mappings_keeps_the_permissions_of_copies() {
+        use std::os::unix::fs::PermissionsExt;
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join("setup.sh"), "#!/bin/sh").unwrap();
+        fs::set_permissions(
+            dotfiles_root.join("setup.sh"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping {
+                mode: LinkMode::Copy,
+                ..Mapping::new(PathBuf::from("setup.sh"))
+            }],
+            ..Default::default()
+        };
+
+        link_mappings(&global_args, &config, &home, LinkOptions::default()).unwrap();
+        let mode = fs::metadata(home.join("setup.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+
+        // only the content of copies is compared:
+        fs::set_permissions(home.join("setup.sh"), fs::Permissions::from_mode(0o644)).unwrap();
+        let entry = (PathBuf::from("setup.sh"), DotfilesEntryState::Mapped);
+        let state = get_dotfiles_entry_state(&global_args, &config, &entry, &home).unwrap();
+        assert!(matches!(state, LinkState::Linked));
+    }
+
+    #[test]
+    fn link_mappings_creates_reverse_symlinks_in_dotfiles_directory() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(home.join(".local")).unwrap();
+        fs::write(home.join(".local/history"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping {
+                    direction: LinkDirection::Reverse,
+                    ..Mapping::new(PathBuf::from(".local/history"))
+                },
+                Mapping {
+                    direction: LinkDirection::Reverse,
+                    ..Mapping::new(PathBuf::from(".missing"))
+                },
+            ],
+            ..Default::default()
+        };
+        let state = |path: &str, entry_state| {
+            let entry = (PathBuf::from(path), entry_state);
+            get_dotfiles_entry_state(&global_args, &config, &entry, &home).unwrap()
+        };
+
+        assert!(matches!(
+            state(".local/history", DotfilesEntryState::Invalid),
+            LinkState::Unlinked
+        ));
+        let linked = link_mappings(&global_args, &config, &home, LinkOptions::default()).unwrap();
+        assert_eq!(linked, 1);
+        assert_eq!(
+            fs::read_link(dotfiles_root.join(".local/history")).unwrap(),
+            home.join(".local/history")
+        );
+        assert!(home
+            .join(".local/history")
+            .symlink_metadata()
+            .unwrap()
+            .is_file());
+        assert!(matches!(
+            state(".local/history", DotfilesEntryState::Mapped),
+            LinkState::Linked
+        ));
+        assert!(matches!(
+            state(".missing", DotfilesEntryState::Invalid),
+            LinkState::Invalid(path) if path == home.join(".missing")
+        ));
+    }
+
+    #[test]
+    fn link_mappings_does_not_change_anything_in_dry_runs() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        fs::write(dotfiles_root.join(".bashrc"), "").unwrap();
+        fs::write(home.join(".bashrc"), "existing").unwrap();
+        let global_args = GlobalArgs {
+            dry_run: true,
+            ..global_args
+        };
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".bashrc")),
+            ],
+            ..Default::default()
+        };
+        let options = LinkOptions {
+            on_conflict: ConflictStrategy::Overwrite,
+            ..Default::default()
+        };
+
+        let planned = link_mappings(&global_args, &config, &home, options).unwrap();
+        assert_eq!(planned, 2);
+        assert!(home.join(".vimrc").symlink_metadata().is_err());
+        assert_eq!(
+            fs::read_to_string(home.join(".bashrc")).unwrap(),
+            "existing"
+        );
+    }
+
+    #[test]
+    fn link_mappings_skips_missing_targets_unless_allowed() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".missing")),
+            ],
+            ..Default::default()
+        };
+
+        let linked = link_mappings(&global_args, &config, &home, LinkOptions::default()).unwrap();
+        assert_eq!(linked, 1);
+        assert_eq!(
+            fs::read_link(home.join(".vimrc")).unwrap(),
+            dotfiles_root.join(".vimrc")
+        );
+        assert!(home.join(".missing").symlink_metadata().is_err());
+
+        let options = LinkOptions {
+            allow_missing: true,
+            ..Default::default()
+        };
+        let linked = link_mappings(&global_args, &config, &home, options).unwrap();
+        assert_eq!(linked, 1);
+        assert_eq!(
+            fs::read_link(home.join(".missing")).unwrap(),
+            dotfiles_root.join(".missing")
+        );
+    }
+}
@@ -4,23 +4,160 @@
 /// 2. The name of the command (eg. `CMD_IDENTIFIER`) which is used to register the command and match on the CLI arguments.
 /// 3. Some kind of `run` function which accepts the sub-command arguments and global arguments passed via CLI
 ///    and executes the program of the command.
-use crate::{cli::GlobalArgs, AppError};
+use crate::{
+    cli::{to_json, verbosity, GlobalArgs},
+    files::get_home_dir,
+    log, trace, AppError,
+};
 use clap::ArgMatches;
+use serde_json::{json, Value};
 
 pub mod add;
+pub mod clone;
+pub mod completions;
+pub mod diff_config;
+pub mod doctor;
+pub mod dump;
+pub mod env;
+pub mod init;
+pub mod link;
+pub mod open;
+pub mod orphans;
+pub mod prune;
+pub mod reconcile;
+pub mod remove;
+pub mod repair_config;
+pub mod rewrite_links;
+pub mod rollback;
+pub mod shellenv;
 pub mod status;
+pub mod suggest;
+pub mod unlink;
 
 pub type CommandResult = Result<(), AppError>;
 
 /// runs the appropriate command based on the provided process arguments
 pub fn run_command(cli_args: &ArgMatches) -> CommandResult {
-    let global_args = GlobalArgs::from_cli_args(&cli_args)?;
-
+    log::init(verbosity(cli_args));
+    // `env` and `doctor` report problems resolving the global args instead of failing on them:
     match cli_args.subcommand() {
+        (env::CMD_IDENTIFIER, _) => return env::run(cli_args),
+        (doctor::CMD_IDENTIFIER, _) => return doctor::run(cli_args),
+        (completions::CMD_IDENTIFIER, Some(cmd_args)) => return completions::run(cmd_args),
+        _ => (),
+    }
+    // `clone` and `init` create the dotfiles directory, so it cannot be resolved beforehand:
+    let global_args = match cli_args.subcommand() {
+        (clone::CMD_IDENTIFIER, Some(cmd_args)) => {
+            GlobalArgs::from_cli_args_with_root(cli_args, Some(clone::get_target_dir(cmd_args)?))?
+        }
+        (init::CMD_IDENTIFIER, Some(cmd_args)) => {
+            GlobalArgs::from_cli_args_with_root(cli_args, init::get_target_dir(cmd_args)?)?
+        }
+        _ => GlobalArgs::from_cli_args(cli_args)?,
+    };
+    if global_args.no_color {
+        colored::control::set_override(false);
+    }
+    // legacy Windows consoles print ANSI codes as-is unless virtual terminal processing can be enabled:
+    #[cfg(windows)]
+    if colored::control::set_virtual_terminal(true).is_err() {
+        colored::control::set_override(false);
+    }
+    if let Some(trace_file) = &global_args.trace_file {
+        let redacted_home = if global_args.trace_redact_home {
+            Some(get_home_dir()?)
+        } else {
+            None
+        };
+        trace::init(trace_file, redacted_home)?;
+    }
+    trace::event(
+        "args",
+        json!({
+            "command": cli_args.subcommand_name(),
+            "dotfiles_root": global_args.dotfiles_root,
+            "hostname": global_args.hostname,
+        }),
+    );
+
+    let result = match cli_args.subcommand() {
         (status::CMD_IDENTIFIER, Some(cmd_args)) => status::run(cmd_args, &global_args),
         (add::CMD_IDENTIFIER, Some(cmd_args)) => add::run(cmd_args, &global_args),
+        (link::CMD_IDENTIFIER, Some(cmd_args)) => link::run(cmd_args, &global_args),
+        (reconcile::CMD_IDENTIFIER, Some(cmd_args)) => reconcile::run(cmd_args, &global_args),
+        (rollback::CMD_IDENTIFIER, Some(cmd_args)) => rollback::run(cmd_args, &global_args),
+        (suggest::CMD_IDENTIFIER, Some(cmd_args)) => suggest::run(cmd_args, &global_args),
+        (open::CMD_IDENTIFIER, Some(cmd_args)) => open::run(cmd_args, &global_args),
+        (shellenv::CMD_IDENTIFIER, Some(cmd_args)) => shellenv::run(cmd_args, &global_args),
+        (orphans::CMD_IDENTIFIER, Some(cmd_args)) => orphans::run(cmd_args, &global_args),
+        (prune::CMD_IDENTIFIER, Some(cmd_args)) => prune::run(cmd_args, &global_args),
+        (dump::CMD_IDENTIFIER, Some(cmd_args)) => dump::run(cmd_args, &global_args),
+        (diff_config::CMD_IDENTIFIER, Some(cmd_args)) => diff_config::run(cmd_args, &global_args),
+        (repair_config::CMD_IDENTIFIER, Some(cmd_args)) => {
+            repair_config::run(cmd_args, &global_args)
+        }
+        (unlink::CMD_IDENTIFIER, Some(cmd_args)) => unlink::run(cmd_args, &global_args),
+        (remove::CMD_IDENTIFIER, Some(cmd_args)) => remove::run(cmd_args, &global_args),
+        (clone::CMD_IDENTIFIER, Some(cmd_args)) => clone::run(cmd_args, &global_args),
+        (init::CMD_IDENTIFIER, Some(cmd_args)) => init::run(cmd_args, &global_args),
+        (rewrite_links::CMD_IDENTIFIER, Some(cmd_args)) => {
+            rewrite_links::run(cmd_args, &global_args)
+        }
         ("", _) => Err(AppError::CliMissingCommand),
         // should never be called thanks to `clap`s own validation:
         (cmd, _) => Err(AppError::CliInvalidCommand(cmd.to_string())),
+    };
+
+    trace::event(
+        "result",
+        json!({ "error": result.as_ref().err().map(|err| err.to_string()) }),
+    );
+    result
+}
+
+/// prints the changes a command would apply instead of applying them, see `--dry-run`.
+/// Each change consists of a human-readable description and a JSON descriptor, which are printed
+/// as a single JSON array if `--json` is set.
+pub fn print_planned_changes(
+    global_args: &GlobalArgs,
+    changes: Vec<(String, Value)>,
+) -> CommandResult {
+    if global_args.json {
+        let descriptors: Vec<Value> = changes.into_iter().map(|(_, value)| value).collect();
+        let output = to_json(&descriptors, global_args.json_pretty)
+            .map_err(|err| AppError::FsOther(format!("Failed to serialize changes: {}", err)))?;
+        println!("{}", output);
+    } else if changes.is_empty() {
+        println!("Dry run: nothing would be done.");
+    } else {
+        println!("Dry run: following things would be done:");
+        for (description, _) in changes {
+            println!("- {}", description);
+        }
     }
+
+    Ok(())
+}
+
+/// creates an empty home directory containing an empty dotfiles directory, like `~/dotfiles`,
+/// in a new temporary directory for tests. Returns the temporary directory, which removes both once dropped,
+/// the home and dotfiles directory and global arguments using this dotfiles directory.
+#[cfg(test)]
+pub fn setup_test_dirs() -> (
+    tempfile::TempDir,
+    std::path::PathBuf,
+    std::path::PathBuf,
+    GlobalArgs,
+) {
+    let tmp = tempfile::tempdir().unwrap();
+    let home = tmp.path().join("home");
+    let dotfiles_root = home.join("dotfiles");
+    std::fs::create_dir_all(&dotfiles_root).unwrap();
+    let global_args = GlobalArgs {
+        dotfiles_root: dotfiles_root.clone(),
+        ..Default::default()
+    };
+
+    (tmp, home, dotfiles_root, global_args)
 }
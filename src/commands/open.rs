@@ -0,0 +1,158 @@
+use super::{status::get_dotfiles_source, CommandResult};
+use crate::{
+    cli::GlobalArgs,
+    config::AppConfig,
+    errors::AppError,
+    files::{get_cwd, get_home_dir, normalize_lexically},
+    last_status::resolve_entry_number,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+pub const CMD_IDENTIFIER: &str = "open";
+const CMD_ABOUT: &str = r#"
+Opens the file in your dotfiles directory which is linked to the given path in your home directory
+using the editor set in $EDITOR (or `vi` if it is not set).
+The path may also point into a mapped directory, eg. `~/.config/nvim/init.vim` if `.config/nvim` is mapped.
+Instead of a path, the number of an entry of the last `dotfiles status --number` can be given.
+Fails if the given path is not part of any mapping.
+"#;
+const ARG_PATH: &str = "path";
+/// the editor used if `$EDITOR` is not set.
+const DEFAULT_EDITOR: &str = "vi";
+
+/// returns the clap definition for the open sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT).arg(
+        Arg::with_name(ARG_PATH)
+            .required(true)
+            .help("the path in your home directory to open the tracked version of or the number of an entry of the last `status --number`"),
+    )
+}
+
+struct OpenCommandArgs {
+    /// the value as given, which may be the number of an entry of the last numbered status.
+    value: String,
+    /// the absolute, lexically normalized path to open.
+    path: PathBuf,
+}
+impl OpenCommandArgs {
+    fn from_args(args: &ArgMatches) -> Result<OpenCommandArgs, AppError> {
+        // unwrap is OK here, the argument is required:
+        let value = args.value_of(ARG_PATH).unwrap().to_string();
+        // symlinks are not resolved, otherwise a linked path would already point into the dotfiles directory:
+        let path = normalize_lexically(&get_cwd()?.join(&value));
+
+        Ok(OpenCommandArgs { value, path })
+    }
+}
+
+/// command handler for the `open` sub-command
+/// see `dotfiles open -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let OpenCommandArgs { value, path } = OpenCommandArgs::from_args(args)?;
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let home_dir = get_home_dir()?;
+
+    let repo_file = match resolve_entry_number(&value, ARG_PATH, global_args, &config)? {
+        Some(mapped) => get_dotfiles_source(global_args, &mapped),
+        None => resolve_repo_file(global_args, &config, &home_dir, &path).ok_or_else(|| {
+            AppError::CliInvalidArgValue(
+                ARG_PATH.to_string(),
+                format!("{} is not tracked by any mapping", path.display()),
+            )
+        })?,
+    };
+
+    open_in_editor(&repo_file)
+}
+
+/// returns the file in the dotfiles directory the given absolute path is tracked by.
+/// The path may either be the mapped path itself or be located inside a mapped directory,
+/// both in the home and dotfiles directory. Returns `None` if the path is not part of any mapping.
+fn resolve_repo_file(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    path: &Path,
+) -> Option<PathBuf> {
+    // the dotfiles directory is often located inside the home directory, so it is checked first:
+    let rel_path = path
+        .strip_prefix(&global_args.dotfiles_root)
+        .or_else(|_| path.strip_prefix(home_dir))
+        .ok()?;
+    let mapping = config.get_covering_mapping(rel_path)?;
+    // safe, the mapping is an ancestor of the path:
+    let nested_path = rel_path.strip_prefix(&mapping.path).unwrap();
+
+    Some(get_dotfiles_source(global_args, &mapping.path).join(nested_path))
+}
+
+/// opens the given file in the editor set in `$EDITOR` and waits until it is closed.
+/// The editor may contain arguments, eg. `code --wait`.
+fn open_in_editor(file: &Path) -> Result<(), AppError> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or(DEFAULT_EDITOR);
+    let command_line = format!("{} {}", editor, file.display());
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(file)
+        .status()
+        .map_err(|err| AppError::ExternalCommand(command_line.clone(), err.to_string()))?;
+    if !status.success() {
+        return Err(AppError::ExternalCommand(
+            command_line,
+            format!("editor exited with {}", status),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_repo_file;
+    use crate::{
+        cli::GlobalArgs,
+        config::{AppConfig, Mapping},
+    };
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn resolve_repo_file_resolves_mapped_and_nested_paths() {
+        let home = Path::new("/home/me");
+        let global_args = GlobalArgs {
+            dotfiles_root: PathBuf::from("/home/me/dotfiles"),
+            ..Default::default()
+        };
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".config/nvim")),
+            ],
+            ..Default::default()
+        };
+        let resolve = |path: &str| resolve_repo_file(&global_args, &config, home, Path::new(path));
+
+        assert_eq!(
+            resolve("/home/me/.vimrc"),
+            Some(PathBuf::from("/home/me/dotfiles/.vimrc"))
+        );
+        assert_eq!(
+            resolve("/home/me/.config/nvim/lua/init.lua"),
+            Some(PathBuf::from("/home/me/dotfiles/.config/nvim/lua/init.lua"))
+        );
+        assert_eq!(
+            resolve("/home/me/dotfiles/.config/nvim/init.vim"),
+            Some(PathBuf::from("/home/me/dotfiles/.config/nvim/init.vim"))
+        );
+        assert_eq!(resolve("/home/me/.config/fish/config.fish"), None);
+        assert_eq!(resolve("/etc/hosts"), None);
+    }
+}
@@ -0,0 +1,281 @@
+use super::{
+    prune::{find_prunable_links, PrunableLink},
+    status::{get_dotfiles_entries, DotfilesEntryState},
+    CommandResult,
+};
+use crate::{
+    cli::{max_depth_arg, max_depth_from_args, to_json, GlobalArgs, Operation},
+    config::AppConfig,
+    errors::AppError,
+    files::{abbreviate_path, get_config_file_path, get_home_dir, remove_path},
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+pub const CMD_IDENTIFIER: &str = "orphans";
+const CMD_ABOUT: &str = r#"
+Lists all files and directories in your dotfiles repository which are not referenced by any mapping
+and will therefore never be linked. These are the entries shown as UNMAPPED by `status`.
+The directory of your dotfiles config and version control directories such as `.git` are never listed.
+Use `dotfiles add` to map them or --delete to remove them from your dotfiles repository.
+With --report-unmanaged-links, symlinks in your home directory pointing into your dotfiles repository
+which are not backed by any mapping are listed instead, eg. created manually or left over from a removed mapping.
+"#;
+const ARG_DELETE: &str = "delete";
+const ARG_REPORT_UNMANAGED_LINKS: &str = "report-unmanaged-links";
+/// directories of version control systems, which are part of the dotfiles directory but never orphaned.
+const VCS_DIRS: [&str; 3] = [".git", ".hg", ".svn"];
+
+/// returns the clap definition for the orphans sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(
+            Arg::with_name(ARG_DELETE)
+                .long(ARG_DELETE)
+                .help("deletes all orphaned paths from your dotfiles repository after confirmation"),
+        )
+        .arg(
+            Arg::with_name(ARG_REPORT_UNMANAGED_LINKS)
+                .long(ARG_REPORT_UNMANAGED_LINKS)
+                .conflicts_with(ARG_DELETE)
+                .help("lists symlinks in your home directory pointing into your dotfiles repository which are not backed by any mapping. Use `dotfiles prune` to remove them."),
+        )
+        .arg(max_depth_arg().requires(ARG_REPORT_UNMANAGED_LINKS))
+}
+
+struct OrphansCommandArgs {
+    delete: bool,
+    /// the number of directory levels of the home directory to scan for unmanaged links, if they should be listed.
+    report_unmanaged_links: Option<usize>,
+}
+impl OrphansCommandArgs {
+    fn from_args(args: &ArgMatches) -> Result<OrphansCommandArgs, AppError> {
+        Ok(OrphansCommandArgs {
+            delete: args.is_present(ARG_DELETE),
+            report_unmanaged_links: match args.is_present(ARG_REPORT_UNMANAGED_LINKS) {
+                true => Some(max_depth_from_args(args)?),
+                false => None,
+            },
+        })
+    }
+}
+
+/// command handler for the `orphans` sub-command
+/// see `dotfiles orphans -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let OrphansCommandArgs {
+        delete,
+        report_unmanaged_links,
+    } = OrphansCommandArgs::from_args(args)?;
+    let config = AppConfig::from_config_file(global_args, false)?;
+    if let Some(max_depth) = report_unmanaged_links {
+        return report_unmanaged_link_paths(global_args, &config, max_depth);
+    }
+    let config_file = get_config_file_path(global_args)?;
+    let orphans = get_orphans(global_args, &config, &config_file)?;
+
+    // the orphaned paths are printed as JSON array, nothing is deleted:
+    if global_args.json {
+        let output = to_json(&orphans, global_args.json_pretty)
+            .map_err(|err| AppError::FsOther(format!("Failed to serialize orphans: {}", err)))?;
+        println!("{}", output);
+        return Ok(());
+    }
+    if orphans.is_empty() {
+        println!("Every entry of your dotfiles directory is mapped.");
+        return Ok(());
+    }
+
+    for orphan in &orphans {
+        println!("{}", orphan.display());
+    }
+    if delete
+        && global_args.confirm_operations(
+            &[Operation::Delete],
+            format!(
+                "Delete these {} paths from your dotfiles directory?",
+                orphans.len()
+            ),
+            false,
+        )?
+    {
+        for orphan in &orphans {
+            remove_path(&global_args.dotfiles_root.join(orphan))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// prints all symlinks in the home directory up to the given depth which point into the dotfiles directory,
+/// but are not backed by any mapping, see `get_unmanaged_links`.
+fn report_unmanaged_link_paths(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    max_depth: usize,
+) -> CommandResult {
+    let home_dir = get_home_dir()?;
+    let links = get_unmanaged_links(global_args, config, &home_dir, max_depth)?;
+
+    if global_args.json {
+        let entries: Vec<_> = links
+            .iter()
+            .map(|link| json!({ "path": link.path, "target": link.target }))
+            .collect();
+        let output = to_json(&entries, global_args.json_pretty).map_err(|err| {
+            AppError::FsOther(format!("Failed to serialize unmanaged links: {}", err))
+        })?;
+        println!("{}", output);
+        return Ok(());
+    }
+    if links.is_empty() {
+        println!("Every symlink into your dotfiles directory is backed by a mapping.");
+        return Ok(());
+    }
+    for link in &links {
+        println!(
+            "{} -> {}",
+            link.path.display(),
+            abbreviate_path(&link.target, &global_args.dotfiles_root, &home_dir)
+        );
+    }
+
+    Ok(())
+}
+
+/// returns all symlinks in the given home directory up to the given depth which point into the dotfiles directory,
+/// but whose path is not covered by any mapping. These are the dangling and orphaned links of `prune`, except for
+/// the ones of existing mappings.
+fn get_unmanaged_links(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    max_depth: usize,
+) -> Result<Vec<PrunableLink>, AppError> {
+    Ok(
+        find_prunable_links(global_args, config, home_dir, max_depth)?
+            .into_iter()
+            .filter(|link| config.get_covering_mapping(&link.path).is_none())
+            .collect(),
+    )
+}
+
+/// returns the relative paths of all entries in the dotfiles directory which are not referenced by any mapping.
+/// Entries containing the given config file, its included config files and version control directories
+/// are excluded.
+fn get_orphans(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    config_file: &Path,
+) -> Result<Vec<PathBuf>, AppError> {
+    let entries = get_dotfiles_entries(global_args, config)?;
+    let config_files: Vec<&Path> = std::iter::once(config_file)
+        .chain(
+            config
+                .included
+                .iter()
+                .map(|included| included.path.as_path()),
+        )
+        .collect();
+
+    Ok(entries
+        .into_iter()
+        .filter(|(_, state)| *state == DotfilesEntryState::Unmapped)
+        .map(|(path, _)| path)
+        .filter(|path| {
+            let path = global_args.dotfiles_root.join(path);
+            !config_files.iter().any(|file| file.starts_with(&path))
+        })
+        .filter(|path| !VCS_DIRS.iter().any(|dir| path.starts_with(dir)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_orphans, get_unmanaged_links};
+    use crate::{
+        commands::setup_test_dirs,
+        config::{AppConfig, IncludedConfig, Mapping},
+        files::symlink,
+    };
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn get_unmanaged_links_skips_links_of_mappings_and_outside_of_the_dotfiles() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".zshrc"), "").unwrap();
+        // a mapping pointing to a renamed file is reported by `status`:
+        symlink(dotfiles_root.join(".bashrc-old"), home.join(".bashrc")).unwrap();
+        symlink(dotfiles_root.join(".zshrc"), home.join(".zshrc")).unwrap();
+        symlink(dotfiles_root.join(".inputrc"), home.join(".inputrc")).unwrap();
+        symlink(home.join("elsewhere"), home.join(".external")).unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".bashrc"))],
+            ..Default::default()
+        };
+
+        let links = get_unmanaged_links(&global_args, &config, &home, 3).unwrap();
+        let paths: Vec<_> = links.into_iter().map(|link| link.path).collect();
+
+        assert_eq!(
+            paths,
+            vec![PathBuf::from(".inputrc"), PathBuf::from(".zshrc")]
+        );
+    }
+
+    #[test]
+    fn get_orphans_lists_unmapped_entries_except_config_and_vcs() {
+        let (_tmp, _, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(dotfiles_root.join(".config/nvim")).unwrap();
+        fs::create_dir_all(dotfiles_root.join(".config/old-app")).unwrap();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        fs::write(dotfiles_root.join(".old-bashrc"), "").unwrap();
+        fs::create_dir_all(dotfiles_root.join(".config/dotfiles")).unwrap();
+        fs::write(dotfiles_root.join(".config/dotfiles/config.toml"), "").unwrap();
+        fs::create_dir_all(dotfiles_root.join(".git")).unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".config/nvim")),
+                Mapping::new(PathBuf::from(".missing")),
+            ],
+            ..Default::default()
+        };
+
+        let config_file = dotfiles_root.join(".config/dotfiles/config.toml");
+        let orphans = get_orphans(&global_args, &config, &config_file).unwrap();
+
+        assert_eq!(
+            orphans,
+            vec![
+                PathBuf::from(".config/old-app"),
+                PathBuf::from(".old-bashrc")
+            ]
+        );
+    }
+
+    #[test]
+    fn get_orphans_excludes_included_config_files() {
+        let (_tmp, _, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(dotfiles_root.join("includes")).unwrap();
+        fs::write(dotfiles_root.join("includes/work.toml"), "").unwrap();
+        fs::write(dotfiles_root.join("private.toml"), "").unwrap();
+        fs::write(dotfiles_root.join(".old-bashrc"), "").unwrap();
+        let included = |path: &str| IncludedConfig {
+            path: dotfiles_root.join(path),
+            writable: false,
+            mappings: vec![],
+        };
+        let config = AppConfig {
+            included: vec![included("includes/work.toml"), included("private.toml")],
+            ..Default::default()
+        };
+
+        let config_file = dotfiles_root.join(".config/dotfiles/config.toml");
+        let orphans = get_orphans(&global_args, &config, &config_file).unwrap();
+
+        assert_eq!(orphans, vec![PathBuf::from(".old-bashrc")]);
+    }
+}
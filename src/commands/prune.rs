@@ -0,0 +1,263 @@
+use super::{
+    print_planned_changes,
+    status::{get_dotfiles_entry_state, DotfilesEntryState, LinkState},
+    CommandResult,
+};
+use crate::{
+    cli::{max_depth_arg, max_depth_from_args, GlobalArgs, Operation},
+    config::AppConfig,
+    errors::AppError,
+    files::{abbreviate_path, get_home_dir, remove_path, resolve_symlink_target, scan_home_dir},
+    log,
+};
+use clap::{App, ArgMatches, SubCommand};
+use colored::*;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+pub const CMD_IDENTIFIER: &str = "prune";
+const CMD_ABOUT: &str = r#"
+Scans your home directory for symlinks into your dotfiles directory which are no longer needed
+and removes them after confirmation:
+  dangling: the symlink points to a path which does not exist in your dotfiles directory anymore.
+  orphaned: the symlink is not part of any mapping, eg. because the mapping has been removed.
+Symlinks pointing outside of your dotfiles directory and symlinks of mappings with `pinned = true` are never touched.
+Only the first levels of your home directory are scanned, see --max-depth.
+"#;
+
+/// Describes why a symlink in the home directory can be pruned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneReason {
+    /// the symlink points to a path in the dotfiles directory which does not exist.
+    Dangling,
+    /// the symlink points to an existing path in the dotfiles directory, but is not part of any mapping.
+    Orphaned,
+}
+impl PruneReason {
+    /// returns the lowercase name of the reason as printed and used in JSON output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PruneReason::Dangling => "dangling",
+            PruneReason::Orphaned => "orphaned",
+        }
+    }
+}
+
+/// A symlink in the home directory which can be pruned.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PrunableLink {
+    /// the path of the symlink relative to the home directory.
+    pub path: PathBuf,
+    /// the absolute path the symlink points to.
+    pub target: PathBuf,
+    /// why the symlink can be pruned.
+    pub reason: PruneReason,
+}
+
+/// returns the clap definition for the prune sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(max_depth_arg())
+}
+
+struct PruneCommandArgs {
+    max_depth: usize,
+}
+impl PruneCommandArgs {
+    fn from_args(args: &ArgMatches) -> Result<PruneCommandArgs, AppError> {
+        Ok(PruneCommandArgs {
+            max_depth: max_depth_from_args(args)?,
+        })
+    }
+}
+
+/// command handler for the `prune` sub-command
+/// see `dotfiles prune -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let PruneCommandArgs { max_depth } = PruneCommandArgs::from_args(args)?;
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let home_dir = get_home_dir()?;
+
+    let links = find_prunable_links(global_args, &config, &home_dir, max_depth)?;
+    // a dry run only prints the planned changes:
+    if global_args.dry_run {
+        let planned = links
+            .iter()
+            .map(|link| {
+                let home_path = home_dir.join(&link.path);
+                (
+                    format!(
+                        "deleting {} symlink {}",
+                        link.reason.name(),
+                        home_path.display()
+                    ),
+                    json!({
+                        "action": "prune",
+                        "reason": link.reason.name(),
+                        "home_path": home_path,
+                        "target": link.target,
+                    }),
+                )
+            })
+            .collect();
+        return print_planned_changes(global_args, planned);
+    }
+    if links.is_empty() {
+        println!("No dangling or orphaned symlinks found. Have a good time!");
+        return Ok(());
+    }
+
+    let mut removed = 0;
+    for link in &links {
+        let home_path = home_dir.join(&link.path);
+        println!(
+            "{} {} -> {}",
+            link.reason.name().to_uppercase().yellow(),
+            link.path.display(),
+            abbreviate_path(&link.target, &global_args.dotfiles_root, &home_dir)
+        );
+        if global_args.confirm_operations(
+            &[Operation::Symlink],
+            format!("Remove {}?", home_path.display()),
+            true,
+        )? {
+            remove_path(&home_path)?;
+            removed += 1;
+        }
+    }
+    println!("Removed {} symlink(s).", removed);
+
+    Ok(())
+}
+
+/// returns all symlinks in the given home directory up to the given depth which point into the dotfiles directory
+/// and are either dangling or orphaned, see `PruneReason`. Symlinks of mappings are only dangling if `status`
+/// reports them as stale links, copies and reverse mappings are never considered.
+pub fn find_prunable_links(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    max_depth: usize,
+) -> Result<Vec<PrunableLink>, AppError> {
+    let mut links = vec![];
+    for path in scan_home_dir(home_dir, max_depth, &[&global_args.dotfiles_root]) {
+        let home_path = home_dir.join(&path);
+        let is_symlink = home_path
+            .symlink_metadata()
+            .map(|meta| meta.file_type().is_symlink())
+            .unwrap_or(false);
+        if !is_symlink {
+            continue;
+        }
+        let target = resolve_symlink_target(&home_path).map_err(|err| {
+            AppError::FsOther(format!("Failed to read {}: {}", home_path.display(), err))
+        })?;
+        // symlinks to the dotfiles directory itself are not part of any mapping by design:
+        if !target.starts_with(&global_args.dotfiles_root) || target == global_args.dotfiles_root {
+            continue;
+        }
+
+        let reason = match config.get_covering_mapping(&path) {
+            // pinned mappings are only ever removed explicitly, see `dotfiles remove`:
+            Some(mapping) if mapping.pinned => continue,
+            Some(mapping)
+                if mapping.path == path && !mapping.is_copy() && !mapping.is_reverse() =>
+            {
+                let entry = (path.clone(), DotfilesEntryState::Mapped);
+                let state = get_dotfiles_entry_state(global_args, config, &entry, home_dir)
+                    .map_err(|err| {
+                        AppError::FsOther(format!("Failed to read your linked dotfiles: {}", err))
+                    })?;
+                match state {
+                    LinkState::StaleLink(_) => PruneReason::Dangling,
+                    _ => continue,
+                }
+            }
+            // symlinks inside of mapped directories are part of the mapping:
+            Some(_) => continue,
+            None if target.symlink_metadata().is_err() => PruneReason::Dangling,
+            None => PruneReason::Orphaned,
+        };
+        log::debug(format_args!(
+            "{} is {}, it points to {}",
+            path.display(),
+            reason.name(),
+            target.display()
+        ));
+        links.push(PrunableLink {
+            path,
+            target,
+            reason,
+        });
+    }
+
+    Ok(links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_prunable_links, PrunableLink, PruneReason};
+    use crate::{
+        commands::setup_test_dirs,
+        config::{AppConfig, Mapping},
+        files::symlink,
+    };
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn find_prunable_links_only_returns_dangling_and_orphaned_links_into_the_dotfiles() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(dotfiles_root.join(".config/app")).unwrap();
+        fs::create_dir_all(home.join(".config")).unwrap();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        fs::write(dotfiles_root.join(".bashrc"), "").unwrap();
+        fs::write(dotfiles_root.join(".zshrc"), "").unwrap();
+        // linked mapping:
+        symlink(dotfiles_root.join(".vimrc"), home.join(".vimrc")).unwrap();
+        // mapping pointing to a renamed file:
+        symlink(dotfiles_root.join(".bashrc-old"), home.join(".bashrc")).unwrap();
+        // removed mapping, whose file still exists:
+        symlink(dotfiles_root.join(".zshrc"), home.join(".zshrc")).unwrap();
+        symlink(dotfiles_root.join(".config/app"), home.join(".config/app")).unwrap();
+        // removed mapping, whose file has been deleted:
+        symlink(dotfiles_root.join(".inputrc"), home.join(".inputrc")).unwrap();
+        // never touched:
+        symlink(home.join("elsewhere"), home.join(".external")).unwrap();
+        symlink(&dotfiles_root, home.join("dots")).unwrap();
+        // pinned mapping pointing to a renamed file:
+        symlink(
+            dotfiles_root.join(".gitconfig-old"),
+            home.join(".gitconfig"),
+        )
+        .unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".bashrc")),
+                Mapping {
+                    pinned: true,
+                    ..Mapping::new(PathBuf::from(".gitconfig"))
+                },
+            ],
+            ..Default::default()
+        };
+
+        let links = find_prunable_links(&global_args, &config, &home, 3).unwrap();
+
+        let link = |path: &str, target: &str, reason| PrunableLink {
+            path: PathBuf::from(path),
+            target: dotfiles_root.join(target),
+            reason,
+        };
+        assert_eq!(
+            links,
+            vec![
+                link(".bashrc", ".bashrc-old", PruneReason::Dangling),
+                link(".config/app", ".config/app", PruneReason::Orphaned),
+                link(".inputrc", ".inputrc", PruneReason::Dangling),
+                link(".zshrc", ".zshrc", PruneReason::Orphaned),
+            ]
+        );
+    }
+}
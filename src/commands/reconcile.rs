@@ -0,0 +1,247 @@
+use super::{print_planned_changes, CommandResult};
+use crate::{
+    cli::{GlobalArgs, Operation},
+    config::{normalize_mapping, AppConfig, Mapping},
+    errors::AppError,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use colored::*;
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+};
+
+pub const CMD_IDENTIFIER: &str = "reconcile";
+const CMD_ABOUT: &str = r#"
+Updates the mappings in your dotfiles configuration to exactly match a list of desired mappings.
+Mappings missing in the configuration are added, mappings not part of the given list are removed,
+except for mappings with `pinned = true`, which are only removed by `dotfiles remove`.
+The desired mappings can be provided in one of the following formats:
+- one path per line (empty lines and lines starting with # are ignored)
+- a JSON array of paths, eg. [".vimrc", ".config/nvim"]
+- a TOML document containing a `mappings` array, such as the dotfiles configuration itself
+This command only updates the configuration. Use `link` afterwards to create missing symlinks.
+The changes are confirmed before the configuration is updated. Because the mappings are read from stdin,
+use --yes or --assume-yes-for mapping to update it without asking.
+"#;
+const ARG_MAPPINGS_FROM_STDIN: &str = "mappings-from-stdin";
+
+/// returns the clap definition for the reconcile sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT).arg(
+        Arg::with_name(ARG_MAPPINGS_FROM_STDIN)
+            .long(ARG_MAPPINGS_FROM_STDIN)
+            .required(true)
+            .help("reads the complete list of desired mappings from stdin"),
+    )
+}
+
+/// A TOML document containing the desired mappings, such as the dotfiles config itself.
+/// Mappings are read the same way as from the config, so they may be plain paths or tables with options.
+/// All other keys of the document are ignored.
+#[derive(Deserialize)]
+struct TomlMappings {
+    mappings: Vec<Mapping>,
+}
+
+/// command handler for the `reconcile` sub-command
+/// see `dotfiles reconcile -h` for an overview.
+pub fn run(_args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|err| AppError::FsOther(format!("Could not read from stdin: {}", err)))?;
+    let desired = parse_mappings(&input)?;
+
+    let mut config = AppConfig::from_config_file(global_args, true)?;
+    let current: Vec<PathBuf> = config.mappings.iter().map(|m| m.path.clone()).collect();
+    let desired_paths: Vec<PathBuf> = desired.iter().map(|m| m.path.clone()).collect();
+    let (added, mut removed) = get_mapping_diff(&current, &desired_paths);
+    // pinned mappings are only ever removed explicitly, see `dotfiles remove`:
+    removed.retain(|path| {
+        !config
+            .get_mapping(path)
+            .is_some_and(|mapping| mapping.pinned)
+    });
+
+    // keeps the order and options of the existing mappings and appends new ones in the given order:
+    for mapping in &removed {
+        config.remove_mapping(mapping);
+    }
+    // new mappings keep the options they were given with, eg. in a TOML document:
+    for mapping in desired
+        .into_iter()
+        .filter(|mapping| added.contains(&mapping.path))
+    {
+        config.add_mapping(mapping);
+    }
+    config.validate()?;
+
+    // a dry run only prints the planned changes:
+    if global_args.dry_run {
+        let planned = added
+            .iter()
+            .map(|path| {
+                (
+                    format!("adding mapping {}", path.display()),
+                    json!({ "action": "add_mapping", "path": path }),
+                )
+            })
+            .chain(removed.iter().map(|path| {
+                (
+                    format!("removing mapping {}", path.display()),
+                    json!({ "action": "remove_mapping", "path": path }),
+                )
+            }))
+            .collect();
+        return print_planned_changes(global_args, planned);
+    }
+    if added.is_empty() && removed.is_empty() {
+        println!("The configured mappings are already up to date.");
+        return Ok(());
+    }
+    for mapping in &added {
+        println!("{} {}", "+".green(), mapping.display());
+    }
+    for mapping in &removed {
+        println!("{} {}", "-".red(), mapping.display());
+    }
+    if global_args.confirm_operations(&[Operation::Mapping], "Update the configuration?", true)? {
+        config.to_config_file(global_args)?;
+    }
+
+    Ok(())
+}
+
+/// parses the desired mappings from the given input, which is either a JSON array,
+/// a TOML document containing `mappings` or a plain list of paths, one per line.
+/// Only mappings of TOML documents may use options, all others use the default options.
+fn parse_mappings(input: &str) -> Result<Vec<Mapping>, AppError> {
+    let parsed: Vec<Mapping> = if input.trim_start().starts_with('[') {
+        serde_json::from_str::<Vec<PathBuf>>(input)
+            .map_err(|err| {
+                AppError::CliInvalidArgValue(
+                    ARG_MAPPINGS_FROM_STDIN.to_string(),
+                    format!("the input is not a valid JSON array of paths: {}", err),
+                )
+            })?
+            .into_iter()
+            .map(|path| Mapping::new(normalize_mapping(path)))
+            .collect()
+    } else if looks_like_toml(input) {
+        toml::from_str::<TomlMappings>(input)
+            .map_err(|err| {
+                AppError::CliInvalidArgValue(
+                    ARG_MAPPINGS_FROM_STDIN.to_string(),
+                    format!(
+                        "the input is not a valid TOML document containing mappings: {}",
+                        err
+                    ),
+                )
+            })?
+            .mappings
+    } else {
+        input
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| Mapping::new(normalize_mapping(PathBuf::from(line))))
+            .collect()
+    };
+
+    let mut mappings: Vec<Mapping> = vec![];
+    for mapping in parsed {
+        if !mappings.iter().any(|m| m.path == mapping.path) {
+            mappings.push(mapping);
+        }
+    }
+
+    Ok(mappings)
+}
+
+/// returns true if the given input is meant to be a TOML document, ie. it contains a key/value pair or
+/// an array of tables. Such input is never read as a list of paths, which would plan to add each of its lines.
+fn looks_like_toml(input: &str) -> bool {
+    input.contains('=') || input.contains("[[")
+}
+
+/// returns the mappings which are only part of `desired` (to add)
+/// and the mappings which are only part of `current` (to remove).
+fn get_mapping_diff(current: &[PathBuf], desired: &[PathBuf]) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let added = desired
+        .iter()
+        .filter(|mapping| !current.contains(mapping))
+        .cloned()
+        .collect();
+    let removed = current
+        .iter()
+        .filter(|mapping| !desired.contains(mapping))
+        .cloned()
+        .collect();
+
+    (added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_mapping_diff, parse_mappings};
+    use crate::config::{Manage, Mapping};
+    use std::path::PathBuf;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn parse_mappings_supports_all_formats() {
+        let expected = vec![
+            Mapping::new(PathBuf::from(".vimrc")),
+            Mapping::new(PathBuf::from(".config/nvim")),
+        ];
+        assert_eq!(
+            parse_mappings("# comment\n./.vimrc\n\n.config/nvim\n").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_mappings(r#"[".vimrc", ".config/nvim", ".vimrc"]"#).unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_mappings("config_version = 1\nmappings = ['.vimrc', '.config/nvim']").unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_mappings_rejects_invalid_toml() {
+        assert!(parse_mappings("config_version = 2\nmappings = [\".vimrc\"").is_err());
+        assert!(parse_mappings("[[mappings]]\nmanage = \"once\"\n").is_err());
+    }
+
+    #[test]
+    fn parse_mappings_reads_table_mappings_of_a_config() {
+        let config = "config_version = 2\nmappings = [\".vimrc\", { path = \"./.bashrc\", manage = \"once\" }]\n";
+        assert_eq!(
+            parse_mappings(config).unwrap(),
+            vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping {
+                    manage: Manage::Once,
+                    ..Mapping::new(PathBuf::from(".bashrc"))
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn get_mapping_diff_returns_added_and_removed_mappings() {
+        let (added, removed) = get_mapping_diff(
+            &paths(&[".vimrc", ".bashrc"]),
+            &paths(&[".bashrc", ".config/nvim"]),
+        );
+        assert_eq!(added, paths(&[".config/nvim"]));
+        assert_eq!(removed, paths(&[".vimrc"]));
+    }
+}
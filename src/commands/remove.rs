@@ -0,0 +1,347 @@
+use super::{add::resolve_input_path, print_planned_changes, CommandResult};
+use crate::{
+    cli::{GlobalArgs, Operation},
+    config::{normalize_mapping, AppConfig},
+    errors::AppError,
+    files::{
+        create_parent_dir, get_cwd, get_home_dir, move_path, remove_path, resolve_symlink_target,
+    },
+    trace,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+pub const CMD_IDENTIFIER: &str = "remove";
+const CMD_ABOUT: &str = r#"
+Removes the given path from the dotfiles mappings, the inverse of `add`. This command will:
+1) delete the symlink in your home directory pointing to your dotfiles directory.
+2) move the file or folder from your dotfiles directory back to your home directory.
+3) remove the path from the mappings in the dotfiles configuration file.
+
+With --keep-in-repo, the file or folder stays in your dotfiles directory and only the mapping
+and its symlink are removed.
+"#;
+const ARG_PATH: &str = "path";
+const ARG_KEEP_IN_REPO: &str = "keep-in-repo";
+
+/// Describes a single required IO change to be done. Used to display a list of changes
+/// to the user to sign of.
+#[derive(Debug, PartialEq, Eq)]
+enum RequiredChanges {
+    RemoveSymlink(PathBuf),
+    MoveFile(PathBuf, PathBuf),
+    RemoveMapping(PathBuf),
+}
+/// Describes a list of steps which can be skipped
+type SkippingChanges = Vec<&'static str>;
+
+/// Describes a `remove` sub-command specific error.
+#[derive(Debug)]
+pub enum Error {
+    /// the given path is outside the home *and* dotfiles directory.
+    OutsideValidDir(PathBuf),
+    /// the given path is not mapped. Consists of the relative path.
+    NotMapped(PathBuf),
+    /// A file which is not the symlink of the mapping blocks moving the file back to the home directory.
+    /// Consists of the absolute path into the home directory.
+    HomePathExists(PathBuf),
+}
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::OutsideValidDir(_) => write!(
+                f,
+                "The given path must be either inside your home or dotfiles directory."
+            ),
+            Error::NotMapped(path) => {
+                write!(f, "{} is not part of your mappings.", path.display())
+            }
+            Error::HomePathExists(path) => write!(
+                f,
+                "{} already exists and is not linked to your dotfiles. Remove it or use --keep-in-repo and run this command again.",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// returns the clap definition for the remove sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .alias("rm")
+        .about(CMD_ABOUT)
+        .arg(
+            Arg::with_name(ARG_PATH)
+                .required(true)
+                .help("the path to the mapped directory or file to remove."),
+        )
+        .arg(
+            Arg::with_name(ARG_KEEP_IN_REPO)
+                .long(ARG_KEEP_IN_REPO)
+                .help("keeps the file in your dotfiles directory, only the mapping and its symlink are removed"),
+        )
+}
+
+struct RemoveCommandArgs {
+    /// The absolute path to remove from the dotfiles. Symlinks are not resolved.
+    path: PathBuf,
+    /// if true, the file is not moved back to the home directory.
+    keep_in_repo: bool,
+}
+impl RemoveCommandArgs {
+    fn from_args(args: &ArgMatches) -> Result<RemoveCommandArgs, AppError> {
+        // unwrap is OK here, the argument is required:
+        let path = PathBuf::from(args.value_of(ARG_PATH).unwrap());
+
+        Ok(RemoveCommandArgs {
+            path: resolve_input_path(&path, get_cwd)?,
+            keep_in_repo: args.is_present(ARG_KEEP_IN_REPO),
+        })
+    }
+}
+
+/// command handler for the `remove` sub-command
+/// see `dotfiles remove -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let RemoveCommandArgs { path, keep_in_repo } = RemoveCommandArgs::from_args(args)?;
+    let mut config = AppConfig::from_config_file(global_args, true)?;
+    let home_dir = get_home_dir()?;
+
+    let (changes, skipped) = get_required_changes(
+        &config,
+        &global_args.dotfiles_root,
+        &home_dir,
+        &path,
+        keep_in_repo,
+    )
+    .map_err(AppError::CmdRemoveError)?;
+    trace::event(
+        "planned_changes",
+        json!({
+            "path": path,
+            "changes": format!("{:?}", changes),
+            "skipped": skipped,
+        }),
+    );
+
+    // nothing is asked or changed in a dry run:
+    if global_args.dry_run {
+        let planned = changes
+            .iter()
+            .map(|change| (describe_change(change), change_to_json(change)))
+            .collect();
+        return print_planned_changes(global_args, planned);
+    }
+    if !skipped.is_empty() {
+        println!("Following steps can be skipped:");
+        for skip in skipped {
+            println!("- {}", skip);
+        }
+    }
+    println!("Following things will be done:");
+    for change in &changes {
+        println!("- {}", describe_change(change));
+    }
+    let operations: Vec<Operation> = changes.iter().map(get_operation).collect();
+    if global_args.confirm_operations(&operations, "Continue?", true)? {
+        for change in &changes {
+            apply_change(change, &mut config, global_args)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// returns a human readable description of the given change.
+fn describe_change(change: &RequiredChanges) -> String {
+    match change {
+        RequiredChanges::RemoveSymlink(path) => format!("deleting symlink {}", path.display()),
+        RequiredChanges::MoveFile(from, to) => {
+            format!("moving {} -> {}", from.display(), to.display())
+        }
+        RequiredChanges::RemoveMapping(path) => {
+            format!("removing {} from mappings in config file", path.display())
+        }
+    }
+}
+
+/// returns the type of the given change, see `--assume-yes-for`.
+fn get_operation(change: &RequiredChanges) -> Operation {
+    match change {
+        RequiredChanges::RemoveSymlink(_) => Operation::Symlink,
+        RequiredChanges::MoveFile(_, _) => Operation::Move,
+        RequiredChanges::RemoveMapping(_) => Operation::Mapping,
+    }
+}
+
+/// returns the machine-readable descriptor of the given change, see `--dry-run`.
+fn change_to_json(change: &RequiredChanges) -> Value {
+    match change {
+        RequiredChanges::RemoveSymlink(path) => json!({ "action": "remove_symlink", "path": path }),
+        RequiredChanges::MoveFile(from, to) => json!({ "action": "move", "from": from, "to": to }),
+        RequiredChanges::RemoveMapping(path) => {
+            json!({ "action": "remove_mapping", "path": path })
+        }
+    }
+}
+
+/// returns the changes reverting the given mapped path, see `RequiredChanges::MoveFile` of `add`.
+/// The mapping is removed last, so it is kept if moving its file fails.
+fn get_required_changes(
+    config: &AppConfig,
+    dotfiles_root: &Path,
+    home_dir: &Path,
+    path: &Path,
+    keep_in_repo: bool,
+) -> Result<(Vec<RequiredChanges>, SkippingChanges), Error> {
+    let is_in_dotfiles = path.starts_with(dotfiles_root);
+    // the dotfiles dir is often a subdirectory of the home dir:
+    let mappings_path = normalize_mapping(
+        if is_in_dotfiles {
+            Ok(path.strip_prefix(dotfiles_root).unwrap())
+        } else if path.starts_with(home_dir) {
+            Ok(path.strip_prefix(home_dir).unwrap())
+        } else {
+            Err(Error::OutsideValidDir(path.to_owned()))
+        }?
+        .to_owned(),
+    );
+    let mapping = config
+        .get_mapping(&mappings_path)
+        .ok_or_else(|| Error::NotMapped(mappings_path.clone()))?;
+
+    let homedir_path = home_dir.join(&mappings_path);
+    let dotfiles_path = dotfiles_root.join(&mappings_path);
+    // reverse mappings keep their file in the home directory and the symlink in the dotfiles directory:
+    let (link_path, link_target) = if mapping.is_reverse() {
+        (&dotfiles_path, &homedir_path)
+    } else {
+        (&homedir_path, &dotfiles_path)
+    };
+
+    let mut changes: Vec<RequiredChanges> = Vec::new();
+    let mut skipped: SkippingChanges = Vec::new();
+
+    let is_linked = link_path
+        .symlink_metadata()
+        .map(|meta| meta.file_type().is_symlink())
+        .unwrap_or(false)
+        && resolve_symlink_target(link_path).ok().as_ref() == Some(link_target);
+    if is_linked {
+        changes.push(RequiredChanges::RemoveSymlink(link_path.to_owned()));
+    } else {
+        skipped.push("no symlink will be deleted, paths are not linked.");
+    }
+
+    if mapping.is_reverse() {
+        skipped.push(
+            "no file will be moved, reverse mappings keep their file in your home directory.",
+        );
+    } else if keep_in_repo {
+        skipped.push("no file will be moved, it is kept in your dotfiles directory.");
+    } else if dotfiles_path.symlink_metadata().is_ok() {
+        if !is_linked && homedir_path.symlink_metadata().is_ok() {
+            return Err(Error::HomePathExists(homedir_path));
+        }
+        changes.push(RequiredChanges::MoveFile(dotfiles_path, homedir_path));
+    } else {
+        skipped.push("no file will be moved, it does not exist in your dotfiles directory.");
+    }
+
+    changes.push(RequiredChanges::RemoveMapping(mapping.path.to_owned()));
+
+    Ok((changes, skipped))
+}
+
+/// applies a single change.
+fn apply_change(
+    change: &RequiredChanges,
+    config: &mut AppConfig,
+    global_args: &GlobalArgs,
+) -> Result<(), AppError> {
+    match change {
+        RequiredChanges::RemoveSymlink(path) => remove_path(path),
+        RequiredChanges::MoveFile(from, to) => {
+            create_parent_dir(to)?;
+            move_path(from, to)
+        }
+        RequiredChanges::RemoveMapping(path) => {
+            config.remove_mapping(path);
+            config.to_config_file(global_args)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_required_changes, Error, RequiredChanges};
+    use crate::{
+        commands::setup_test_dirs,
+        config::{AppConfig, Mapping},
+        files::symlink,
+    };
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn get_required_changes_moves_linked_files_back_home() {
+        let (_tmp, home, dotfiles_root, _) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        symlink(dotfiles_root.join(".vimrc"), home.join(".vimrc")).unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".vimrc"))],
+            ..Default::default()
+        };
+
+        let (changes, _) =
+            get_required_changes(&config, &dotfiles_root, &home, &home.join(".vimrc"), false)
+                .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                RequiredChanges::RemoveSymlink(home.join(".vimrc")),
+                RequiredChanges::MoveFile(dotfiles_root.join(".vimrc"), home.join(".vimrc")),
+                RequiredChanges::RemoveMapping(PathBuf::from(".vimrc")),
+            ]
+        );
+
+        let (changes, _) = get_required_changes(
+            &config,
+            &dotfiles_root,
+            &home,
+            &dotfiles_root.join(".vimrc"),
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                RequiredChanges::RemoveSymlink(home.join(".vimrc")),
+                RequiredChanges::RemoveMapping(PathBuf::from(".vimrc")),
+            ]
+        );
+    }
+
+    #[test]
+    fn get_required_changes_rejects_unmapped_and_blocked_paths() {
+        let (_tmp, home, dotfiles_root, _) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        fs::write(home.join(".vimrc"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".vimrc"))],
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            get_required_changes(&config, &dotfiles_root, &home, &home.join(".bashrc"), false),
+            Err(Error::NotMapped(_))
+        ));
+        assert!(matches!(
+            get_required_changes(&config, &dotfiles_root, &home, &home.join(".vimrc"), false),
+            Err(Error::HomePathExists(_))
+        ));
+    }
+}
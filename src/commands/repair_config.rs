@@ -0,0 +1,42 @@
+use super::CommandResult;
+use crate::{cli::GlobalArgs, config::AppConfig, files::get_config_file_path};
+use clap::{App, ArgMatches, SubCommand};
+
+pub const CMD_IDENTIFIER: &str = "repair-config";
+const CMD_ABOUT: &str = r#"
+Rewrites your dotfiles config with a valid `config_version`, eg. after it was changed to an invalid value by hand.
+Configs with a negative or non-numeric `config_version` are upgraded from the first version and rewritten
+using the current version, all mappings and options are kept. Keys unknown to this version of dotfiles are lost.
+Configs of an older but valid version do not need to be repaired: they are read as they are and upgraded
+by the next command changing your config.
+"#;
+
+/// returns the clap definition for the repair-config sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT)
+}
+
+/// command handler for the `repair-config` sub-command
+/// see `dotfiles repair-config -h` for an overview.
+pub fn run(_args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    global_args.require_dotfiles_root()?;
+    let config_path = get_config_file_path(global_args)?;
+    if !config_path.exists() {
+        println!(
+            "Could not find a dotfiles config at {}, nothing to repair.",
+            config_path.display()
+        );
+        return Ok(());
+    }
+
+    // loading the config replaces an invalid version with a valid one:
+    let config = AppConfig::from_path(&config_path, global_args.collect_errors)?;
+    config.to_config_file(global_args)?;
+    println!(
+        "Rewrote {} using config_version {}.",
+        config_path.display(),
+        config.config_version
+    );
+
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use super::{
+    print_planned_changes,
+    status::{get_dotfiles_entry_state, DotfilesEntryState, LinkState},
+    CommandResult,
+};
+use crate::{
+    cli::{only_arg, only_from_args, GlobalArgs},
+    config::AppConfig,
+    errors::AppError,
+    files::{
+        create_symlink_for, get_home_dir, get_relative_path, remove_path, resolve_symlink_target,
+    },
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use colored::*;
+use serde_json::json;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const CMD_IDENTIFIER: &str = "rewrite-links";
+const CMD_ABOUT: &str = r#"
+Recreates the symlinks of all linked mappings using the given style, eg. to make them portable.
+Relative symlinks such as `~/.vimrc -> dotfiles/.vimrc` keep working if your home directory and your
+dotfiles directory are moved together, absolute symlinks keep working if only the symlink is moved.
+Symlinks already using the given style are not changed. Conflicts and copies of mappings using
+`mode = "copy"` are skipped.
+"#;
+const ARG_STYLE: &str = "style";
+/// the names of all supported symlink styles, see `LinkStyle`.
+const STYLES: [&str; 2] = ["relative", "absolute"];
+
+/// Describes how the target of a symlink is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStyle {
+    /// the target is relative to the directory containing the symlink.
+    Relative,
+    /// the target is an absolute path.
+    Absolute,
+}
+
+/// returns the clap definition for the rewrite-links sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(
+            Arg::with_name(ARG_STYLE)
+                .long(ARG_STYLE)
+                .takes_value(true)
+                .required(true)
+                .value_name("STYLE")
+                .possible_values(&STYLES)
+                .help("the style the symlinks should be rewritten to"),
+        )
+        .arg(only_arg())
+}
+
+struct RewriteLinksCommandArgs {
+    /// the style to rewrite the symlinks to.
+    style: LinkStyle,
+    /// the path prefix of the mappings to rewrite, if provided via CLI.
+    only: Option<PathBuf>,
+}
+impl RewriteLinksCommandArgs {
+    fn from_args(args: &ArgMatches) -> RewriteLinksCommandArgs {
+        RewriteLinksCommandArgs {
+            // safe, the argument is required and clap only accepts known styles:
+            style: match args.value_of(ARG_STYLE).unwrap() {
+                "relative" => LinkStyle::Relative,
+                _ => LinkStyle::Absolute,
+            },
+            only: only_from_args(args),
+        }
+    }
+}
+
+/// command handler for the `rewrite-links` sub-command
+/// see `dotfiles rewrite-links -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let RewriteLinksCommandArgs { style, only } = RewriteLinksCommandArgs::from_args(args);
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let home_dir = get_home_dir()?;
+
+    let rewritten = rewrite_links(global_args, &config, &home_dir, style, only.as_deref())?;
+    if !global_args.dry_run {
+        println!("Rewrote {} symlink(s).", rewritten);
+    }
+
+    Ok(())
+}
+
+/// Recreates the symlinks of all linked mappings in the given style and prints the action taken per mapping.
+/// Returns the number of rewritten symlinks. If given, only mappings starting with `only` are considered.
+/// In a dry run, nothing is changed and the symlinks to rewrite are printed instead, see `print_planned_changes`.
+pub fn rewrite_links(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    style: LinkStyle,
+    only: Option<&Path>,
+) -> Result<usize, AppError> {
+    let mut rewritten = 0;
+    let mut planned = vec![];
+    for mapping in config
+        .mappings
+        .iter()
+        .filter(|mapping| only.is_none_or(|only| mapping.path.starts_with(only)))
+    {
+        // reverse mappings keep their symlink in the dotfiles directory:
+        let link_path = if mapping.is_reverse() {
+            global_args.dotfiles_root.join(&mapping.path)
+        } else {
+            home_dir.join(&mapping.path)
+        };
+        let entry = (mapping.path.clone(), DotfilesEntryState::Mapped);
+        let state =
+            get_dotfiles_entry_state(global_args, config, &entry, home_dir).map_err(|err| {
+                AppError::FsOther(format!("Failed to read your linked dotfiles: {}", err))
+            })?;
+        let reason = match state {
+            LinkState::Linked if mapping.is_copy() => "is a copy, not a symlink",
+            LinkState::Linked => {
+                // mappings linked through a mapped parent directory do not have a symlink of their own:
+                let current_target = match fs::read_link(&link_path) {
+                    Ok(target) => target,
+                    Err(_) => continue,
+                };
+                let target =
+                    get_styled_target(&link_path, &current_target, style).map_err(|err| {
+                        AppError::FsOther(format!(
+                            "Failed to resolve the symlink {}: {}",
+                            link_path.display(),
+                            err
+                        ))
+                    })?;
+                if target == current_target {
+                    continue;
+                }
+                if global_args.dry_run {
+                    planned.push((
+                        format!(
+                            "rewriting symlink {} -> {}",
+                            link_path.display(),
+                            target.display()
+                        ),
+                        json!({ "action": "rewrite_symlink", "link": link_path, "target": target }),
+                    ));
+                } else {
+                    remove_path(&link_path)?;
+                    create_symlink_for(&link_path, &target)?;
+                    println!("{} {}", "REWRITTEN".green(), mapping.path.display());
+                }
+                rewritten += 1;
+                continue;
+            }
+            LinkState::ConflictNoLink(_) | LinkState::Stale(_) => "is not a symlink",
+            LinkState::ConflictWrongTarget(_) | LinkState::External(_) => {
+                "does not point to your dotfiles directory"
+            }
+            _ => continue,
+        };
+        // a dry run only prints the planned changes:
+        if !global_args.dry_run {
+            println!(
+                "{} {} {} {}",
+                "SKIPPED  ".yellow(),
+                mapping.path.display(),
+                link_path.display(),
+                reason
+            );
+        }
+    }
+
+    if global_args.dry_run {
+        print_planned_changes(global_args, planned)?;
+    }
+    Ok(rewritten)
+}
+
+/// returns the target of the symlink at the given path in the given style.
+/// `current_target` is the target as currently stored in the symlink.
+fn get_styled_target(
+    link_path: &Path,
+    current_target: &Path,
+    style: LinkStyle,
+) -> std::io::Result<PathBuf> {
+    match style {
+        LinkStyle::Absolute if current_target.is_absolute() => Ok(current_target.to_owned()),
+        LinkStyle::Absolute => resolve_symlink_target(link_path),
+        LinkStyle::Relative if current_target.is_relative() => Ok(current_target.to_owned()),
+        LinkStyle::Relative => {
+            // safe, the link path is always absolute and has a parent:
+            let link_dir = link_path.parent().unwrap();
+            Ok(get_relative_path(link_dir, current_target))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite_links, LinkStyle};
+    use crate::{
+        commands::setup_test_dirs,
+        config::{AppConfig, Mapping},
+        files::{resolve_symlink_target, symlink},
+    };
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    #[test]
+    fn rewrite_links_only_rewrites_linked_symlinks_in_another_style() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(home.join(".config")).unwrap();
+        fs::create_dir_all(dotfiles_root.join(".config")).unwrap();
+        for file in [".vimrc", ".bashrc", ".config/starship.toml"] {
+            fs::write(dotfiles_root.join(file), "").unwrap();
+        }
+        symlink(dotfiles_root.join(".vimrc"), home.join(".vimrc")).unwrap();
+        symlink(
+            dotfiles_root.join(".config/starship.toml"),
+            home.join(".config/starship.toml"),
+        )
+        .unwrap();
+        fs::write(home.join(".bashrc"), "real file").unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".bashrc")),
+                Mapping::new(PathBuf::from(".config/starship.toml")),
+            ],
+            ..Default::default()
+        };
+
+        let rewritten =
+            rewrite_links(&global_args, &config, &home, LinkStyle::Relative, None).unwrap();
+        assert_eq!(rewritten, 2);
+        assert_eq!(
+            fs::read_link(home.join(".vimrc")).unwrap(),
+            Path::new("dotfiles/.vimrc")
+        );
+        assert_eq!(
+            fs::read_link(home.join(".config/starship.toml")).unwrap(),
+            Path::new("../dotfiles/.config/starship.toml")
+        );
+        assert_eq!(
+            resolve_symlink_target(&home.join(".config/starship.toml")).unwrap(),
+            dotfiles_root.join(".config/starship.toml")
+        );
+        assert_eq!(
+            fs::read_to_string(home.join(".bashrc")).unwrap(),
+            "real file"
+        );
+
+        let rewritten =
+            rewrite_links(&global_args, &config, &home, LinkStyle::Relative, None).unwrap();
+        assert_eq!(rewritten, 0);
+
+        let rewritten =
+            rewrite_links(&global_args, &config, &home, LinkStyle::Absolute, None).unwrap();
+        assert_eq!(rewritten, 2);
+        assert_eq!(
+            fs::read_link(home.join(".vimrc")).unwrap(),
+            dotfiles_root.join(".vimrc")
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use super::{
+    link::{link_mappings, LinkOptions},
+    print_planned_changes, CommandResult,
+};
+use crate::{
+    cli::GlobalArgs,
+    config::AppConfig,
+    errors::AppError,
+    files::get_home_dir,
+    git::{is_git_repo, run_git},
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::json;
+
+pub const CMD_IDENTIFIER: &str = "rollback";
+const CMD_ABOUT: &str = r#"
+Reverts all uncommitted changes in your dotfiles repository and fixes your symlinks afterwards.
+Requires your dotfiles directory to be (part of) a GIT repository. This command will:
+1) revert all unstaged changes using `git checkout -- .`
+   or all staged and unstaged changes using `git reset HEAD -- .` and `git checkout -- .` if --hard is provided.
+2) create all missing symlinks the same way the `link` command does.
+Only files inside of your dotfiles directory are reset, even if it is part of a bigger repository.
+Untracked files are never touched.
+"#;
+const ARG_HARD: &str = "hard";
+
+/// returns the clap definition for the rollback sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT).arg(
+        Arg::with_name(ARG_HARD)
+            .long(ARG_HARD)
+            .help("reverts staged changes as well by unstaging them before checking them out"),
+    )
+}
+
+struct RollbackCommandArgs {
+    hard: bool,
+}
+impl RollbackCommandArgs {
+    fn from_args(args: &ArgMatches) -> RollbackCommandArgs {
+        RollbackCommandArgs {
+            hard: args.is_present(ARG_HARD),
+        }
+    }
+}
+
+/// command handler for the `rollback` sub-command
+/// see `dotfiles rollback -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let RollbackCommandArgs { hard } = RollbackCommandArgs::from_args(args);
+    global_args.require_dotfiles_root()?;
+    let root = &global_args.dotfiles_root;
+    if !is_git_repo(root) {
+        return Err(AppError::FsOther(format!(
+            "{} is not a GIT repository, cannot roll back any changes.",
+            root.display()
+        )));
+    }
+
+    // `checkout` restores from the index, which is reset to the last commit first for --hard.
+    // All commands are limited to the dotfiles directory by the `.` pathspec, which matters if it is nested
+    // in a bigger repository, such as a git-tracked home directory:
+    let (diff_args, rollback_commands): (&[&str], &[&[&str]]) = if hard {
+        (
+            &["diff", "--name-only", "--relative", "HEAD", "--", "."],
+            &[
+                &["reset", "-q", "HEAD", "--", "."],
+                &["checkout", "--", "."],
+            ],
+        )
+    } else {
+        (
+            &["diff", "--name-only", "--relative", "--", "."],
+            &[&["checkout", "--", "."]],
+        )
+    };
+    let changed_files = run_git(root, diff_args)?;
+    let changed_files: Vec<&str> = changed_files.lines().collect();
+    // neither the files are reset nor the mappings relinked in a dry run:
+    if global_args.dry_run {
+        let planned = changed_files
+            .iter()
+            .map(|file| {
+                (
+                    format!("resetting {}", file),
+                    json!({ "action": "reset", "path": file }),
+                )
+            })
+            .collect();
+        return print_planned_changes(global_args, planned);
+    }
+
+    if changed_files.is_empty() {
+        println!("There are no changes to roll back.");
+    } else {
+        println!("Following files will be reset to their last committed state:");
+        for file in &changed_files {
+            println!("- {}", file);
+        }
+        if !global_args.confirm("Continue?", false)? {
+            return Ok(());
+        }
+        for rollback_args in rollback_commands {
+            run_git(root, rollback_args)?;
+        }
+        println!("Reset {} file(s).", changed_files.len());
+    }
+
+    // the config itself may have been reset as well, therefore it is loaded after the rollback:
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let linked = link_mappings(
+        global_args,
+        &config,
+        &get_home_dir()?,
+        LinkOptions::default(),
+    )?;
+    println!("Relinked {} mapping(s).", linked);
+
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use super::{suggest::shell_quote, CommandResult};
+use crate::{cli::GlobalArgs, errors::AppError};
+use clap::{App, Arg, ArgMatches, SubCommand};
+pub const CMD_IDENTIFIER: &str = "shellenv";
+const CMD_ABOUT: &str = r#"
+Prints shell code defining shortcuts for common workflows, which is meant to be evaluated by your shell:
+  bash/zsh: eval "$(dotfiles shellenv bash)"
+  fish:     dotfiles shellenv fish | source
+It exports DOTFILES_ROOT, so all following dotfiles commands use the current dotfiles directory,
+and defines the functions dfs (status), dfa (add), dfl (link), dfu (unlink) and dfcd, which changes into
+your dotfiles directory. Use --helpers to only define some of them.
+"#;
+const ARG_SHELL: &str = "shell";
+const ARG_HELPERS: &str = "helpers";
+/// the names of all supported shells.
+const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// A shell function defined by `shellenv`.
+struct Helper {
+    /// the name of the function.
+    name: &'static str,
+    /// the sub-command it runs with the arguments of the function. Changes into the dotfiles directory if `None`.
+    command: Option<&'static str>,
+}
+
+const HELPERS: [Helper; 5] = [
+    Helper {
+        name: "dfs",
+        command: Some("status"),
+    },
+    Helper {
+        name: "dfa",
+        command: Some("add"),
+    },
+    Helper {
+        name: "dfl",
+        command: Some("link"),
+    },
+    Helper {
+        name: "dfu",
+        command: Some("unlink"),
+    },
+    Helper {
+        name: "dfcd",
+        command: None,
+    },
+];
+/// the names of all helpers, see `HELPERS`.
+const HELPER_NAMES: [&str; 5] = ["dfs", "dfa", "dfl", "dfu", "dfcd"];
+
+/// returns the clap definition for the shellenv sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(
+            Arg::with_name(ARG_SHELL)
+                .required(true)
+                .possible_values(&SHELLS)
+                .help("the shell to print the code for"),
+        )
+        .arg(
+            Arg::with_name(ARG_HELPERS)
+                .long(ARG_HELPERS)
+                .takes_value(true)
+                .use_delimiter(true)
+                .value_name("HELPERS")
+                .possible_values(&HELPER_NAMES)
+                .help("only defines the given comma-separated helpers, eg. `dfs,dfcd`. Defaults to all helpers."),
+        )
+}
+
+struct ShellenvCommandArgs {
+    /// the name of the shell to print the code for, see `SHELLS`.
+    shell: String,
+    /// the names of the helpers to define. All helpers are defined if empty.
+    helpers: Vec<String>,
+}
+impl ShellenvCommandArgs {
+    fn from_args(args: &ArgMatches) -> ShellenvCommandArgs {
+        ShellenvCommandArgs {
+            // unwrap is OK here, the argument is required:
+            shell: args.value_of(ARG_SHELL).unwrap().to_string(),
+            helpers: args
+                .values_of(ARG_HELPERS)
+                .map(|values| values.map(String::from).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// command handler for the `shellenv` sub-command
+/// see `dotfiles shellenv -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let ShellenvCommandArgs { shell, helpers } = ShellenvCommandArgs::from_args(args);
+    let dotfiles_root = global_args.dotfiles_root.to_str().ok_or_else(|| {
+        AppError::FsOther(format!(
+            "{} is not valid unicode and cannot be exported",
+            global_args.dotfiles_root.display()
+        ))
+    })?;
+
+    print!("{}", format_shellenv(&shell, dotfiles_root, &helpers));
+    Ok(())
+}
+
+/// returns the code for the given shell exporting the given dotfiles directory and defining the given helpers.
+/// All helpers are defined if none are given.
+fn format_shellenv(shell: &str, dotfiles_root: &str, helpers: &[String]) -> String {
+    let helpers = HELPERS
+        .iter()
+        .filter(|helper| helpers.is_empty() || helpers.iter().any(|name| name == helper.name));
+
+    let mut code = match shell {
+        "fish" => format!("set -gx DOTFILES_ROOT {}\n", fish_quote(dotfiles_root)),
+        _ => format!("export DOTFILES_ROOT={}\n", shell_quote(dotfiles_root)),
+    };
+    for helper in helpers {
+        let line = match (shell, helper.command) {
+            ("fish", Some(command)) => format!(
+                "function {}; command dotfiles {} $argv; end",
+                helper.name, command
+            ),
+            ("fish", None) => format!("function {}; cd $DOTFILES_ROOT; end", helper.name),
+            (_, Some(command)) => format!(
+                "{}() {{ command dotfiles {} \"$@\"; }}",
+                helper.name, command
+            ),
+            (_, None) => format!("{}() {{ cd \"$DOTFILES_ROOT\" || return; }}", helper.name),
+        };
+        code.push_str(&line);
+        code.push('\n');
+    }
+
+    code
+}
+
+/// wraps the given text in single quotes for fish, which only escapes backslashes and single quotes inside of them.
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fish_quote, format_shellenv};
+
+    #[test]
+    fn format_shellenv_defines_the_selected_helpers_per_shell() {
+        let root = "/home/me/my dotfiles";
+        let helpers = vec!["dfs".to_string(), "dfcd".to_string()];
+
+        assert_eq!(
+            format_shellenv("bash", root, &helpers),
+            "export DOTFILES_ROOT='/home/me/my dotfiles'\n\
+             dfs() { command dotfiles status \"$@\"; }\n\
+             dfcd() { cd \"$DOTFILES_ROOT\" || return; }\n"
+        );
+        assert_eq!(
+            format_shellenv("fish", root, &helpers),
+            "set -gx DOTFILES_ROOT '/home/me/my dotfiles'\n\
+             function dfs; command dotfiles status $argv; end\n\
+             function dfcd; cd $DOTFILES_ROOT; end\n"
+        );
+        // all helpers are defined by default:
+        assert_eq!(format_shellenv("zsh", root, &[]).lines().count(), 6);
+    }
+
+    #[test]
+    fn fish_quote_escapes_quotes_and_backslashes() {
+        assert_eq!(fish_quote(r"/home/me/it's\"), r"'/home/me/it\'s\\'");
+    }
+}
@@ -1,78 +1,847 @@
-use super::CommandResult;
-use crate::{cli::GlobalArgs, config, errors::AppError, files::get_home_dir};
-use clap::{App, ArgMatches, SubCommand};
+use super::{add::resolve_input_path, CommandResult};
+use crate::{
+    cli::{only_arg, only_from_args, to_json, GlobalArgs, ARG_ONLY},
+    config,
+    errors::AppError,
+    files::{
+        abbreviate_path, count_files, get_cwd, get_home_dir, get_last_status_file_path,
+        resolve_symlink_target,
+    },
+    ignore::{IgnoreRules, IGNORE_FILE_NAME},
+    last_status::LastStatus,
+    log,
+    template::render_mapping,
+    trace,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use colored::*;
 use config::{AppConfig, Mapping};
-use std::{collections::VecDeque, fs, io, iter::FromIterator, path::PathBuf};
+use serde::{Serialize, Serializer};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fs, io,
+    iter::FromIterator,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
 pub const CMD_IDENTIFIER: &str = "status";
 const CMD_ABOUT: &str = r#"
 Shows the current status for all files and directories in your dotfiles repository.
 For each file, a status and a possible explanation is shown:
-LINKED  : The file is linked from the home directory to the dotfiles directory.
-INVALID : This path is listed in the configuration, but does not exist in the dotfiles repository.
-CONFLICT: The path exists in the home directory, but is either not a symlink
-          or does not point to its counterpart in the dotfiles directory.
-UNLINKED: The file is currently not linked to the home directory.
-UNMAPPED: This file or directory in the dotfiles repository is nowhere mentioned under mappings
-          and will therefore never be linked.
+LINKED   : The file is linked from the home directory to the dotfiles directory.
+INVALID  : This path is listed in the configuration, but does not exist in the dotfiles repository.
+CONFLICT : The path exists in the home directory, but is either not a symlink
+           or does not point to its counterpart in the dotfiles directory.
+UNLINKED : The file is currently not linked to the home directory.
+UNMAPPED : This file or directory in the dotfiles repository is nowhere mentioned under mappings
+           and will therefore never be linked. Paths matching a pattern in `.dotfilesignore` are not listed.
+UNMANAGED: This path is mapped with `manage = "once"`. It was linked when it was added,
+           but is not checked anymore.
+EXTERNAL : The path in the home directory is a symlink pointing outside of the dotfiles directory,
+           which is allowed by `allow_external_target = true` in its mapping.
+STALE    : The path is mapped with `mode = "copy"`, but the content of the copy in the home directory
+           differs from the (rendered) file in the dotfiles repository. Permissions are not compared.
+PENDING  : This path is mapped with `pinned = true`, but does not exist in the dotfiles repository (yet).
+           Unlike INVALID, it is not treated as an error.
+A file at `hosts/<hostname>/<path>` in the dotfiles repository overrides the mapped `<path>` on the host
+with the given name. Linked entries show which source is active.
+Pass a path in your home or dotfiles directory, eg. `dotfiles status ~/.config/nvim`, to only show the entries
+of this path and below. Relative paths are resolved relative to the current directory.
+Use --explain <STATE> for details and how to resolve a state.
+Use --format tap to print a TAP test point per mapping: LINKED and EXTERNAL mappings are `ok`, UNMANAGED and PENDING
+mappings are skipped and all others are `not ok`. Unmapped entries are not mappings and therefore not listed.
+
+Exits with status 2 if any entry is CONFLICT or INVALID, see --check and --strict, and with status 1 on errors.
 "#;
 
+const ARG_TRUNCATE: &str = "truncate";
+const ARG_NO_TRUNCATE: &str = "no-truncate";
+const ARG_COUNT: &str = "count";
+const ARG_CHECK: &str = "check";
+const ARG_STRICT: &str = "strict";
+const ARG_STATE: &str = "state";
+const ARG_FORMAT: &str = "format";
+/// the names of all supported output formats of the entries, besides `--json`.
+const FORMATS: [&str; 2] = ["text", "tap"];
+const ARG_FULL_PATHS: &str = "full-paths";
+const ARG_GROUP_BY_TARGET_DIR: &str = "group-by-target-dir";
+const ARG_EXPLAIN: &str = "explain";
+const ARG_WITH_METADATA: &str = "with-metadata";
+const ARG_COUNT_DIR_CONTENTS: &str = "count-dir-contents";
+const ARG_THEME: &str = "theme";
+const ARG_SYMBOLS: &str = "symbols";
+const ARG_NUMBER: &str = "number";
+const ARG_PATH: &str = "path";
+/// the states which need the attention of the user, see `status --check` and `dump`.
+pub const PROBLEM_STATES: [&str; 3] = ["invalid", "conflict", "unmapped"];
+/// the states failing the status check if `--check` is not provided.
+const DEFAULT_FAILING_STATES: [&str; 2] = ["conflict", "invalid"];
+/// the exit status of `status` if an entry is in a failing state, to tell it apart from errors.
+const EXIT_STATUS_FAILING_STATES: i32 = 2;
+/// the names of all built-in color themes, see `get_state_color`.
+const THEMES: [&str; 2] = ["default", "high-contrast"];
+/// the directory in the dotfiles root containing a directory of host-specific overrides per hostname.
+pub const HOSTS_DIR: &str = "hosts";
+/// paths are never truncated to less than this number of characters
+const MIN_PATH_WIDTH: usize = 12;
+
 /// returns the clap definition for the status sub-command
 pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
-    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT)
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(
+            Arg::with_name(ARG_TRUNCATE)
+                .long(ARG_TRUNCATE)
+                .takes_value(true)
+                .value_name("COLUMNS")
+                .help("shortens long paths to fit the given line width. Defaults to the width of the terminal."),
+        )
+        .arg(
+            Arg::with_name(ARG_NO_TRUNCATE)
+                .long(ARG_NO_TRUNCATE)
+                .conflicts_with(ARG_TRUNCATE)
+                .help("always prints the full paths, even if they do not fit the terminal width"),
+        )
+        .arg(
+            Arg::with_name(ARG_COUNT)
+                .long(ARG_COUNT)
+                .takes_value(true)
+                .value_name("STATE")
+                .possible_values(&LinkState::NAMES)
+                .help("only prints the number of entries in the given state. Exits with status 2 if the number is not 0."),
+        )
+        .arg(
+            Arg::with_name(ARG_CHECK)
+                .long(ARG_CHECK)
+                .takes_value(true)
+                .min_values(0)
+                .use_delimiter(true)
+                .value_name("STATES")
+                .possible_values(&LinkState::NAMES)
+                .conflicts_with(ARG_COUNT)
+                .help("selects the states exiting with status 2. Without --check, conflict and invalid entries fail. A bare --check fails unmapped entries as well, --check <a>,<b> fails exactly the given comma-separated states."),
+        )
+        .arg(
+            Arg::with_name(ARG_STRICT)
+                .long(ARG_STRICT)
+                .help("exits with status 2 if any entry is unlinked as well"),
+        )
+        .arg(
+            Arg::with_name(ARG_FULL_PATHS)
+                .long(ARG_FULL_PATHS)
+                .help("prints full paths in descriptions instead of abbreviating them with `$DOTFILES` and `~`"),
+        )
+        .arg(
+            Arg::with_name(ARG_GROUP_BY_TARGET_DIR)
+                .long(ARG_GROUP_BY_TARGET_DIR)
+                .help("groups the entries by the directory in your home directory they are linked into"),
+        )
+        .arg(
+            Arg::with_name(ARG_EXPLAIN)
+                .long(ARG_EXPLAIN)
+                .takes_value(true)
+                .value_name("STATE")
+                .possible_values(&LinkState::NAMES)
+                .conflicts_with(ARG_COUNT)
+                .help("prints what the given state means and how to resolve it, without checking any files"),
+        )
+        .arg(
+            Arg::with_name(ARG_WITH_METADATA)
+                .long(ARG_WITH_METADATA)
+                .help("shows the type, size and age of each entry in your dotfiles directory"),
+        )
+        .arg(
+            Arg::with_name(ARG_COUNT_DIR_CONTENTS)
+                .long(ARG_COUNT_DIR_CONTENTS)
+                .help("shows the number of files each directory in your dotfiles directory contains"),
+        )
+        .arg(
+            Arg::with_name(ARG_THEME)
+                .long(ARG_THEME)
+                .takes_value(true)
+                .value_name("THEME")
+                .possible_values(&THEMES)
+                .help("the color theme used for the states. Defaults to `theme` in your settings file or `default`."),
+        )
+        .arg(
+            Arg::with_name(ARG_SYMBOLS)
+                .long(ARG_SYMBOLS)
+                .help("shows a symbol instead of the name of each state, eg. ✓ for linked. Defaults to `use_symbols` in your settings file."),
+        )
+        .arg(
+            Arg::with_name(ARG_NUMBER)
+                .long(ARG_NUMBER)
+                .short("n")
+                .conflicts_with(ARG_COUNT)
+                .help("numbers the printed entries. Commands taking a mapped path accept these numbers instead until the next numbered status, eg. `dotfiles unlink 3`."),
+        )
+        .arg(
+            Arg::with_name(ARG_STATE)
+                .long(ARG_STATE)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("STATE")
+                .possible_values(&LinkState::NAMES)
+                .conflicts_with(ARG_COUNT)
+                .help("only shows entries in the given state. Can be repeated to show entries in any of the given states."),
+        )
+        .arg(
+            Arg::with_name(ARG_FORMAT)
+                .long(ARG_FORMAT)
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&FORMATS)
+                .default_value("text")
+                .conflicts_with(ARG_COUNT)
+                .help("the format to print the entries in. `tap` prints a test point per mapping, see above."),
+        )
+        .arg(only_arg())
+        .arg(
+            Arg::with_name(ARG_PATH)
+                .value_name("PATH")
+                .conflicts_with(ARG_ONLY)
+                .help("only shows the entries of the given path in your home or dotfiles directory and below"),
+        )
 }
 
-struct StatusCommandArgs {}
+struct StatusCommandArgs {
+    /// the maximum width of a printed line, if paths should be truncated to fit it.
+    max_width: Option<usize>,
+    /// the name of the state to count entries of, if only the count should be printed.
+    count: Option<String>,
+    /// the names of the states failing the check, which lets the command exit with a non-zero status.
+    check: Vec<String>,
+    /// if true, paths in descriptions are printed in full instead of abbreviated.
+    full_paths: bool,
+    /// if true, entries are grouped by the home directory they are linked into.
+    group_by_target_dir: bool,
+    /// the name of the state to explain, if only its explanation should be printed.
+    explain: Option<String>,
+    /// if true, the metadata of each entry in the dotfiles directory is shown.
+    with_metadata: bool,
+    /// if true, the number of files of each directory in the dotfiles directory is shown.
+    count_dir_contents: bool,
+    /// the name of the color theme to use, if provided via CLI.
+    theme: Option<String>,
+    /// if true, states are shown as symbols instead of their names.
+    symbols: bool,
+    /// if true, the printed entries are numbered and stored as the last status.
+    number: bool,
+    /// the path prefix of the entries to show, if provided via CLI.
+    only: Option<PathBuf>,
+    /// the absolute path in the home or dotfiles directory of the entries to show, if provided via CLI.
+    /// Symlinks are not resolved.
+    path: Option<PathBuf>,
+    /// the names of the states of the entries to show. All entries are shown if empty.
+    states: Vec<String>,
+    /// if true, the entries are printed in the Test Anything Protocol format.
+    tap: bool,
+}
 impl StatusCommandArgs {
-    fn from_args(_: &ArgMatches) -> StatusCommandArgs {
-        StatusCommandArgs {}
+    fn from_args(args: &ArgMatches) -> Result<StatusCommandArgs, AppError> {
+        let max_width = match args.value_of(ARG_TRUNCATE) {
+            Some(value) => Some(value.parse::<usize>().map_err(|_| {
+                AppError::CliInvalidArgValue(
+                    ARG_TRUNCATE.to_string(),
+                    format!("{} is not a valid number of columns", value),
+                )
+            })?),
+            // only returns a width if stdout is a terminal, output piped to other programs is never truncated:
+            None if !args.is_present(ARG_NO_TRUNCATE) => {
+                term_size::dimensions_stdout().map(|(width, _)| width)
+            }
+            None => None,
+        };
+
+        Ok(StatusCommandArgs {
+            max_width,
+            count: args.value_of(ARG_COUNT).map(String::from),
+            check: get_failing_states(args),
+            full_paths: args.is_present(ARG_FULL_PATHS),
+            group_by_target_dir: args.is_present(ARG_GROUP_BY_TARGET_DIR),
+            explain: args.value_of(ARG_EXPLAIN).map(String::from),
+            with_metadata: args.is_present(ARG_WITH_METADATA),
+            count_dir_contents: args.is_present(ARG_COUNT_DIR_CONTENTS),
+            theme: args.value_of(ARG_THEME).map(String::from),
+            symbols: args.is_present(ARG_SYMBOLS),
+            number: args.is_present(ARG_NUMBER),
+            only: only_from_args(args),
+            path: args
+                .value_of(ARG_PATH)
+                .map(|path| resolve_input_path(Path::new(path), get_cwd))
+                .transpose()?,
+            states: args
+                .values_of(ARG_STATE)
+                .map(|values| values.map(String::from).collect())
+                .unwrap_or_default(),
+            tap: args.value_of(ARG_FORMAT) == Some("tap"),
+        })
     }
 }
 
 /// Handler of the `status` sub-command.
 /// Iterates over all files configured under mappings in the dotfiles config file and
 pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
-    let _args = StatusCommandArgs::from_args(args);
-    let config = AppConfig::from_config_file(global_args)?;
+    let StatusCommandArgs {
+        max_width,
+        count,
+        check,
+        full_paths,
+        group_by_target_dir,
+        explain,
+        with_metadata,
+        count_dir_contents,
+        theme,
+        symbols,
+        number,
+        only,
+        path,
+        states: shown_states,
+        tap,
+    } = StatusCommandArgs::from_args(args)?;
+    if tap && global_args.json {
+        return Err(AppError::CliInvalidArgValue(
+            ARG_FORMAT.to_string(),
+            "tap cannot be combined with --json".to_string(),
+        ));
+    }
+    if let Some(state_name) = explain {
+        // safe, clap only accepts the names of existing states:
+        let (meaning, remediation) = explain_state(&state_name).unwrap();
+        println!("{}", state_name.to_uppercase().bold());
+        println!("{}\n", meaning);
+        println!("{}", remediation);
+        return Ok(());
+    }
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let home_dir = get_home_dir()?;
+    let only = match path {
+        Some(path) => Some(get_path_prefix(
+            &path,
+            &global_args.dotfiles_root,
+            &home_dir,
+        )?),
+        None => only,
+    };
 
-    let dotfile_entries = get_dotfiles_entries(global_args, &config).map_err(|err| {
-        AppError::FsOther(format!(
-            "Failed to read your dotfile directory at {}: {}",
-            global_args.dotfiles_root.display(),
-            err
-        ))
-    })?;
-    for entry in &dotfile_entries {
-        let status =
-            get_dotfiles_entry_state(global_args, entry, &get_home_dir()?).map_err(|err| {
-                AppError::FsOther(format!("Failed to read your linked dotfiles: {}", err))
-            })?;
-        let text_status = match status {
-            LinkState::Unlinked => "UNLINKED".yellow(),
-            LinkState::Linked => "LINKED  ".green(),
-            LinkState::Invalid(_) => "INVALID ".purple(),
-            LinkState::ConflictNoLink(_) => "CONFLICT".red(),
-            LinkState::ConflictWrongTarget(_) => "CONFLICT".red(),
-            LinkState::Unmapped => "UNMAPPED".white(),
+    let (dotfile_entries, states) =
+        get_entry_states(global_args, &config, &home_dir, only.as_deref())?;
+
+    if let Some(state_name) = count {
+        let count = states
+            .iter()
+            .filter(|state| state.name() == state_name)
+            .count();
+        println!("{}", count);
+        // allows scripts to check the result without parsing the output. Status 1 is reserved for errors:
+        return match count {
+            0 => Ok(()),
+            _ => Err(AppError::ExitStatus(EXIT_STATUS_FAILING_STATES)),
         };
+    }
 
+    // the check is evaluated before printing, but only decides the exit status afterwards:
+    let check_result = match count_failures(&states, &check) {
+        0 => Ok(()),
+        _ => Err(AppError::ExitStatus(EXIT_STATUS_FAILING_STATES)),
+    };
+    // the filter only affects the printed entries, the check above still considers all of them:
+    let (dotfile_entries, states): (Vec<_>, Vec<_>) = dotfile_entries
+        .into_iter()
+        .zip(states)
+        .filter(|(_, state)| shown_states.is_empty() || state.is_any_of(&shown_states))
+        .unzip();
+
+    if global_args.json {
+        let entries: Vec<StatusEntry> = dotfile_entries
+            .iter()
+            .zip(&states)
+            .map(|((path, _), state)| {
+                get_status_entry(global_args, path, state, with_metadata, count_dir_contents)
+            })
+            .collect();
+        let output = to_json(&entries, global_args.json_pretty)
+            .map_err(|err| AppError::FsOther(format!("Failed to serialize status: {}", err)))?;
+        println!("{}", output);
+        return check_result;
+    }
+    if tap {
+        let entries: Vec<_> = dotfile_entries
+            .iter()
+            .map(|(path, _)| path.as_path())
+            .zip(&states)
+            .collect();
+        print!("{}", format_tap(&entries));
+        return check_result;
+    }
+
+    let now = SystemTime::now();
+    let settings = &global_args.settings;
+    let theme = theme.or_else(|| settings.theme.clone()).unwrap_or_default();
+    let symbols = symbols || settings.use_symbols == Some(true);
+    let labels = get_state_labels(symbols, &settings.symbols);
+    // the numbers are right-aligned to the widest one:
+    let number_width = states.len().to_string().len();
+    let print_entry = |path: &Path, status: LinkState, index: usize| {
+        let text_status =
+            labels[status.name()].color(get_state_color(&theme, &settings.colors, status.name()));
+
+        let display_path = |path: &Path| {
+            if full_paths {
+                format!("{:?}", path)
+            } else {
+                abbreviate_path(path, &global_args.dotfiles_root, &home_dir)
+            }
+        };
         let description = match status {
-            LinkState::ConflictNoLink(target) => format!("{:?} is not a symlink", target),
-            LinkState::ConflictWrongTarget(target) => format!("points to {:?} instead", target),
-            LinkState::Invalid(target) => format!("{:?} does not exist", target),
-            _ => String::new(),
+            LinkState::ConflictNoLink(target) => {
+                format!("{} is not a symlink", display_path(&target)).red()
+            }
+            LinkState::ConflictWrongTarget(target) => {
+                format!("points to {} instead", display_path(&target)).red()
+            }
+            LinkState::Invalid(target) => format!("{} does not exist", display_path(&target)).red(),
+            LinkState::Pending(target) => {
+                format!("{} does not exist yet", display_path(&target)).dimmed()
+            }
+            LinkState::External(target) => format!("points to {}", display_path(&target)).red(),
+            LinkState::Stale(_) => "differs from your dotfiles".blue(),
+            LinkState::StaleLink(target) => format!(
+                "points to {}, which no longer exists. Run `dotfiles link` to relink it",
+                display_path(&target)
+            )
+            .blue(),
+            // shows which source is active if a host-specific file overrides the shared one:
+            LinkState::Linked | LinkState::Unlinked => match get_host_override(global_args, path) {
+                Some(source) => format!("from {}", display_path(&source)).cyan(),
+                None => "".normal(),
+            },
+            _ => "".normal(),
+        };
+        // only stats the files if requested, because it adds a syscall per entry,
+        // as does looking up a host-specific source:
+        let mut annotation = String::new();
+        if with_metadata || count_dir_contents {
+            let source = get_dotfiles_source(global_args, path);
+            if with_metadata {
+                if let Some(metadata) = EntryMetadata::from_path(&source) {
+                    annotation.push_str(&format!("[{}] ", metadata.annotation(now)));
+                }
+            }
+            // only traverses directories if requested, because they may contain lots of files:
+            if count_dir_contents && is_dir(&source) {
+                annotation.push_str(&format!("[{} files] ", count_files(&source)));
+            }
+        }
+
+        let path = path.display().to_string();
+        let path = match max_width {
+            Some(width) => {
+                // the status label and the two separating spaces:
+                let used_width = text_status.chars().count()
+                    + annotation.chars().count()
+                    + description.chars().count()
+                    + 2;
+                elide_middle(&path, width.saturating_sub(used_width).max(MIN_PATH_WIDTH))
+            }
+            None => path,
         };
 
+        let number = if number {
+            format!("{:>width$} ", index + 1, width = number_width)
+        } else {
+            String::new()
+        };
         println!(
-            "{} {} {}",
+            "{}{} {} {}{}",
+            number.dimmed(),
             text_status,
-            entry.0.display(),
-            description.red()
+            path,
+            annotation.dimmed(),
+            description
         );
+    };
+
+    let entries = dotfile_entries
+        .iter()
+        .map(|entry| entry.0.as_path())
+        .zip(states);
+    // the entries in the order they are printed, which is the order they are numbered in:
+    let mut printed: Vec<PathBuf> = Vec::new();
+    if group_by_target_dir {
+        let groups = group_by_home_dir(entries, &home_dir);
+        for (index, (dir, group)) in groups.into_iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            println!(
+                "{}",
+                abbreviate_path(&dir, &global_args.dotfiles_root, &home_dir).bold()
+            );
+            for (path, status) in group {
+                print_entry(path, status, printed.len());
+                printed.push(path.to_owned());
+            }
+        }
+    } else {
+        for (path, status) in entries {
+            print_entry(path, status, printed.len());
+            printed.push(path.to_owned());
+        }
+    }
+    if number {
+        LastStatus {
+            dotfiles_root: global_args.dotfiles_root.clone(),
+            entries: printed,
+        }
+        .to_file(&get_last_status_file_path()?)?;
+    }
+
+    check_result
+}
+
+/// returns the names of the states failing the status check, see `--check` and `--strict`.
+fn get_failing_states(args: &ArgMatches) -> Vec<String> {
+    let mut failing: Vec<String> = match args.values_of(ARG_CHECK) {
+        Some(values) if values.len() > 0 => values.map(String::from).collect(),
+        // `--check` without a list of states fails on all problem states:
+        _ if args.is_present(ARG_CHECK) => PROBLEM_STATES.iter().map(|s| s.to_string()).collect(),
+        _ => DEFAULT_FAILING_STATES
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+    };
+    if args.is_present(ARG_STRICT) {
+        failing.push("unlinked".to_string());
+    }
+
+    failing
+}
+
+/// returns the number of the given states whose name is one of the given failing state names.
+fn count_failures(states: &[LinkState], failing: &[String]) -> usize {
+    states
+        .iter()
+        .filter(|state| state.is_any_of(failing))
+        .count()
+}
+
+/// returns the given entries as TAP version 13 report with a test point per mapping, see `status -h`.
+/// Failing test points contain a YAML block describing the problem.
+fn format_tap(entries: &[(&Path, &LinkState)]) -> String {
+    let mappings: Vec<_> = entries
+        .iter()
+        .filter(|(_, state)| !matches!(state, LinkState::Unmapped))
+        .collect();
+    let mut output = format!("TAP version 13\n1..{}\n", mappings.len());
+    for (index, (path, state)) in mappings.iter().enumerate() {
+        // `#` starts a directive in TAP and must be escaped in descriptions:
+        let description = path.to_string_lossy().replace('#', "\\#");
+        let number = index + 1;
+        match state {
+            LinkState::Linked | LinkState::External(_) => {
+                output.push_str(&format!("ok {} - {}\n", number, description))
+            }
+            LinkState::Unmanaged => output.push_str(&format!(
+                "ok {} - {} # SKIP mapped with manage = \"once\"\n",
+                number, description
+            )),
+            LinkState::Pending(_) => output.push_str(&format!(
+                "ok {} - {} # SKIP pinned mapping does not exist yet\n",
+                number, description
+            )),
+            _ => {
+                // JSON strings are valid YAML scalars, which takes care of quoting:
+                let quote = |text: &str| json!(text).to_string();
+                // safe, all states have an explanation:
+                let (meaning, _) = explain_state(state.name()).unwrap();
+                output.push_str(&format!("not ok {} - {}\n  ---\n", number, description));
+                output.push_str(&format!("  state: {}\n", quote(state.name())));
+                output.push_str(&format!("  kind: {}\n", quote(state.kind())));
+                if let Some(detail) = state.path() {
+                    output.push_str(&format!("  path: {}\n", quote(&detail.to_string_lossy())));
+                }
+                output.push_str(&format!("  message: {}\n  ...\n", quote(meaning)));
+            }
+        }
+    }
+
+    output
+}
+
+/// returns the color of the state with the given name in the built-in theme with the given name.
+/// Colors set in `overrides` take precedence, unknown color names fall back to the theme.
+/// Unknown themes fall back to the default theme.
+fn get_state_color(theme: &str, overrides: &HashMap<String, String>, state_name: &str) -> Color {
+    if let Some(color) = overrides
+        .get(state_name)
+        .and_then(|color| color.parse().ok())
+    {
+        return color;
+    }
+
+    match (theme, state_name) {
+        ("high-contrast", "linked") => Color::BrightGreen,
+        ("high-contrast", "unlinked") => Color::BrightYellow,
+        ("high-contrast", "invalid") => Color::BrightMagenta,
+        ("high-contrast", "conflict") => Color::BrightRed,
+        ("high-contrast", "external") => Color::BrightCyan,
+        ("high-contrast", "stale") => Color::BrightBlue,
+        ("high-contrast", "pending") => Color::BrightBlack,
+        ("high-contrast", _) => Color::BrightWhite,
+        (_, "linked") => Color::Green,
+        (_, "unlinked") => Color::Yellow,
+        (_, "invalid") => Color::Magenta,
+        (_, "conflict") => Color::Red,
+        (_, "external") => Color::Cyan,
+        (_, "stale") => Color::Blue,
+        (_, "pending") => Color::BrightBlack,
+        _ => Color::White,
+    }
+}
+
+/// returns the default symbol of the state with the given name, see `status --symbols`.
+fn get_state_symbol(state_name: &str) -> &'static str {
+    match state_name {
+        "linked" => "✓",
+        "unlinked" => "○",
+        "invalid" => "✗",
+        "conflict" => "!",
+        "unmapped" => "?",
+        "unmanaged" => "-",
+        "external" => "↗",
+        "pending" => "…",
+        _ => "~",
+    }
+}
+
+/// returns the label printed in front of each entry per state name, either its upper-case name
+/// or its symbol if `symbols` is true. Symbols set in `overrides` take precedence over the default ones.
+/// All labels are padded to the same width, so the paths are aligned in a column.
+fn get_state_labels(
+    symbols: bool,
+    overrides: &HashMap<String, String>,
+) -> HashMap<&'static str, String> {
+    let labels: Vec<(&str, String)> = LinkState::NAMES
+        .iter()
+        .map(|name| {
+            let label = match overrides.get(*name) {
+                _ if !symbols => name.to_uppercase(),
+                Some(symbol) => symbol.to_owned(),
+                None => get_state_symbol(name).to_string(),
+            };
+            (*name, label)
+        })
+        .collect();
+    let width = labels
+        .iter()
+        .map(|(_, label)| label.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    labels
+        .into_iter()
+        .map(|(name, label)| (name, format!("{:<width$}", label, width = width)))
+        .collect()
+}
+
+/// Describes the metadata of an entry in the dotfiles directory, see `--with-metadata`.
+/// Symlinks in the dotfiles directory are not followed.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct EntryMetadata {
+    /// either `file`, `dir` or `symlink`
+    pub kind: &'static str,
+    /// the size in bytes as reported by the file system
+    pub size: u64,
+    /// the last modification time, if supported by the file system.
+    /// Serialized as seconds since the unix epoch, like in `dump`.
+    #[serde(serialize_with = "serialize_unix_secs")]
+    pub modified: Option<SystemTime>,
+}
+
+fn serialize_unix_secs<S: Serializer>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    time.and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .serialize(serializer)
+}
+impl EntryMetadata {
+    /// reads the metadata of the given path. Returns `None` if it does not exist or cannot be read.
+    pub fn from_path(path: &Path) -> Option<EntryMetadata> {
+        let metadata = path.symlink_metadata().ok()?;
+        let kind = if metadata.file_type().is_symlink() {
+            "symlink"
+        } else if metadata.is_dir() {
+            "dir"
+        } else {
+            "file"
+        };
+
+        Some(EntryMetadata {
+            kind,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    /// returns a compact human readable summary of this metadata, eg. `file 1.2K 3d`.
+    /// The size of directories is omitted, because it does not reflect their content.
+    pub fn annotation(&self, now: SystemTime) -> String {
+        let mut parts = vec![self.kind.to_string()];
+        if self.kind != "dir" {
+            parts.push(format_size(self.size));
+        }
+        if let Some(age) = self
+            .modified
+            .and_then(|modified| now.duration_since(modified).ok())
+        {
+            parts.push(format_age(age));
+        }
+
+        parts.join(" ")
+    }
+}
+
+/// returns true if the given path is a directory to traverse when looking for mappings.
+/// Symlinks to directories are only traversed if `follow_symlinks` is true and their target has not been
+/// traversed through a symlink yet. This stops cycles, eg. a symlink to one of its parent directories,
+/// because each target is followed once at most. The resolved targets are added to `followed`.
+fn should_traverse(
+    path: &Path,
+    follow_symlinks: bool,
+    followed: &mut HashSet<PathBuf>,
+) -> io::Result<bool> {
+    let meta = path.symlink_metadata()?;
+    if meta.is_dir() || !follow_symlinks || !meta.file_type().is_symlink() || !path.is_dir() {
+        return Ok(meta.is_dir());
+    }
+    if followed.insert(path.canonicalize()?) {
+        log::debug(format_args!("{} is a symlink and followed", path.display()));
+        Ok(true)
+    } else {
+        log::debug(format_args!(
+            "{} is not followed, its target has been traversed already",
+            path.display()
+        ));
+        Ok(false)
+    }
+}
+
+/// returns true if the given path is a directory, without following symlinks.
+pub fn is_dir(path: &Path) -> bool {
+    path.symlink_metadata()
+        .map(|meta| meta.is_dir())
+        .unwrap_or(false)
+}
+
+/// formats the given number of bytes using the largest fitting binary unit, eg. `1536 => 1.5K`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
     }
 
-    Ok(())
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", size, UNITS[unit])
+}
+
+/// formats the given duration using its largest unit, eg. `3 days and 4 hours => 3d`.
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// returns the detailed meaning of the state with the given name and the common steps to resolve it.
+/// Returns `None` if there is no state with this name, see `LinkState::NAMES`.
+pub fn explain_state(state_name: &str) -> Option<(&'static str, &'static str)> {
+    match state_name {
+        "linked" => Some((
+            "The path in your home directory is a symlink pointing to its counterpart in your dotfiles directory.",
+            "Nothing to do, changes in your home directory end up in your dotfiles directory.",
+        )),
+        "unlinked" => Some((
+            "The path is mapped and exists in your dotfiles directory, but there is nothing at its location in your home directory.",
+            "Run `dotfiles link` to create the missing symlinks.",
+        )),
+        "invalid" => Some((
+            "The path is listed under mappings in your configuration, but does not exist in your dotfiles directory.",
+            "Restore the file in your dotfiles directory, eg. using `dotfiles rollback`, or remove the mapping from your configuration.",
+        )),
+        "conflict" => Some((
+            "The path exists in your home directory, but it is either not a symlink or a symlink pointing somewhere else than your dotfiles directory.",
+            "Run `dotfiles link --on-conflict backup` to move the blocking file aside and link it, or remove the blocking file manually and run `dotfiles link`.",
+        )),
+        "unmapped" => Some((
+            "The path exists in your dotfiles directory, but is not listed under mappings in your configuration. It will never be linked.",
+            "Add the path to the mappings in your configuration and run `dotfiles link`, or ignore it if it is not meant to be linked.",
+        )),
+        "unmanaged" => Some((
+            "The path is mapped with `manage = \"once\"`. It was linked when it was added, but is neither linked nor checked anymore.",
+            "Nothing to do. Remove `manage = \"once\"` from its mapping if it should be checked again.",
+        )),
+        "external" => Some((
+            "The path in your home directory is a symlink pointing outside of your dotfiles directory, which is allowed by `allow_external_target = true` in its mapping.",
+            "Nothing to do. Remove `allow_external_target` from its mapping if the symlink should point to your dotfiles directory.",
+        )),
+        "stale" => Some((
+            "Either the path is mapped with `mode = \"copy\"`, but the file in your home directory differs from the file in your dotfiles directory, rendered as template if `template = true`. Or the path in your home directory is a symlink into your dotfiles directory pointing to a path which no longer exists there, eg. because it has been renamed.",
+            "Run `dotfiles link` to replace the copy with the current version or to relink the symlink. Move changes made to a copy to your dotfiles directory first, otherwise they are lost.",
+        )),
+        "pending" => Some((
+            "The path is mapped with `pinned = true`, but does not exist in your dotfiles directory (yet). Unlike an invalid mapping, it is not reported as an error.",
+            "Nothing to do. Create the file in your dotfiles directory and run `dotfiles link` once it is needed, or remove the mapping with `dotfiles remove`.",
+        )),
+        _ => None,
+    }
+}
+
+/// buckets the given entries by the directory their symlinks are located in, eg.
+/// `.config/nvim/init.vim => ~/.config/nvim`. The buckets are sorted by directory,
+/// the entries of a bucket keep their order.
+fn group_by_home_dir<'a, T>(
+    entries: impl IntoIterator<Item = (&'a Path, T)>,
+    home_dir: &Path,
+) -> BTreeMap<PathBuf, Vec<(&'a Path, T)>> {
+    let mut groups: BTreeMap<PathBuf, Vec<(&'a Path, T)>> = BTreeMap::new();
+    for (path, item) in entries {
+        let home_path = home_dir.join(path);
+        let dir = home_path.parent().unwrap_or(home_dir).to_owned();
+        groups.entry(dir).or_default().push((path, item));
+    }
+
+    groups
+}
+
+/// shortens the given text to `max_width` characters by replacing its middle part with `…`,
+/// eg. `.config/nvim/lua/plugins/init.lua => .config/…/init.lua`.
+/// Keeps more of the end than the beginning, because the end of a path is usually more telling.
+fn elide_middle(text: &str, max_width: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max_width || max_width < 3 {
+        return text.to_string();
+    }
+
+    let head = (max_width - 1) / 3;
+    let tail = max_width - 1 - head;
+    let head: String = chars[..head].iter().collect();
+    let tail: String = chars[chars.len() - tail..].iter().collect();
+
+    format!("{}…{}", head, tail)
 }
 
 pub enum MappingSourceStatus {
@@ -95,7 +864,7 @@ pub struct MappingStatus<'a> {
 impl<'a> MappingStatus<'a> {
     pub fn from_mapping(mapping: &'a Mapping) -> Result<MappingStatus<'a>, AppError> {
         Ok(MappingStatus {
-            path: mapping,
+            path: &mapping.path,
             src_state: MappingSourceStatus::Existing,
             target_state: MappingTargetStatus::Missing,
         })
@@ -107,6 +876,8 @@ pub enum DotfilesEntryState {
     Mapped,
     Unmapped,
     Invalid,
+    /// the entry is mapped, but should not be checked, see `config::Manage::Once`.
+    Unmanaged,
 }
 pub type DotfilesEntry = (PathBuf, DotfilesEntryState);
 
@@ -115,49 +886,224 @@ pub type DotfilesEntry = (PathBuf, DotfilesEntryState);
 /// If a directory or file is nested in a parent which is not part of any configured mapping, it is also excluded.
 /// Each returned path additionally contains the information, if it is linked or unlinked based on the configured mappings.
 /// All entries of config.mappings which could not be found in the dotfiles directory are also attached with the state `Invalid`.
-fn get_dotfiles_entries(
+/// Because the traversal skips some directories, such as the hosts directory, these mappings are checked for existence once more.
+/// Unmapped entries matching a pattern of the ignore file are excluded, see `IgnoreRules`.
+pub fn get_dotfiles_entries(
     global_args: &GlobalArgs,
     config: &AppConfig,
+) -> Result<Vec<DotfilesEntry>, AppError> {
+    let ignore = IgnoreRules::from_dotfiles_root(&global_args.dotfiles_root)?;
+    traverse_dotfiles_dir(global_args, config, &ignore).map_err(|err| {
+        AppError::FsOther(format!(
+            "Failed to read your dotfile directory at {}: {}",
+            global_args.dotfiles_root.display(),
+            err
+        ))
+    })
+}
+
+/// returns the entries of the dotfiles directory, see `get_dotfiles_entries`.
+fn traverse_dotfiles_dir(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    ignore: &IgnoreRules,
 ) -> io::Result<Vec<DotfilesEntry>> {
     let mut dotfiles: Vec<DotfilesEntry> = Vec::new();
     let dotfile_root = &global_args.dotfiles_root;
     let mut queue = VecDeque::from_iter(fs::read_dir(dotfile_root)?);
     let mappings = &config.mappings;
+    // the resolved targets of the followed symlinks, see `should_traverse`.
+    // The dotfiles root is traversed already, so symlinks to it are never followed:
+    let mut followed: HashSet<PathBuf> = HashSet::new();
+    if config.follows_symlinks() {
+        followed.insert(dotfile_root.canonicalize()?);
+    }
 
     while let Some(next) = queue.pop_front() {
         let path = next?.path();
         // this is safe, because we are only iterating items contained in the dotfiles root directory:
         let rel_path = path.strip_prefix(dotfile_root).unwrap().to_owned();
-        // if the entry itself is mapped: add it to the output but don't traverse it further:
-        if mappings.contains(&rel_path) {
-            dotfiles.push((rel_path, DotfilesEntryState::Mapped));
+        // host-specific files are never listed themselves, they only override mapped paths:
+        if rel_path == Path::new(HOSTS_DIR) && config.get_mapping(&rel_path).is_none() {
+            log::debug(format_args!(
+                "{} is skipped, it contains host-specific files",
+                rel_path.display()
+            ));
+            continue;
+        }
+        // if the entry itself is mapped: add it to the output but only traverse it further
+        // if it contains nested mappings, see `AppConfig::allow_nested`:
+        if let Some(mapping) = config.get_mapping(&rel_path) {
+            let state = if mapping.is_managed() {
+                DotfilesEntryState::Mapped
+            } else {
+                DotfilesEntryState::Unmanaged
+            };
+            let has_nested = mappings
+                .iter()
+                .any(|m| m.path != rel_path && m.path.starts_with(&rel_path));
+            if has_nested && should_traverse(&path, config.follows_symlinks(), &mut followed)? {
+                log::debug(format_args!(
+                    "{} is mapped and traversed, it contains nested mappings",
+                    rel_path.display()
+                ));
+                queue.extend(fs::read_dir(&path)?);
+            }
+            log::trace(format_args!("{} is {:?}", rel_path.display(), state));
+            dotfiles.push((rel_path, state));
         // there is no mapping on or into the current path: stop traversing it,
-        // but add the current path itself to output (as "unmapped")
-        } else if mappings.into_iter().any(|m| m.starts_with(&rel_path)) == false {
-            dotfiles.push((rel_path, DotfilesEntryState::Unmapped));
-        // make sure we only traverse into directories and do not follow symlinks:
-        } else if path.symlink_metadata()?.is_dir() {
+        // but add the current path itself to output (as "unmapped"), unless it is covered by a mapped parent:
+        } else if !mappings.iter().any(|m| m.path.starts_with(&rel_path)) {
+            if let Some(mapping) = config.get_covering_mapping(&rel_path) {
+                log::debug(format_args!(
+                    "{} is skipped, it is covered by the mapping {}",
+                    rel_path.display(),
+                    mapping.path.display()
+                ));
+            // a config file provided via CLI is part of the repository, but never meant to be linked:
+            } else if global_args.config_file.as_ref() == Some(&path) {
+                log::debug(format_args!(
+                    "{} is skipped, it is the config file provided via -c",
+                    rel_path.display()
+                ));
+            // as are config files included by the config:
+            } else if config.included.iter().any(|included| included.path == path) {
+                log::debug(format_args!(
+                    "{} is skipped, it is an included config file",
+                    rel_path.display()
+                ));
+            } else if ignore.is_ignored(&rel_path, || is_dir(&path)) {
+                log::debug(format_args!(
+                    "{} is skipped, it is ignored by {}",
+                    rel_path.display(),
+                    IGNORE_FILE_NAME
+                ));
+            } else {
+                log::trace(format_args!("{} is unmapped", rel_path.display()));
+                dotfiles.push((rel_path, DotfilesEntryState::Unmapped));
+            }
+        // make sure we only traverse into directories and do not follow symlinks, unless configured:
+        } else if should_traverse(&path, config.follows_symlinks(), &mut followed)? {
             // there exist one or more mappings into the current directory:
             // we do not add the current path to the output, but traverse it instead.
+            log::trace(format_args!(
+                "{} is traversed, it contains mappings",
+                rel_path.display()
+            ));
             queue.extend(fs::read_dir(&path)?);
         }
     }
 
-    // every other entry in config.mapping which is not part of dotfiles yet, has to be invalid,
-    // because it was not found during the traversal of the dotfiles directory.
-    // insert them with state `Invalid`:
+    // every other entry in config.mapping which is not part of dotfiles yet was not found during the traversal
+    // of the dotfiles directory. Skipped directories could still contain it, so it is only `Invalid` if it
+    // does not exist at its path in the dotfiles directory either:
     dotfiles.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
     for mapping in mappings {
-        match dotfiles.binary_search_by(|(path, _)| path.cmp(mapping)) {
+        // unmanaged mappings are never checked, not even for their existence:
+        let state = if !mapping.is_managed() {
+            DotfilesEntryState::Unmanaged
+        } else if get_host_override(global_args, &mapping.path).is_some()
+            || dotfile_root.join(&mapping.path).symlink_metadata().is_ok()
+        {
+            DotfilesEntryState::Mapped
+        } else {
+            DotfilesEntryState::Invalid
+        };
+        match dotfiles.binary_search_by(|(path, _)| path.cmp(&mapping.path)) {
             Ok(_) => (),
-            Err(pos) => dotfiles.insert(pos, (mapping.to_owned(), DotfilesEntryState::Invalid)),
+            Err(pos) => {
+                log::debug(format_args!(
+                    "{} was not found while traversing your dotfiles directory and is {:?}",
+                    mapping.path.display(),
+                    state
+                ));
+                dotfiles.insert(pos, (mapping.path.to_owned(), state))
+            }
         }
     }
 
     Ok(dotfiles)
 }
 
+/// returns the prefix of the mapped paths the given absolute path refers to, the same way `add` turns a path into
+/// a mapping: paths in the dotfiles directory are relative to it, all others relative to the home directory.
+/// Fails if the path is outside of both directories.
+fn get_path_prefix(
+    path: &Path,
+    dotfiles_root: &Path,
+    home_dir: &Path,
+) -> Result<PathBuf, AppError> {
+    // the dotfiles directory is often located inside the home directory, so it is checked first:
+    let rel_path = path
+        .strip_prefix(dotfiles_root)
+        .or_else(|_| path.strip_prefix(home_dir))
+        .map_err(|_| {
+            AppError::CliInvalidArgValue(
+                ARG_PATH.to_string(),
+                format!(
+                    "{} is neither inside your home nor your dotfiles directory",
+                    path.display()
+                ),
+            )
+        })?;
+    log::debug(format_args!(
+        "only showing the entries of {}",
+        rel_path.display()
+    ));
+
+    Ok(config::normalize_mapping(rel_path.to_owned()))
+}
+
+/// returns true if the given entry path is part of the subtree starting at `only`.
+/// Paths are compared component-wise, eg. `.config/nvim` does not contain `.config/nvim-old`.
+/// All paths are in scope if `only` is `None`.
+pub fn is_in_scope(path: &Path, only: Option<&Path>) -> bool {
+    only.is_none_or(|prefix| path.starts_with(prefix))
+}
+
+/// returns all entries of the dotfiles directory (see `get_dotfiles_entries`) in the subtree starting at `only`
+/// together with the state of each entry in the given home directory, in the same order.
+pub fn get_entry_states(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    only: Option<&Path>,
+) -> Result<(Vec<DotfilesEntry>, Vec<LinkState>), AppError> {
+    let mut entries = get_dotfiles_entries(global_args, config)?;
+    // filtered before resolving the states, which is the expensive part:
+    entries.retain(|(path, _)| is_in_scope(path, only));
+    let mut states = vec![];
+    for entry in &entries {
+        let state =
+            get_dotfiles_entry_state(global_args, config, entry, home_dir).map_err(|err| {
+                AppError::FsOther(format!("Failed to read your linked dotfiles: {}", err))
+            })?;
+        states.push(state);
+    }
+
+    Ok((entries, states))
+}
+
+/// returns the path of the file in the dotfiles directory the given mapped path should be linked to.
+/// A host-specific file at `DOTFILES/hosts/<hostname>/<path>` takes precedence over the shared `DOTFILES/<path>`.
+pub fn get_dotfiles_source(global_args: &GlobalArgs, path: &Path) -> PathBuf {
+    get_host_override(global_args, path).unwrap_or_else(|| global_args.dotfiles_root.join(path))
+}
+
+/// returns the host-specific file overriding the given mapped path on the current host, if it exists.
+pub fn get_host_override(global_args: &GlobalArgs, path: &Path) -> Option<PathBuf> {
+    let hostname = global_args.hostname.as_ref()?;
+    let host_path = global_args
+        .dotfiles_root
+        .join(HOSTS_DIR)
+        .join(hostname)
+        .join(path);
+
+    host_path.symlink_metadata().ok().map(|_| host_path)
+}
+
 /// Describes the status of a link configured in mappings
+#[derive(Debug)]
 pub enum LinkState {
     /// file does not exist in the dotfiles repository
     Invalid(PathBuf),
@@ -171,42 +1117,907 @@ pub enum LinkState {
     ConflictNoLink(PathBuf),
     /// File in dotfiles repo is not listen in mappings
     Unmapped,
+    /// file is mapped with `manage = "once"` and therefore not checked
+    Unmanaged,
+    /// symlink found pointing outside of the dotfiles repository, which is allowed by the mapping
+    External(PathBuf),
+    /// the copy of a mapping using `LinkMode::Copy` differs from its (rendered) source
+    Stale(PathBuf),
+    /// symlink found pointing into the dotfiles repository, but at a path which does not exist there anymore,
+    /// eg. because the file has been renamed
+    StaleLink(PathBuf),
+    /// file does not exist in the dotfiles repository, but its mapping is `pinned` and therefore kept
+    Pending(PathBuf),
+}
+
+impl LinkState {
+    /// the names of all states as returned by `LinkState::name`.
+    pub const NAMES: [&'static str; 9] = [
+        "linked",
+        "unlinked",
+        "invalid",
+        "conflict",
+        "unmapped",
+        "unmanaged",
+        "external",
+        "stale",
+        "pending",
+    ];
+
+    /// returns the path this state refers to, such as the conflicting file, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            LinkState::Invalid(path)
+            | LinkState::ConflictWrongTarget(path)
+            | LinkState::ConflictNoLink(path)
+            | LinkState::External(path)
+            | LinkState::Stale(path)
+            | LinkState::StaleLink(path)
+            | LinkState::Pending(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// returns the name of this state as it is used in CLI arguments, such as `conflict`.
+    /// Both kinds of conflicts share the same name, as do stale copies and symlinks.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkState::Linked => "linked",
+            LinkState::Unlinked => "unlinked",
+            LinkState::Invalid(_) => "invalid",
+            LinkState::ConflictNoLink(_) | LinkState::ConflictWrongTarget(_) => "conflict",
+            LinkState::Unmapped => "unmapped",
+            LinkState::Unmanaged => "unmanaged",
+            LinkState::External(_) => "external",
+            LinkState::Stale(_) | LinkState::StaleLink(_) => "stale",
+            LinkState::Pending(_) => "pending",
+        }
+    }
+
+    /// returns true if the name of this state is one of the given names, see `LinkState::name`.
+    /// Used to select states by the names provided via CLI, such as `--state conflict`.
+    pub fn is_any_of<S: AsRef<str>>(&self, names: &[S]) -> bool {
+        names.iter().any(|name| name.as_ref() == self.name())
+    }
+
+    /// returns the stable name of this state used in machine-readable output, such as `ConflictNoLink`.
+    /// In contrast to `name`, both kinds of conflicts and stale entries are distinguished.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LinkState::Linked => "Linked",
+            LinkState::Unlinked => "Unlinked",
+            LinkState::Invalid(_) => "Invalid",
+            LinkState::ConflictNoLink(_) => "ConflictNoLink",
+            LinkState::ConflictWrongTarget(_) => "ConflictWrongTarget",
+            LinkState::Unmapped => "Unmapped",
+            LinkState::Unmanaged => "Unmanaged",
+            LinkState::External(_) => "External",
+            LinkState::Stale(_) => "Stale",
+            LinkState::StaleLink(_) => "StaleLink",
+            LinkState::Pending(_) => "Pending",
+        }
+    }
+}
+
+/// serializes a state as its `kind`, the path of the state is not part of it.
+impl Serialize for LinkState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.kind())
+    }
+}
+
+/// A single entry of the machine-readable output of `status --json`.
+#[derive(Serialize)]
+struct StatusEntry<'a> {
+    path: &'a Path,
+    state: &'a LinkState,
+    /// the path the state refers to, see `LinkState::path`.
+    detail: Option<&'a Path>,
+    /// only set with `--with-metadata`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<EntryMetadata>,
+    /// only set for directories with `--count-dir-contents`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_count: Option<usize>,
+}
+
+/// builds the `--json` entry of the given path. Its metadata and file count are only read if requested.
+fn get_status_entry<'a>(
+    global_args: &GlobalArgs,
+    path: &'a Path,
+    state: &'a LinkState,
+    with_metadata: bool,
+    count_dir_contents: bool,
+) -> StatusEntry<'a> {
+    let mut entry = StatusEntry {
+        path,
+        state,
+        detail: state.path(),
+        metadata: None,
+        file_count: None,
+    };
+    if with_metadata || count_dir_contents {
+        let source = get_dotfiles_source(global_args, path);
+        if with_metadata {
+            entry.metadata = EntryMetadata::from_path(&source);
+        }
+        if count_dir_contents && is_dir(&source) {
+            entry.file_count = Some(count_files(&source));
+        }
+    }
+
+    entry
 }
 
 /// Returns the status for a given dotfiles entry.
 pub fn get_dotfiles_entry_state(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    entry: &DotfilesEntry,
+    target_dir: &Path,
+) -> io::Result<LinkState> {
+    let mapping = config.get_mapping(&entry.0);
+    let state = match mapping {
+        Some(mapping) if mapping.is_copy() && entry.1 == DotfilesEntryState::Mapped => {
+            resolve_copy_state(global_args, mapping, target_dir)?
+        }
+        Some(mapping) if mapping.is_reverse() && entry.1 != DotfilesEntryState::Unmanaged => {
+            resolve_reverse_state(global_args, mapping, target_dir)?
+        }
+        _ => match resolve_dotfiles_entry_state(global_args, entry, target_dir)? {
+            // symlinks pointing outside of the dotfiles directory may be allowed explicitly:
+            LinkState::ConflictWrongTarget(target)
+                if !target.starts_with(&global_args.dotfiles_root)
+                    && mapping.is_some_and(|mapping| mapping.allow_external_target) =>
+            {
+                LinkState::External(target)
+            }
+            state => state,
+        },
+    };
+    // pinned mappings are kept on purpose while their file is missing, eg. until it is created on this host:
+    let state = match state {
+        LinkState::Invalid(path) if mapping.is_some_and(|mapping| mapping.pinned) => {
+            LinkState::Pending(path)
+        }
+        state => state,
+    };
+    trace::event(
+        "entry_state",
+        json!({ "path": entry.0, "state": format!("{:?}", state) }),
+    );
+
+    Ok(state)
+}
+
+/// returns the state of an existing mapping using `LinkMode::Copy`: it is linked if the file in the
+/// home directory equals the rendered source, stale if it differs or is a symlink, eg. from an earlier symlink mapping.
+/// Directories blocking the copy are reported as conflict.
+fn resolve_copy_state(
+    global_args: &GlobalArgs,
+    mapping: &Mapping,
+    target_dir: &Path,
+) -> io::Result<LinkState> {
+    let actual_file_path = target_dir.join(&mapping.path);
+    let actual_file_meta = match actual_file_path.symlink_metadata() {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(LinkState::Unlinked),
+        Err(err) => return Err(err),
+    };
+    if actual_file_meta.is_dir() {
+        return Ok(LinkState::ConflictNoLink(actual_file_path));
+    }
+    if actual_file_meta.file_type().is_symlink() {
+        return Ok(LinkState::Stale(actual_file_path));
+    }
+
+    let source = get_dotfiles_source(global_args, &mapping.path);
+    let expected = render_mapping(global_args, target_dir, mapping, &source)?;
+    // only the content is compared, a copy with changed permissions is not stale:
+    if fs::read(&actual_file_path)? == expected {
+        Ok(LinkState::Linked)
+    } else {
+        Ok(LinkState::Stale(actual_file_path))
+    }
+}
+
+/// returns the state of a mapping using `LinkDirection::Reverse`: the file in the home directory must exist
+/// and the path in the dotfiles directory must be a symlink pointing to it.
+/// Paths mentioned in the returned state refer to the dotfiles directory, except for the missing file of `Invalid`.
+fn resolve_reverse_state(
+    global_args: &GlobalArgs,
+    mapping: &Mapping,
+    target_dir: &Path,
+) -> io::Result<LinkState> {
+    let home_path = target_dir.join(&mapping.path);
+    let link_path = global_args.dotfiles_root.join(&mapping.path);
+    if home_path.symlink_metadata().is_err() {
+        return Ok(LinkState::Invalid(home_path));
+    }
+    let link_meta = match link_path.symlink_metadata() {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(LinkState::Unlinked),
+        Err(err) => return Err(err),
+    };
+    if !link_meta.file_type().is_symlink() {
+        return Ok(LinkState::ConflictNoLink(link_path));
+    }
+
+    let actual_target = resolve_symlink_target(&link_path)?;
+    if actual_target != home_path {
+        Ok(LinkState::ConflictWrongTarget(actual_target))
+    } else {
+        Ok(LinkState::Linked)
+    }
+}
+
+fn resolve_dotfiles_entry_state(
     global_args: &GlobalArgs,
     entry: &DotfilesEntry,
-    target_dir: &PathBuf,
+    target_dir: &Path,
 ) -> io::Result<LinkState> {
     let (path, state) = entry;
 
     // path to the symlink at the target location
     let actual_file_path = target_dir.join(path);
     // path to the file in the dotfiles repository
-    let expected_target = global_args.dotfiles_root.join(path);
+    let expected_target = get_dotfiles_source(global_args, path);
 
     // invalid and unmapped entries can be translated directly:
     match state {
         DotfilesEntryState::Invalid => return Ok(LinkState::Invalid(expected_target)),
         DotfilesEntryState::Unmapped => return Ok(LinkState::Unmapped),
+        DotfilesEntryState::Unmanaged => return Ok(LinkState::Unmanaged),
         _ => (),
     };
 
-    // the entry in the dotfiles exists, but the corresponding file in the home directory does not:
-    if actual_file_path.exists() == false {
-        return Ok(LinkState::Unlinked);
+    // the entry in the dotfiles exists, but the corresponding file in the home directory does not.
+    // Broken symlinks are checked below, they may point to a path which no longer exists in the dotfiles:
+    let actual_file_meta = match actual_file_path.symlink_metadata() {
+        Ok(meta) => meta,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(LinkState::Unlinked),
+        Err(err) => return Err(err),
     };
-
-    let actual_file_meta = actual_file_path.symlink_metadata()?;
-    if actual_file_meta.file_type().is_symlink() == false {
+    if !actual_file_meta.file_type().is_symlink() {
+        // nested mappings are linked by the symlink of their mapped parent directory,
+        // in which case the path in the home directory resolves to the file in the dotfiles directory itself:
+        if let (Ok(actual), Ok(expected)) = (
+            actual_file_path.canonicalize(),
+            expected_target.canonicalize(),
+        ) {
+            if actual == expected {
+                return Ok(LinkState::Linked);
+            }
+        }
         return Ok(LinkState::ConflictNoLink(actual_file_path));
     };
 
-    let actual_target = fs::read_link(&actual_file_path)?;
-    if actual_target != expected_target {
-        Ok(LinkState::ConflictWrongTarget(actual_target))
-    } else {
+    let actual_target = resolve_symlink_target(&actual_file_path)?;
+    if actual_target == expected_target {
         Ok(LinkState::Linked)
+    } else if actual_target.starts_with(&global_args.dotfiles_root)
+        && actual_target.symlink_metadata().is_err()
+    {
+        Ok(LinkState::StaleLink(actual_target))
+    } else {
+        Ok(LinkState::ConflictWrongTarget(actual_target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        count_failures, elide_middle, explain_state, format_age, format_size, format_tap,
+        get_dotfiles_entries, get_dotfiles_entry_state, get_path_prefix, get_state_color,
+        get_state_labels, get_status_entry, group_by_home_dir, is_in_scope, DotfilesEntryState,
+        LinkState, DEFAULT_FAILING_STATES,
+    };
+    use crate::{
+        cli::GlobalArgs,
+        commands::setup_test_dirs,
+        config::{AppConfig, IncludedConfig, Mapping},
+        files::symlink,
+    };
+    use colored::Color;
+    use std::{
+        collections::HashMap,
+        fs,
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    #[test]
+    fn count_failures_only_counts_the_given_states() {
+        let states = vec![
+            LinkState::Linked,
+            LinkState::Unmapped,
+            LinkState::ConflictNoLink(PathBuf::from("/h/.a")),
+            LinkState::ConflictWrongTarget(PathBuf::from("/h/.b")),
+        ];
+        let failing = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(count_failures(&states, &failing(&["conflict"])), 2);
+        assert_eq!(
+            count_failures(&states, &failing(&["invalid", "unmapped"])),
+            1
+        );
+        assert_eq!(count_failures(&states, &failing(&["external"])), 0);
+    }
+
+    #[test]
+    fn format_tap_lists_a_test_point_per_mapping() {
+        let conflict = LinkState::ConflictNoLink(PathBuf::from("/h/.bashrc"));
+        let entries = vec![
+            (Path::new(".vimrc"), &LinkState::Linked),
+            (Path::new("notes.md"), &LinkState::Unmapped),
+            (Path::new(".bashrc"), &conflict),
+            (Path::new(".profile"), &LinkState::Unmanaged),
+            (Path::new("#tmp"), &LinkState::Unlinked),
+        ];
+
+        let output = format_tap(&entries);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines[..4],
+            [
+                "TAP version 13",
+                "1..4",
+                "ok 1 - .vimrc",
+                "not ok 2 - .bashrc"
+            ]
+        );
+        assert_eq!(
+            lines[4..8],
+            [
+                "  ---",
+                "  state: \"conflict\"",
+                "  kind: \"ConflictNoLink\"",
+                "  path: \"/h/.bashrc\""
+            ]
+        );
+        assert!(lines[8].starts_with("  message: \"The path exists"));
+        assert_eq!(
+            lines[9..12],
+            [
+                "  ...",
+                "ok 3 - .profile # SKIP mapped with manage = \"once\"",
+                "not ok 4 - \\#tmp"
+            ]
+        );
+    }
+
+    #[test]
+    fn is_any_of_matches_both_kinds_of_conflicts_by_name() {
+        let state = LinkState::ConflictWrongTarget(PathBuf::from("/h/.a"));
+        assert!(state.is_any_of(&["unlinked", "conflict"]));
+        assert!(LinkState::ConflictNoLink(PathBuf::from("/h/.a")).is_any_of(&["conflict"]));
+        assert!(!state.is_any_of(&["linked"]));
+        assert!(!LinkState::Linked.is_any_of::<&str>(&[]));
+    }
+
+    #[test]
+    fn group_by_home_dir_buckets_by_parent_directory() {
+        let home = Path::new("/home/me");
+        let entries = vec![
+            (Path::new(".vimrc"), 1),
+            (Path::new(".config/nvim/init.vim"), 2),
+            (Path::new(".bashrc"), 3),
+            (Path::new(".config/nvim/lua"), 4),
+        ];
+
+        let groups: Vec<(PathBuf, Vec<i32>)> = group_by_home_dir(entries, home)
+            .into_iter()
+            .map(|(dir, group)| (dir, group.into_iter().map(|(_, item)| item).collect()))
+            .collect();
+
+        assert_eq!(
+            groups,
+            vec![
+                (PathBuf::from("/home/me"), vec![1, 3]),
+                (PathBuf::from("/home/me/.config/nvim"), vec![2, 4]),
+            ]
+        );
+    }
+
+    #[test]
+    fn explain_state_covers_all_states() {
+        for name in &LinkState::NAMES {
+            assert!(explain_state(name).is_some(), "no explanation for {}", name);
+        }
+        assert!(explain_state("unknown").is_none());
+    }
+
+    #[test]
+    fn link_states_are_serialized_as_their_kind() {
+        let states = vec![
+            LinkState::Linked,
+            LinkState::ConflictNoLink(PathBuf::from("/home/.vimrc")),
+        ];
+        assert_eq!(
+            serde_json::to_string(&states).unwrap(),
+            r#"["Linked","ConflictNoLink"]"#
+        );
+    }
+
+    #[test]
+    fn format_size_uses_largest_fitting_unit() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1536), "1.5K");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0M");
+    }
+
+    #[test]
+    fn format_age_uses_largest_unit() {
+        assert_eq!(format_age(Duration::from_secs(42)), "42s");
+        assert_eq!(format_age(Duration::from_secs(3 * 3600 + 59)), "3h");
+        assert_eq!(format_age(Duration::from_secs(4 * 86400 + 3600)), "4d");
+    }
+
+    #[test]
+    fn get_state_color_prefers_valid_overrides() {
+        let overrides: HashMap<String, String> =
+            vec![("linked", "blue"), ("conflict", "not-a-color")]
+                .into_iter()
+                .map(|(state, color)| (state.to_string(), color.to_string()))
+                .collect();
+
+        assert_eq!(
+            get_state_color("default", &overrides, "linked"),
+            Color::Blue
+        );
+        assert_eq!(
+            get_state_color("default", &overrides, "conflict"),
+            Color::Red
+        );
+        assert_eq!(
+            get_state_color("high-contrast", &overrides, "conflict"),
+            Color::BrightRed
+        );
+        assert_eq!(
+            get_state_color("unknown", &overrides, "unlinked"),
+            Color::Yellow
+        );
+    }
+
+    #[test]
+    fn get_state_labels_pads_names_and_symbols_to_the_same_width() {
+        let labels = get_state_labels(false, &HashMap::new());
+        assert_eq!(labels["linked"], "LINKED   ");
+        assert_eq!(labels["unmanaged"], "UNMANAGED");
+
+        let labels = get_state_labels(true, &HashMap::new());
+        assert_eq!(labels["linked"], "✓");
+        assert_eq!(labels["conflict"], "!");
+
+        let overrides: HashMap<String, String> = vec![("conflict".to_string(), "!!".to_string())]
+            .into_iter()
+            .collect();
+        let labels = get_state_labels(true, &overrides);
+        assert_eq!(labels["conflict"], "!!");
+        assert_eq!(labels["linked"], "✓ ");
+        assert_eq!(labels.len(), LinkState::NAMES.len());
+    }
+
+    #[test]
+    fn is_in_scope_compares_path_components() {
+        let only = Some(Path::new(".config/nvim"));
+        assert!(is_in_scope(Path::new(".config/nvim"), only));
+        assert!(is_in_scope(Path::new(".config/nvim/init.vim"), only));
+        assert!(!is_in_scope(Path::new(".config/nvim-old"), only));
+        assert!(!is_in_scope(Path::new(".config"), only));
+        assert!(is_in_scope(Path::new(".vimrc"), None));
+    }
+
+    #[test]
+    fn get_path_prefix_is_relative_to_the_dotfiles_or_home_directory() {
+        let home = Path::new("/home/me");
+        let dotfiles_root = Path::new("/home/me/dotfiles");
+        let prefix = |path: &str| get_path_prefix(Path::new(path), dotfiles_root, home);
+
+        assert_eq!(
+            prefix("/home/me/.config/nvim").unwrap(),
+            Path::new(".config/nvim")
+        );
+        assert_eq!(
+            prefix("/home/me/dotfiles/.config/nvim").unwrap(),
+            Path::new(".config/nvim")
+        );
+        // the whole home directory includes all entries:
+        assert!(is_in_scope(
+            Path::new(".vimrc"),
+            Some(&prefix("/home/me").unwrap())
+        ));
+        assert!(prefix("/etc/nvim").is_err());
+    }
+
+    #[test]
+    fn elide_middle_keeps_short_texts() {
+        assert_eq!(elide_middle(".vimrc", 10), ".vimrc");
+        assert_eq!(elide_middle(".vimrc", 6), ".vimrc");
+    }
+
+    #[test]
+    fn elide_middle_shortens_long_texts_to_max_width() {
+        let elided = elide_middle(".config/nvim/lua/plugins/init.lua", 19);
+        assert_eq!(elided, ".confi…ins/init.lua");
+        assert_eq!(elided.chars().count(), 19);
+    }
+
+    #[test]
+    fn status_entries_only_contain_metadata_if_requested() {
+        let (_tmp, _, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".vimrc"), "set nu").unwrap();
+        let path = Path::new(".vimrc");
+        let state = LinkState::Unlinked;
+        let to_json = |with_metadata: bool| {
+            let entry = get_status_entry(&global_args, path, &state, with_metadata, false);
+            let output = serde_json::to_string(&entry).unwrap();
+            serde_json::from_str::<serde_json::Value>(&output).unwrap()
+        };
+
+        let json = to_json(true);
+        assert_eq!(json["path"], ".vimrc");
+        assert_eq!(json["state"], "Unlinked");
+        assert_eq!(json["metadata"]["kind"], "file");
+        assert_eq!(json["metadata"]["size"], 6);
+        assert!(json["metadata"]["modified"].is_u64());
+        assert!(to_json(false).get("metadata").is_none());
+    }
+
+    #[test]
+    fn status_entries_of_directories_contain_the_file_count_if_requested() {
+        let (_tmp, _, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(dotfiles_root.join(".config/nvim/lua")).unwrap();
+        fs::write(dotfiles_root.join(".config/nvim/init.lua"), "").unwrap();
+        fs::write(dotfiles_root.join(".config/nvim/lua/plugins.lua"), "").unwrap();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        let state = LinkState::Unlinked;
+        let to_json = |path: &str, count_dir_contents: bool| {
+            let entry = get_status_entry(
+                &global_args,
+                Path::new(path),
+                &state,
+                false,
+                count_dir_contents,
+            );
+            let output = serde_json::to_string(&entry).unwrap();
+            serde_json::from_str::<serde_json::Value>(&output).unwrap()
+        };
+
+        assert_eq!(to_json(".config/nvim", true)["file_count"], 2);
+        assert!(to_json(".config/nvim", false).get("file_count").is_none());
+        // files have no file count:
+        assert!(to_json(".vimrc", true).get("file_count").is_none());
+    }
+
+    #[test]
+    fn get_dotfiles_entry_state_accepts_relative_symlinks() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        symlink("dotfiles/.vimrc", home.join(".vimrc")).unwrap();
+        fs::write(dotfiles_root.join(".bashrc"), "").unwrap();
+        symlink("./dotfiles/../dotfiles/.vimrc", home.join(".bashrc")).unwrap();
+        let config = AppConfig::default();
+
+        let linked = (PathBuf::from(".vimrc"), DotfilesEntryState::Mapped);
+        let state = get_dotfiles_entry_state(&global_args, &config, &linked, &home).unwrap();
+        assert!(matches!(state, LinkState::Linked), "got {:?}", state);
+
+        let wrong = (PathBuf::from(".bashrc"), DotfilesEntryState::Mapped);
+        let state = get_dotfiles_entry_state(&global_args, &config, &wrong, &home).unwrap();
+        assert!(
+            matches!(state, LinkState::ConflictWrongTarget(ref target) if *target == global_args.dotfiles_root.join(".vimrc")),
+            "got {:?}",
+            state
+        );
+    }
+
+    #[test]
+    fn get_dotfiles_entry_state_reports_symlinks_to_renamed_files_as_stale() {
+        let (tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        fs::write(dotfiles_root.join(".bashrc"), "").unwrap();
+        symlink(dotfiles_root.join(".vimrc"), home.join(".vimrc")).unwrap();
+        symlink(tmp.path().join("missing"), home.join(".bashrc")).unwrap();
+        // the file is renamed in the dotfiles directory, its symlink still points to the old name:
+        fs::rename(dotfiles_root.join(".vimrc"), dotfiles_root.join(".gvimrc")).unwrap();
+        fs::rename(home.join(".vimrc"), home.join(".gvimrc")).unwrap();
+        let config = AppConfig::default();
+
+        let renamed = (PathBuf::from(".gvimrc"), DotfilesEntryState::Mapped);
+        let state = get_dotfiles_entry_state(&global_args, &config, &renamed, &home).unwrap();
+        assert!(
+            matches!(state, LinkState::StaleLink(ref target) if *target == global_args.dotfiles_root.join(".vimrc")),
+            "got {:?}",
+            state
+        );
+        assert!(state.is_any_of(&["stale"]));
+
+        // broken symlinks pointing outside of the dotfiles directory are still conflicts:
+        let broken = (PathBuf::from(".bashrc"), DotfilesEntryState::Mapped);
+        let state = get_dotfiles_entry_state(&global_args, &config, &broken, &home).unwrap();
+        assert!(
+            matches!(state, LinkState::ConflictWrongTarget(_)),
+            "got {:?}",
+            state
+        );
+    }
+
+    #[test]
+    fn get_dotfiles_entry_state_allows_external_targets_if_configured() {
+        let (tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join(".ssh"), "").unwrap();
+        fs::write(tmp.path().join("external"), "").unwrap();
+        symlink(tmp.path().join("external"), home.join(".ssh")).unwrap();
+        let entry = (PathBuf::from(".ssh"), DotfilesEntryState::Mapped);
+
+        let mut config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".ssh"))],
+            ..Default::default()
+        };
+        let state = get_dotfiles_entry_state(&global_args, &config, &entry, &home).unwrap();
+        assert!(
+            matches!(state, LinkState::ConflictWrongTarget(_)),
+            "got {:?}",
+            state
+        );
+
+        config.mappings[0].allow_external_target = true;
+        let state = get_dotfiles_entry_state(&global_args, &config, &entry, &home).unwrap();
+        assert!(
+            matches!(state, LinkState::External(ref target) if *target == tmp.path().join("external")),
+            "got {:?}",
+            state
+        );
+    }
+
+    #[test]
+    fn get_dotfiles_entry_state_reports_missing_pinned_mappings_as_pending() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        let entry = (PathBuf::from(".work.sh"), DotfilesEntryState::Invalid);
+
+        let mut config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".work.sh"))],
+            ..Default::default()
+        };
+        let state = get_dotfiles_entry_state(&global_args, &config, &entry, &home).unwrap();
+        assert!(matches!(state, LinkState::Invalid(_)), "got {:?}", state);
+
+        config.mappings[0].pinned = true;
+        let state = get_dotfiles_entry_state(&global_args, &config, &entry, &home).unwrap();
+        assert!(
+            matches!(state, LinkState::Pending(ref path) if *path == dotfiles_root.join(".work.sh")),
+            "got {:?}",
+            state
+        );
+        assert!(!state.is_any_of(&DEFAULT_FAILING_STATES));
+    }
+
+    #[test]
+    fn nested_mappings_are_listed_and_linked_through_their_parent() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(home.join(".config")).unwrap();
+        fs::create_dir_all(dotfiles_root.join(".config/nvim/lua")).unwrap();
+        fs::write(dotfiles_root.join(".config/nvim/init.vim"), "").unwrap();
+        fs::write(dotfiles_root.join(".config/nvim/lua/plugins.lua"), "").unwrap();
+        symlink(
+            dotfiles_root.join(".config/nvim"),
+            home.join(".config/nvim"),
+        )
+        .unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".config/nvim")),
+                Mapping::new(PathBuf::from(".config/nvim/init.vim")),
+            ],
+            allow_nested: Some(true),
+            ..Default::default()
+        };
+
+        // unmapped entries of the mapped parent are covered by it:
+        let entries = get_dotfiles_entries(&global_args, &config).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from(".config/nvim"), DotfilesEntryState::Mapped),
+                (
+                    PathBuf::from(".config/nvim/init.vim"),
+                    DotfilesEntryState::Mapped
+                ),
+            ]
+        );
+
+        for entry in &entries {
+            let state = get_dotfiles_entry_state(&global_args, &config, entry, &home).unwrap();
+            assert!(matches!(state, LinkState::Linked), "got {:?}", state);
+        }
+    }
+
+    #[test]
+    fn get_dotfiles_entries_follows_symlinked_directories_only_if_configured() {
+        let (_tmp, _, dotfiles_root, global_args) = setup_test_dirs();
+        let common = dotfiles_root.join("nvim-common");
+        fs::create_dir_all(&common).unwrap();
+        fs::create_dir_all(dotfiles_root.join(".config")).unwrap();
+        fs::write(common.join("init.vim"), "").unwrap();
+        fs::write(common.join("extra.vim"), "").unwrap();
+        // a cycle, which must not be followed forever:
+        symlink(&common, common.join("self")).unwrap();
+        symlink(&common, dotfiles_root.join(".config/nvim")).unwrap();
+        let mut config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".config/nvim/init.vim")),
+                Mapping::new(PathBuf::from(".config/nvim/self/init.vim")),
+            ],
+            ..Default::default()
+        };
+
+        let entries = get_dotfiles_entries(&global_args, &config).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    PathBuf::from(".config/nvim/init.vim"),
+                    DotfilesEntryState::Mapped
+                ),
+                (
+                    PathBuf::from(".config/nvim/self/init.vim"),
+                    DotfilesEntryState::Mapped
+                ),
+                (PathBuf::from("nvim-common"), DotfilesEntryState::Unmapped),
+            ]
+        );
+
+        config.follow_symlinks = Some(true);
+        let entries = get_dotfiles_entries(&global_args, &config).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    PathBuf::from(".config/nvim/extra.vim"),
+                    DotfilesEntryState::Unmapped
+                ),
+                (
+                    PathBuf::from(".config/nvim/init.vim"),
+                    DotfilesEntryState::Mapped
+                ),
+                (
+                    PathBuf::from(".config/nvim/self/init.vim"),
+                    DotfilesEntryState::Mapped
+                ),
+                (PathBuf::from("nvim-common"), DotfilesEntryState::Unmapped),
+            ]
+        );
+    }
+
+    #[test]
+    fn get_dotfiles_entries_skips_included_config_files() {
+        let (_tmp, _, dotfiles_root, global_args) = setup_test_dirs();
+        fs::write(dotfiles_root.join("work.toml"), "").unwrap();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        let config = AppConfig {
+            included: vec![IncludedConfig {
+                path: dotfiles_root.join("work.toml"),
+                writable: true,
+                mappings: vec![],
+            }],
+            ..Default::default()
+        };
+
+        let entries = get_dotfiles_entries(&global_args, &config).unwrap();
+        assert_eq!(
+            entries,
+            vec![(PathBuf::from(".vimrc"), DotfilesEntryState::Unmapped)]
+        );
+    }
+
+    #[test]
+    fn get_dotfiles_entries_only_reports_mappings_in_skipped_directories_invalid_if_missing() {
+        let (_tmp, _, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(dotfiles_root.join(".git/hooks")).unwrap();
+        fs::create_dir_all(dotfiles_root.join("hosts/shared")).unwrap();
+        fs::write(dotfiles_root.join(".git/hooks/pre-commit"), "").unwrap();
+        fs::write(dotfiles_root.join("hosts/shared/.gitconfig"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".git/hooks/pre-commit")),
+                Mapping::new(PathBuf::from("hosts/shared/.gitconfig")),
+                Mapping::new(PathBuf::from("hosts/shared/.missing")),
+            ],
+            ..Default::default()
+        };
+
+        // the hosts directory is not traversed, but its mapped files exist:
+        let entries = get_dotfiles_entries(&global_args, &config).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    PathBuf::from(".git/hooks/pre-commit"),
+                    DotfilesEntryState::Mapped
+                ),
+                (
+                    PathBuf::from("hosts/shared/.gitconfig"),
+                    DotfilesEntryState::Mapped
+                ),
+                (
+                    PathBuf::from("hosts/shared/.missing"),
+                    DotfilesEntryState::Invalid
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn get_dotfiles_entries_does_not_treat_siblings_sharing_a_prefix_as_nested() {
+        let (_tmp, _, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(dotfiles_root.join(".config/nvim")).unwrap();
+        fs::create_dir_all(dotfiles_root.join(".config-old")).unwrap();
+        fs::write(dotfiles_root.join(".config/nvim-backup"), "").unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".config/nvim"))],
+            ..Default::default()
+        };
+
+        let mut entries = get_dotfiles_entries(&global_args, &config).unwrap();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from(".config/nvim"), DotfilesEntryState::Mapped),
+                (
+                    PathBuf::from(".config/nvim-backup"),
+                    DotfilesEntryState::Unmapped
+                ),
+                (PathBuf::from(".config-old"), DotfilesEntryState::Unmapped),
+            ]
+        );
+    }
+
+    #[test]
+    fn host_specific_files_override_shared_files() {
+        let (_tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        let host_dir = dotfiles_root.join("hosts/my-host");
+        fs::create_dir_all(&host_dir).unwrap();
+        fs::write(dotfiles_root.join(".vimrc"), "").unwrap();
+        fs::write(host_dir.join(".vimrc"), "").unwrap();
+        fs::write(host_dir.join(".host-only"), "").unwrap();
+        symlink(host_dir.join(".vimrc"), home.join(".vimrc")).unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".host-only")),
+            ],
+            ..Default::default()
+        };
+        let global_args = GlobalArgs {
+            hostname: Some(String::from("my-host")),
+            ..global_args
+        };
+
+        // the hosts directory itself is not listed and host-only files are valid:
+        let entries = get_dotfiles_entries(&global_args, &config).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from(".host-only"), DotfilesEntryState::Mapped),
+                (PathBuf::from(".vimrc"), DotfilesEntryState::Mapped),
+            ]
+        );
+
+        let state = get_dotfiles_entry_state(&global_args, &config, &entries[1], &home).unwrap();
+        assert!(matches!(state, LinkState::Linked), "got {:?}", state);
+
+        // on other hosts, the shared file is expected:
+        let global_args = GlobalArgs {
+            hostname: Some(String::from("other-host")),
+            ..global_args
+        };
+        let state = get_dotfiles_entry_state(&global_args, &config, &entries[1], &home).unwrap();
+        assert!(
+            matches!(state, LinkState::ConflictWrongTarget(_)),
+            "got {:?}",
+            state
+        );
     }
 }
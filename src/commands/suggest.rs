@@ -0,0 +1,182 @@
+use super::CommandResult;
+use crate::{
+    cli::{max_depth_arg, max_depth_from_args, GlobalArgs},
+    config::AppConfig,
+    errors::AppError,
+    files::{get_home_dir, scan_home_dir},
+};
+use clap::{App, ArgMatches, SubCommand};
+use std::path::{Path, PathBuf};
+
+pub const CMD_IDENTIFIER: &str = "suggest";
+const CMD_ABOUT: &str = r#"
+Looks for well-known dotfiles in your home directory which are not part of your dotfiles yet
+and prints the `dotfiles add` commands to add them.
+Additional paths can be listed under `suggestions` in your dotfiles configuration.
+Paths ending with `/*` suggest every entry of the given directory, eg. `.config/*`.
+Only the first levels of your home directory are scanned, see --max-depth.
+"#;
+
+/// paths relative to the home directory which are checked in any case.
+const DEFAULT_SUGGESTIONS: [&str; 13] = [
+    ".bashrc",
+    ".bash_profile",
+    ".profile",
+    ".zshrc",
+    ".zprofile",
+    ".vimrc",
+    ".gitconfig",
+    ".gitignore_global",
+    ".tmux.conf",
+    ".inputrc",
+    ".editorconfig",
+    ".ssh/config",
+    ".config/*",
+];
+
+/// returns the clap definition for the suggest sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER)
+        .about(CMD_ABOUT)
+        .arg(max_depth_arg())
+}
+
+struct SuggestCommandArgs {
+    max_depth: usize,
+}
+impl SuggestCommandArgs {
+    fn from_args(args: &ArgMatches) -> Result<SuggestCommandArgs, AppError> {
+        Ok(SuggestCommandArgs {
+            max_depth: max_depth_from_args(args)?,
+        })
+    }
+}
+
+/// command handler for the `suggest` sub-command
+/// see `dotfiles suggest -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let SuggestCommandArgs { max_depth } = SuggestCommandArgs::from_args(args)?;
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let home_dir = get_home_dir()?;
+
+    let patterns: Vec<String> = DEFAULT_SUGGESTIONS
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .chain(config.suggestions.iter().cloned())
+        .collect();
+
+    let suggestions: Vec<PathBuf> =
+        scan_home_dir(&home_dir, max_depth, &[&global_args.dotfiles_root])
+            .into_iter()
+            .filter(|path| {
+                patterns
+                    .iter()
+                    .any(|pattern| matches_pattern(path, pattern))
+            })
+            .filter(|path| is_untracked(&config, &home_dir, path))
+            // never suggest to add the dotfiles directory itself:
+            .filter(|path| !global_args.dotfiles_root.starts_with(home_dir.join(path)))
+            .collect();
+    if suggestions.is_empty() {
+        println!("Could not find any untracked dotfiles. Have a good time!");
+    } else {
+        println!("Following files are not part of your dotfiles yet:");
+        for suggestion in suggestions {
+            println!(
+                "dotfiles add {}",
+                shell_quote(&home_dir.join(suggestion).display().to_string())
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// returns true if the given path relative to the home directory matches the given pattern.
+/// A pattern is either a relative path or a directory followed by `/*`, matching all its entries.
+fn matches_pattern(path: &Path, pattern: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(dir) => path.parent() == Some(Path::new(dir)),
+        None => path == Path::new(pattern),
+    }
+}
+
+/// returns true if the given path relative to the home directory could be added to the dotfiles:
+/// It is not a symlink, not mapped yet and neither nested in nor containing another mapping.
+pub fn is_untracked(config: &AppConfig, home_dir: &Path, path: &Path) -> bool {
+    let is_symlink = home_dir
+        .join(path)
+        .symlink_metadata()
+        .map(|meta| meta.file_type().is_symlink())
+        .unwrap_or(true);
+
+    !is_symlink
+        && !config
+            .mappings
+            .iter()
+            .any(|mapping| mapping.path.starts_with(path) || path.starts_with(&mapping.path))
+}
+
+/// wraps the given text in single quotes if it contains characters interpreted by a shell.
+pub fn shell_quote(text: &str) -> String {
+    let is_safe = text
+        .chars()
+        .all(|c| c.is_alphanumeric() || "/._-~+".contains(c));
+    if is_safe {
+        text.to_string()
+    } else {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_untracked, matches_pattern, shell_quote};
+    use crate::config::{AppConfig, Mapping};
+    use std::{fs, path::Path, path::PathBuf};
+
+    #[test]
+    fn is_untracked_ignores_mapped_and_nested_paths() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir_all(home.path().join(".config/nvim")).unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".config/nvim/init.vim"))],
+            ..Default::default()
+        };
+
+        assert!(!is_untracked(
+            &config,
+            home.path(),
+            Path::new(".config/nvim")
+        ));
+        assert!(!is_untracked(
+            &config,
+            home.path(),
+            Path::new(".config/nvim/init.vim")
+        ));
+        fs::create_dir(home.path().join(".config/fish")).unwrap();
+        assert!(is_untracked(
+            &config,
+            home.path(),
+            Path::new(".config/fish")
+        ));
+    }
+
+    #[test]
+    fn matches_pattern_supports_directory_entries() {
+        assert!(matches_pattern(Path::new(".vimrc"), ".vimrc"));
+        assert!(matches_pattern(Path::new(".config/nvim"), ".config/*"));
+        assert!(!matches_pattern(
+            Path::new(".config/nvim/init.vim"),
+            ".config/*"
+        ));
+        assert!(!matches_pattern(Path::new(".config"), ".config/*"));
+    }
+
+    #[test]
+    fn shell_quote_quotes_special_characters() {
+        assert_eq!(shell_quote("/home/me/.vimrc"), "/home/me/.vimrc");
+        assert_eq!(shell_quote("/home/me/my file"), "'/home/me/my file'");
+        assert_eq!(shell_quote("/home/me/it's"), r"'/home/me/it'\''s'");
+    }
+}
@@ -0,0 +1,239 @@
+use super::{
+    print_planned_changes,
+    status::{get_dotfiles_entry_state, DotfilesEntryState, LinkState},
+    CommandResult,
+};
+use crate::{
+    cli::GlobalArgs,
+    config::{normalize_mapping, AppConfig},
+    errors::AppError,
+    files::{get_cwd, get_home_dir, normalize_lexically, remove_path},
+    last_status::resolve_entry_number,
+};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use colored::*;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+pub const CMD_IDENTIFIER: &str = "unlink";
+const CMD_ABOUT: &str = r#"
+Removes the symlinks of your mappings from your home directory, eg. to temporarily test a vanilla setup.
+Neither your dotfiles directory nor your config are changed, use `dotfiles link` to restore the symlinks.
+Only symlinks pointing to your dotfiles directory are removed. Regular files, copies of mappings using
+`mode = "copy"` and symlinks pointing elsewhere are never touched.
+"#;
+const ARG_PATH: &str = "path";
+
+/// returns the clap definition for the unlink sub-command
+pub fn get_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(CMD_IDENTIFIER).about(CMD_ABOUT).arg(
+        Arg::with_name(ARG_PATH).help(
+            "the mapped path to unlink, either relative to your dotfiles directory, in your home directory or the number of an entry of the last `status --number`. Defaults to all mappings.",
+        ),
+    )
+}
+
+struct UnlinkCommandArgs {
+    /// the absolute or mapped path to unlink, if only a single mapping should be unlinked.
+    path: Option<PathBuf>,
+}
+impl UnlinkCommandArgs {
+    fn from_args(args: &ArgMatches) -> UnlinkCommandArgs {
+        UnlinkCommandArgs {
+            path: args.value_of(ARG_PATH).map(PathBuf::from),
+        }
+    }
+}
+
+/// command handler for the `unlink` sub-command
+/// see `dotfiles unlink -h` for an overview.
+pub fn run(args: &ArgMatches, global_args: &GlobalArgs) -> CommandResult {
+    let UnlinkCommandArgs { path } = UnlinkCommandArgs::from_args(args);
+    let config = AppConfig::from_config_file(global_args, false)?;
+    let home_dir = get_home_dir()?;
+
+    let mapping_path = match path {
+        Some(path) => Some(resolve_mapping_path(
+            global_args,
+            &config,
+            &home_dir,
+            &path,
+        )?),
+        None => None,
+    };
+    let unlinked = unlink_mappings(global_args, &config, &home_dir, mapping_path.as_deref())?;
+    if !global_args.dry_run {
+        println!("Removed {} symlink(s).", unlinked);
+    }
+
+    Ok(())
+}
+
+/// returns the path of the mapping the given path refers to.
+/// Absolute paths must point into the home or dotfiles directory, relative paths are either mapped paths
+/// or relative to the current directory. A number refers to an entry of the last `status --number`.
+fn resolve_mapping_path(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    path: &Path,
+) -> Result<PathBuf, AppError> {
+    if let Some(value) = path.to_str() {
+        if let Some(mapped) = resolve_entry_number(value, ARG_PATH, global_args, config)? {
+            return Ok(mapped);
+        }
+    }
+    let mapped = normalize_mapping(path.to_owned());
+    if config.get_mapping(&mapped).is_some() {
+        return Ok(mapped);
+    }
+    // symlinks are not resolved, otherwise a linked path would already point into the dotfiles directory:
+    let abs_path = if path.is_absolute() {
+        normalize_lexically(path)
+    } else {
+        normalize_lexically(&get_cwd()?.join(path))
+    };
+    // the dotfiles directory is often located inside the home directory, so it is checked first:
+    abs_path
+        .strip_prefix(&global_args.dotfiles_root)
+        .or_else(|_| abs_path.strip_prefix(home_dir))
+        .ok()
+        .filter(|rel_path| config.get_mapping(rel_path).is_some())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            AppError::CliInvalidArgValue(
+                ARG_PATH.to_string(),
+                format!("{} is not a mapped path", path.display()),
+            )
+        })
+}
+
+/// Removes the symlinks of all mappings or only the one with the given path from the given home directory
+/// and prints the action taken per mapping. Returns the number of removed symlinks.
+/// In a dry run, nothing is removed and the symlinks to remove are printed instead, see `print_planned_changes`.
+pub fn unlink_mappings(
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+    home_dir: &Path,
+    only: Option<&Path>,
+) -> Result<usize, AppError> {
+    let mut unlinked = 0;
+    let mut planned = vec![];
+    for mapping in config
+        .mappings
+        .iter()
+        .filter(|mapping| only.is_none_or(|path| mapping.path == path))
+    {
+        let home_path = home_dir.join(&mapping.path);
+        // mappings linked once are checked as well, they have been linked when they were added:
+        let entry = (mapping.path.clone(), DotfilesEntryState::Mapped);
+        let state =
+            get_dotfiles_entry_state(global_args, config, &entry, home_dir).map_err(|err| {
+                AppError::FsOther(format!("Failed to read your linked dotfiles: {}", err))
+            })?;
+        let is_symlink = home_path
+            .symlink_metadata()
+            .map(|meta| meta.file_type().is_symlink())
+            .unwrap_or(false);
+
+        let reason = match state {
+            // copies, reverse mappings and mappings linked through a mapped parent directory
+            // do not have a symlink of their own in the home directory:
+            LinkState::Linked
+                if is_symlink
+                    && !mapping.is_copy()
+                    && !mapping.is_reverse()
+                    && global_args.dry_run =>
+            {
+                planned.push((
+                    format!("deleting symlink {}", home_path.display()),
+                    json!({ "action": "unlink", "path": mapping.path, "home_path": home_path }),
+                ));
+                continue;
+            }
+            LinkState::Linked if is_symlink && !mapping.is_copy() && !mapping.is_reverse() => {
+                remove_path(&home_path)?;
+                unlinked += 1;
+                println!("{} {}", "UNLINKED".green(), mapping.path.display());
+                continue;
+            }
+            LinkState::Linked if mapping.is_reverse() => {
+                "its symlink is part of your dotfiles directory"
+            }
+            LinkState::Linked if mapping.is_copy() => "is a copy, not a symlink",
+            LinkState::ConflictNoLink(_) | LinkState::Stale(_) => "is not a symlink",
+            LinkState::ConflictWrongTarget(_) | LinkState::External(_) => {
+                "does not point to your dotfiles directory"
+            }
+            _ => continue,
+        };
+        // a dry run only prints the planned changes:
+        if global_args.dry_run {
+            continue;
+        }
+        println!(
+            "{} {} {} {}",
+            "SKIPPED ".yellow(),
+            mapping.path.display(),
+            home_path.display(),
+            reason
+        );
+    }
+
+    if global_args.dry_run {
+        let count = planned.len();
+        print_planned_changes(global_args, planned)?;
+        return Ok(count);
+    }
+    Ok(unlinked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unlink_mappings;
+    use crate::{
+        commands::setup_test_dirs,
+        config::{AppConfig, Mapping},
+        files::symlink,
+    };
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn unlink_mappings_only_removes_symlinks_into_dotfiles() {
+        let (tmp, home, dotfiles_root, global_args) = setup_test_dirs();
+        fs::create_dir_all(dotfiles_root.join(".config/nvim")).unwrap();
+        for file in [".vimrc", ".bashrc", ".zshrc"] {
+            fs::write(dotfiles_root.join(file), "").unwrap();
+        }
+        symlink(dotfiles_root.join(".vimrc"), home.join(".vimrc")).unwrap();
+        symlink(dotfiles_root.join(".config"), home.join(".config")).unwrap();
+        fs::write(home.join(".bashrc"), "real file").unwrap();
+        symlink(tmp.path().join("elsewhere"), home.join(".zshrc")).unwrap();
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".bashrc")),
+                Mapping::new(PathBuf::from(".zshrc")),
+                Mapping::new(PathBuf::from(".config")),
+            ],
+            ..Default::default()
+        };
+
+        let unlinked =
+            unlink_mappings(&global_args, &config, &home, Some(&PathBuf::from(".vimrc"))).unwrap();
+        assert_eq!(unlinked, 1);
+        assert!(home.join(".vimrc").symlink_metadata().is_err());
+        assert!(home.join(".config").symlink_metadata().is_ok());
+
+        let unlinked = unlink_mappings(&global_args, &config, &home, None).unwrap();
+        assert_eq!(unlinked, 1);
+        assert!(home.join(".config").symlink_metadata().is_err());
+        assert_eq!(
+            fs::read_to_string(home.join(".bashrc")).unwrap(),
+            "real file"
+        );
+        assert!(home.join(".zshrc").symlink_metadata().is_ok());
+        assert!(dotfiles_root.join(".config/nvim").is_dir());
+        assert!(dotfiles_root.join(".vimrc").is_file());
+    }
+}
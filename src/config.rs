@@ -1,154 +1,1134 @@
-use crate::{cli::GlobalArgs, files::get_config_file_path, AppError};
-use promptly::prompt_default;
+use crate::{
+    cli::{GlobalArgs, Operation},
+    files::{get_config_file_path, normalize_lexically},
+    trace, AppError,
+};
+use colored::*;
 use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
 use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
     fs,
-    path::{Component, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
-/// Custom serde deserializer for mappings in the config file.
-/// Makes sure that all paths do not contain a leading current directory by removing the leading dot:
-/// `./.config => .config`.
+/// Removes a leading current directory from the given mapping path: `./.config => .config`.
+/// All mappings read from the config file are normalized this way.
 /// This is important for comparing paths with each other, because the default compare implementation
 /// of PathBuf returns `false` for `Path::from("./.config") == Path::from(".config")`.
-fn into_normalized_mapping<'de, D>(deserializer: D) -> Result<Vec<Mapping>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let input: Vec<PathBuf> = Deserialize::deserialize(deserializer)?;
-    let mut output = vec![];
-    for path in input {
-        let normalized_path = if path.starts_with(Component::CurDir) {
-            path.strip_prefix(Component::CurDir).unwrap().to_owned()
-        } else {
-            path
-        };
-        output.push(normalized_path);
+pub fn normalize_mapping(path: PathBuf) -> PathBuf {
+    if path.starts_with(Component::CurDir) {
+        path.strip_prefix(Component::CurDir).unwrap().to_owned()
+    } else {
+        path
     }
+}
+
+/// Describes how a mapping is handled after it has been added.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Manage {
+    /// the mapping is linked by `add` and `link` and checked by `status`.
+    #[default]
+    Always,
+    /// the mapping is linked by `add`, but ignored by `link` and `status` afterwards.
+    /// Useful for files which are set up once and then edited locally.
+    Once,
+}
+
+/// Describes how a mapped path is put into the home directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    /// a symlink pointing to the file in the dotfiles directory is created.
+    #[default]
+    Symlink,
+    /// the file in the dotfiles directory is copied, optionally rendered as template, see `Mapping::template`.
+    /// Only supported for files.
+    Copy,
+}
+
+/// Describes which side of a mapping contains the actual file and which one the symlink.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkDirection {
+    /// the file lives in the dotfiles directory, the symlink is created in the home directory.
+    #[default]
+    Forward,
+    /// the file lives in the home directory, the symlink is created in the dotfiles directory.
+    /// Always symlinked and never resolved to host-specific files.
+    Reverse,
+}
+
+/// Describes how mappings are written to the config file. Both styles are read in any case.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MappingsStyle {
+    /// a single array of plain paths and inline tables: `mappings = [".vimrc", { path = ".bootstrap", manage = "once" }]`
+    #[default]
+    Flat,
+    /// a `[[mappings]]` table per mapping listing each option on its own line, which keeps diffs of long lists readable.
+    Table,
+}
+
+/// Describes how to handle an existing file or directory at the location of a symlink to create.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// leave the existing file untouched
+    Skip,
+    /// move the existing file aside before creating the symlink
+    Backup,
+    /// delete the existing file before creating the symlink
+    Overwrite,
+    /// ask the user what to do
+    #[default]
+    Prompt,
+}
+impl ConflictStrategy {
+    /// the names of all strategies as used in CLI arguments and the config file.
+    pub const NAMES: [&'static str; 4] = ["skip", "backup", "overwrite", "prompt"];
 
-    Ok(output)
+    /// returns the strategy with the given name, see `ConflictStrategy::NAMES`.
+    pub fn from_name(name: &str) -> Option<ConflictStrategy> {
+        match name {
+            "skip" => Some(ConflictStrategy::Skip),
+            "backup" => Some(ConflictStrategy::Backup),
+            "overwrite" => Some(ConflictStrategy::Overwrite),
+            "prompt" => Some(ConflictStrategy::Prompt),
+            _ => None,
+        }
+    }
 }
 
 /// Describes a mapped path listed in the dotfiles configuration under `mappings`.
-/// Variables of this type contain a relative path, such as `.config/some/conf`.
-#[derive(Serialize, Deserialize, Debug)]
+/// The path is relative, such as `.config/some/conf`.
+/// Mappings using the default options are written as plain paths, all others as inline tables:
+/// `mappings = [".vimrc", { path = ".bootstrap", manage = "once" }]`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "MappingEntry", into = "MappingEntry")]
 pub struct Mapping {
-    from: PathBuf,
-    to: PathBuf,
+    pub path: PathBuf,
+    pub manage: Manage,
+    /// if true, a symlink in the home directory pointing outside of the dotfiles directory
+    /// is accepted instead of being reported as conflict.
+    pub allow_external_target: bool,
+    /// whether the path is symlinked or copied into the home directory.
+    pub mode: LinkMode,
+    /// if true, the file is rendered as template when copied, see `template::render`.
+    /// Only supported by mappings using `LinkMode::Copy`.
+    pub template: bool,
+    /// whether the symlink is created in the home or the dotfiles directory.
+    pub direction: LinkDirection,
+    /// if true, the mapping is kept while its path does not exist in the dotfiles directory:
+    /// `status` reports it as pending instead of invalid and it is never removed automatically, eg. by `prune`.
+    pub pinned: bool,
+}
+impl Mapping {
+    /// returns a new mapping for the given relative path using the default options.
+    pub fn new(path: PathBuf) -> Mapping {
+        Mapping {
+            path,
+            manage: Manage::default(),
+            allow_external_target: false,
+            mode: LinkMode::default(),
+            template: false,
+            direction: LinkDirection::default(),
+            pinned: false,
+        }
+    }
+
+    /// returns true if this mapping should be linked by `link` and checked by `status`.
+    pub fn is_managed(&self) -> bool {
+        self.manage == Manage::Always
+    }
+
+    /// returns true if this mapping is copied instead of symlinked into the home directory.
+    /// Reverse mappings are never copied.
+    pub fn is_copy(&self) -> bool {
+        self.mode == LinkMode::Copy && !self.is_reverse()
+    }
+
+    /// returns true if the symlink of this mapping is created in the dotfiles directory, see `LinkDirection::Reverse`.
+    pub fn is_reverse(&self) -> bool {
+        self.direction == LinkDirection::Reverse
+    }
+}
+
+/// The serialized form of a `Mapping`, either a plain path or a table with options.
+/// Configs written before mappings had options only contain plain paths, which are read as mappings
+/// using the default options. Mappings are only written as table if they use non-default options.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum MappingEntry {
+    Path(PathBuf),
+    Table {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "is_default")]
+        manage: Manage,
+        #[serde(default, skip_serializing_if = "is_default")]
+        allow_external_target: bool,
+        #[serde(default, skip_serializing_if = "is_default")]
+        mode: LinkMode,
+        #[serde(default, skip_serializing_if = "is_default")]
+        template: bool,
+        #[serde(default, skip_serializing_if = "is_default")]
+        direction: LinkDirection,
+        #[serde(default, skip_serializing_if = "is_default")]
+        pinned: bool,
+    },
+}
+
+/// used to omit options with default values when serializing mappings.
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+impl From<MappingEntry> for Mapping {
+    fn from(entry: MappingEntry) -> Self {
+        match entry {
+            MappingEntry::Path(path) => Mapping::new(normalize_mapping(path)),
+            MappingEntry::Table {
+                path,
+                manage,
+                allow_external_target,
+                mode,
+                template,
+                direction,
+                pinned,
+            } => Mapping {
+                path: normalize_mapping(path),
+                manage,
+                allow_external_target,
+                mode,
+                template,
+                direction,
+                pinned,
+            },
+        }
+    }
+}
+impl From<Mapping> for MappingEntry {
+    fn from(mapping: Mapping) -> Self {
+        if mapping == Mapping::new(mapping.path.clone()) {
+            MappingEntry::Path(mapping.path)
+        } else {
+            MappingEntry::Table {
+                path: mapping.path,
+                manage: mapping.manage,
+                allow_external_target: mapping.allow_external_target,
+                mode: mapping.mode,
+                template: mapping.template,
+                direction: mapping.direction,
+                pinned: mapping.pinned,
+            }
+        }
+    }
+}
+
+/// Describes an additional config file listed under `include`, whose mappings are merged into the mappings
+/// of the including config. The path is relative to the directory of the including config file.
+/// Includes are written as plain paths or as tables if they are writable:
+/// `include = ["work.toml", { path = "private.toml", writable = true }]`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "IncludeEntry", into = "IncludeEntry")]
+pub struct Include {
+    pub path: PathBuf,
+    /// if true, new mappings are written to this file instead of the main config file.
+    pub writable: bool,
+}
+
+/// The serialized form of an `Include`, either a plain path or a table with options.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum IncludeEntry {
+    Path(PathBuf),
+    Table {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "is_default")]
+        writable: bool,
+    },
+}
+impl From<IncludeEntry> for Include {
+    fn from(entry: IncludeEntry) -> Self {
+        match entry {
+            IncludeEntry::Path(path) => Include {
+                path,
+                writable: false,
+            },
+            IncludeEntry::Table { path, writable } => Include { path, writable },
+        }
+    }
+}
+impl From<Include> for IncludeEntry {
+    fn from(include: Include) -> Self {
+        if include.writable {
+            IncludeEntry::Table {
+                path: include.path,
+                writable: true,
+            }
+        } else {
+            IncludeEntry::Path(include.path)
+        }
+    }
+}
+
+/// The content of a config file listed under `include`. Only its mappings and own includes are read.
+#[derive(Deserialize)]
+struct IncludedConfigFile {
+    #[serde(default)]
+    mappings: Vec<Mapping>,
+    #[serde(default)]
+    include: Vec<Include>,
+}
+
+/// A loaded config file listed under `include`, see `AppConfig::load_includes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludedConfig {
+    /// the path of the included config file.
+    pub path: PathBuf,
+    /// if true, new mappings are written to this file, see `Include::writable`.
+    pub writable: bool,
+    /// the paths of the mappings listed in this file.
+    pub mappings: Vec<PathBuf>,
+}
+
+/// The version of the config file format written by this binary.
+/// Bumping it requires adding a migration from the previous version to `MIGRATIONS`.
+pub const CURRENT_CONFIG_VERSION: i8 = 2;
+
+/// The migrations of the config file format: the migration at index `i` upgrades a config
+/// from version `i + 1` to version `i + 2`. They are applied in order, see `migrate_document`.
+const MIGRATIONS: [fn(&mut toml::Table); 1] = [migrate_v1_to_v2];
+
+/// version 2 did not change the format, it only introduced migrations.
+fn migrate_v1_to_v2(_document: &mut toml::Table) {}
+
+/// upgrades the given parsed config file to `CURRENT_CONFIG_VERSION` and returns the version it was upgraded from.
+/// Returns `None` if the config uses the current version already or a newer one, which is rejected when validating
+/// the config. Configs with an invalid version are migrated from the first version, but keep their invalid version
+/// so it is reported, see `AppConfig::get_warnings`. `INVALID_CONFIG_VERSION` is returned for them.
+fn migrate_document(document: &mut toml::Table) -> Option<i8> {
+    let (from, version) = match document.get("config_version") {
+        None | Some(toml::Value::Integer(0)) => {
+            (default_config_version(), default_config_version())
+        }
+        Some(toml::Value::Integer(version)) => match i8::try_from(*version) {
+            Ok(version) if version > 0 => (version, version),
+            _ => (INVALID_CONFIG_VERSION, default_config_version()),
+        },
+        Some(_) => (INVALID_CONFIG_VERSION, default_config_version()),
+    };
+    if version >= CURRENT_CONFIG_VERSION {
+        return None;
+    }
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migration(document);
+    }
+    if from != INVALID_CONFIG_VERSION {
+        document.insert(
+            "config_version".to_string(),
+            toml::Value::Integer(CURRENT_CONFIG_VERSION.into()),
+        );
+    }
+
+    Some(from)
+}
+
+/// Very early or hand-written configs may not contain a `config_version` at all.
+/// They are treated as the first version.
+fn default_config_version() -> i8 {
+    1
+}
+
+/// The `config_version` of configs containing an invalid value, such as a string or a negative number.
+/// Such configs are migrated from the first version and their version is replaced by `CURRENT_CONFIG_VERSION`
+/// when validating the config, see `AppConfig::validate_version`.
+const INVALID_CONFIG_VERSION: i8 = -1;
+
+/// reads the `config_version` of a config, mapping values which cannot be a version to `INVALID_CONFIG_VERSION`
+/// instead of failing, so hand-edited configs can still be loaded and repaired.
+fn deserialize_config_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i8, D::Error> {
+    Ok(match toml::Value::deserialize(deserializer)? {
+        toml::Value::Integer(version) if version >= 0 => {
+            i8::try_from(version).unwrap_or(INVALID_CONFIG_VERSION)
+        }
+        _ => INVALID_CONFIG_VERSION,
+    })
+}
+
+/// Defaults for values usually provided via CLI, listed under `[settings]` in the dotfiles config:
+/// ```toml
+/// [settings]
+/// default_profile = "work"
+/// color = false
+/// backup_on_conflict = true
+/// ```
+/// CLI arguments and environment variables always take precedence. The config file is located using the
+/// dotfiles directory, so the dotfiles directory itself cannot be set here, see `Settings::dotfiles_root` instead.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigSettings {
+    /// the profile activated if neither `--profile` nor `DOTFILES_PROFILE` is provided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    /// if false, the output is not colored, as if `--no-color` was provided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<bool>,
+    /// if true, conflicting files are backed up if neither `--on-conflict` nor `on_conflict` is provided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_on_conflict: Option<bool>,
+}
+impl ConfigSettings {
+    /// returns true if no setting is set, in which case the `[settings]` table is not written.
+    fn is_empty(&self) -> bool {
+        *self == ConfigSettings::default()
+    }
+
+    /// reads the `[settings]` table of the config file at the given path without loading the config itself.
+    /// Returns the default settings if the file does not exist or cannot be parsed,
+    /// loading the config reports these problems in any case.
+    pub fn from_config_file(path: &Path) -> ConfigSettings {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| toml::from_str::<toml::Table>(&content).ok())
+            .and_then(|mut document| document.remove("settings"))
+            .and_then(|settings| settings.try_into().ok())
+            .unwrap_or_default()
+    }
 }
 
 /// Describes the parsed configuration from the dotfiles configuration file.
-#[derive(Serialize, Deserialize, Debug)]
+/// Unknown keys, both top-level and in mapping tables, are ignored so configs written by newer versions
+/// can still be read. Options added later must therefore always be optional (`#[serde(default)]`).
+/// Keep in mind that ignored keys are lost when an older binary writes the config.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AppConfig {
+    #[serde(
+        default = "default_config_version",
+        deserialize_with = "deserialize_config_version"
+    )]
     pub config_version: i8, // we can increase it at anytime when necessary..
-    #[serde(deserialize_with = "into_normalized_mapping")]
     pub mappings: Vec<Mapping>,
+    /// additional paths or patterns checked by the `suggest` command, see `commands::suggest`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+    /// if true, mappings are written in the order they were added, new mappings are appended at the end.
+    /// This is the default if neither `preserve_order` nor `sort_mappings` are set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preserve_order: Option<bool>,
+    /// if true, mappings are sorted alphabetically whenever the config is written.
+    /// Cannot be combined with `preserve_order`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_mappings: Option<bool>,
+    /// how mappings are written whenever the config is written. Defaults to `MappingsStyle::Flat`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mappings_style: Option<MappingsStyle>,
+    /// how `link` and `add` handle existing files blocking a symlink if `--on-conflict` is not provided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_conflict: Option<ConflictStrategy>,
+    /// if true, a mapping may be nested in a mapped directory, eg. to use different options for a single file.
+    /// The most specific mapping of a path wins. Nested mappings are rejected by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_nested: Option<bool>,
+    /// if true, symlinks to directories inside the dotfiles directory are traversed like directories
+    /// when looking for mappings and unmapped entries. Symlinks are not followed by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow_symlinks: Option<bool>,
+    /// defaults for values usually provided via CLI, see `ConfigSettings`.
+    #[serde(default, skip_serializing_if = "ConfigSettings::is_empty")]
+    pub settings: ConfigSettings,
+    /// additional config files whose mappings are merged into `mappings` when loading this config, see `Include`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<Include>,
+    /// all loaded included config files, including the ones included by other included files.
+    /// Their mappings are part of `mappings` as well, but are written to their own file.
+    #[serde(skip)]
+    pub included: Vec<IncludedConfig>,
+    /// additional mappings per profile, which are merged into `mappings` if the profile is active, see `--profile`.
+    /// Sorted by name, so writing the config keeps their order stable.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Vec<Mapping>>,
+    /// the name of the active profile and the paths of its mappings, which are part of `mappings` as well.
+    #[serde(skip)]
+    pub active_profile: Option<(String, Vec<PathBuf>)>,
+    /// the version this config was upgraded from when loading it, if it used an older version, see `MIGRATIONS`.
+    #[serde(skip)]
+    pub migrated_from: Option<i8>,
+}
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            config_version: CURRENT_CONFIG_VERSION,
+            mappings: vec![],
+            suggestions: vec![],
+            preserve_order: None,
+            sort_mappings: None,
+            mappings_style: None,
+            on_conflict: None,
+            allow_nested: None,
+            follow_symlinks: None,
+            settings: ConfigSettings::default(),
+            include: vec![],
+            included: vec![],
+            profiles: BTreeMap::new(),
+            active_profile: None,
+            migrated_from: None,
+        }
+    }
 }
 
 impl AppConfig {
-    pub fn from_config_file(global_args: &GlobalArgs) -> Result<AppConfig, AppError> {
+    /// Reads and validates the dotfiles configuration file. Fails if the dotfiles root does not exist.
+    /// If the file does not exist yet, the user is asked to create it if `may_create` is true.
+    /// Otherwise or in a dry run, an empty configuration is returned without writing anything,
+    /// which should be used by all commands not changing the configuration.
+    pub fn from_config_file(
+        global_args: &GlobalArgs,
+        may_create: bool,
+    ) -> Result<AppConfig, AppError> {
+        global_args.require_dotfiles_root()?;
         let config_path = get_config_file_path(global_args)?;
 
+        // nothing is written in a dry run, so a missing config is never created:
+        if !config_path.exists() && (!may_create || global_args.dry_run) {
+            trace::event("config_missing", json!({ "path": config_path }));
+            return Ok(AppConfig::default());
+        }
         // if the config does not exist yet: ask the user to create it:
-        if config_path.exists() == false {
-            let should_create = prompt_default(
+        if !config_path.exists() {
+            let should_create = global_args.confirm_operations(
+                &[Operation::Mapping],
                 format!(
                     "Could not find the dotfiles config file at {:?}. Should I create it?",
                     config_path
                 ),
                 true,
-            )
-            .unwrap_or(false);
+            )?;
 
             if should_create {
-                (AppConfig {
-                    config_version: 1,
-                    mappings: vec![],
-                })
-                .to_config_file(global_args)?;
+                AppConfig::default().to_config_file(global_args)?;
             }
         }
 
-        let config_file_content = fs::read_to_string(&config_path)
-            .map_err(|err| AppError::ConfigFileRead(config_path.clone(), err))?;
+        let config = AppConfig::from_path_with_profile(
+            &config_path,
+            global_args.collect_errors,
+            global_args.profile.as_deref(),
+        )?;
+        // upgraded configs are written once by commands changing the config, so the migrations do not have
+        // to run on every load. Read-only commands load older configs silently and never write them:
+        if let Some(version) = config.migrated_from {
+            if may_create && !global_args.dry_run {
+                config.to_config_file(global_args)?;
+                eprintln!(
+                    "Upgraded your dotfiles config at {} from config_version {} to {}.",
+                    config_path.display(),
+                    version,
+                    config.config_version
+                );
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Reads and validates the configuration file at the given path, which has to exist.
+    /// If `collect_errors` is true, all validation errors are returned together instead of only the first one.
+    pub fn from_path(config_path: &Path, collect_errors: bool) -> Result<AppConfig, AppError> {
+        AppConfig::from_path_with_profile(config_path, collect_errors, None)
+    }
 
-        let config: AppConfig = toml::from_str(&config_file_content)
-            .map_err(|err| AppError::ConfigParse(config_path.clone(), err))?;
+    /// Reads and validates the configuration file at the given path, see `from_path`.
+    /// If provided, the mappings of the given profile are merged into the mappings before validating them.
+    pub fn from_path_with_profile(
+        config_path: &Path,
+        collect_errors: bool,
+        profile: Option<&str>,
+    ) -> Result<AppConfig, AppError> {
+        let config_file_content = fs::read_to_string(config_path)
+            .map_err(|err| AppError::ConfigFileRead(config_path.to_owned(), err))?;
 
-        config.validate_absolute_mappings()?;
-        config.validate_nested_mappings()?;
+        let mut document: toml::Table = toml::from_str(&config_file_content)
+            .map_err(|err| AppError::ConfigParse(config_path.to_owned(), err))?;
+        let migrated_from = migrate_document(&mut document);
+        // parsing the content itself keeps the location of errors, which is lost in the parsed document:
+        let config: Result<AppConfig, _> = match migrated_from {
+            Some(_) => toml::Value::Table(document).try_into(),
+            None => toml::from_str(&config_file_content),
+        };
+        let mut config =
+            config.map_err(|err| AppError::ConfigParse(config_path.to_owned(), err))?;
+        // configs with an invalid version are reported and fixed by `repair-config` instead of being rewritten:
+        config.migrated_from = migrated_from.filter(|version| *version != INVALID_CONFIG_VERSION);
+        config.load_includes(config_path)?;
+        if let Some(profile) = profile {
+            config.activate_profile(profile)?;
+        }
+
+        // collected before validating, because an invalid version is replaced by the validation:
+        let warnings = config.get_warnings();
+        if collect_errors {
+            let errors: Vec<AppError> = config
+                .validate_version()
+                .err()
+                .into_iter()
+                .chain(config.get_validation_errors())
+                .collect();
+            if !errors.is_empty() {
+                return Err(AppError::ConfigInvalid(config_path.to_owned(), errors));
+            }
+        } else {
+            config.validate_version()?;
+            config.validate()?;
+        }
+        for warning in warnings {
+            eprintln!("{}: {}", "Warning".yellow().bold(), warning);
+        }
+        trace::event(
+            "config_loaded",
+            json!({ "path": config_path, "config_version": config.config_version, "mappings": config.mappings }),
+        );
 
         Ok(config)
     }
 
-    /// adds a path to the mappings.
-    pub fn add_mapping(&mut self, mapping: PathBuf) -> () {
+    /// reads all config files listed under `include` and the ones they include themselves,
+    /// and merges their mappings into the mappings of this config.
+    /// Fails if a file includes itself or a mapping is listed in more than one file.
+    fn load_includes(&mut self, config_path: &Path) -> Result<(), AppError> {
+        let includes = self.include.clone();
+        let mut including = vec![get_include_key(config_path)];
+        self.load_included_files(config_path, &includes, &mut including)?;
+
+        let writable: Vec<&IncludedConfig> = self
+            .included
+            .iter()
+            .filter(|included| included.writable)
+            .collect();
+        if let [first, second, ..] = writable[..] {
+            return Err(AppError::ConfigConflictingOptions(
+                format!("`writable = true` of {}", first.path.display()),
+                format!("`writable = true` of {}", second.path.display()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// loads the given includes of the config file at `config_path`, see `load_includes`.
+    /// `including` contains the keys of all files including the current one to detect circular includes,
+    /// see `get_include_key`.
+    fn load_included_files(
+        &mut self,
+        config_path: &Path,
+        includes: &[Include],
+        including: &mut Vec<PathBuf>,
+    ) -> Result<(), AppError> {
+        // safe, the path of a config file always has a parent:
+        let config_dir = config_path.parent().unwrap();
+        for include in includes {
+            let path = normalize_lexically(&config_dir.join(&include.path));
+            let key = get_include_key(&path);
+            if including.contains(&key) {
+                return Err(AppError::ConfigCircularInclude(path));
+            }
+            let content = fs::read_to_string(&path)
+                .map_err(|err| AppError::ConfigFileRead(path.clone(), err))?;
+            let file: IncludedConfigFile =
+                toml::from_str(&content).map_err(|err| AppError::ConfigParse(path.clone(), err))?;
+
+            if let Some(duplicate) = file
+                .mappings
+                .iter()
+                .find(|mapping| self.get_mapping(&mapping.path).is_some())
+            {
+                return Err(AppError::ConfigDuplicateMapping(
+                    duplicate.path.clone(),
+                    path,
+                ));
+            }
+            self.included.push(IncludedConfig {
+                path: path.clone(),
+                writable: include.writable,
+                mappings: file.mappings.iter().map(|m| m.path.clone()).collect(),
+            });
+            self.mappings.extend(file.mappings);
+
+            including.push(key);
+            self.load_included_files(&path, &file.include, including)?;
+            including.pop();
+        }
+
+        Ok(())
+    }
+
+    /// merges the mappings of the profile with the given name into the mappings of this config.
+    /// Fails if there is no such profile or one of its mappings is listed in the config already.
+    fn activate_profile(&mut self, profile: &str) -> Result<(), AppError> {
+        let mappings = self.profiles.get(profile).cloned().ok_or_else(|| {
+            AppError::CliInvalidArgValue(
+                "profile".to_string(),
+                format!("your config does not contain a profile named {}", profile),
+            )
+        })?;
+        if let Some(duplicate) = mappings
+            .iter()
+            .find(|mapping| self.get_mapping(&mapping.path).is_some())
+        {
+            return Err(AppError::ConfigDuplicateProfileMapping(
+                duplicate.path.clone(),
+                profile.to_string(),
+            ));
+        }
+        self.active_profile = Some((
+            profile.to_string(),
+            mappings.iter().map(|m| m.path.clone()).collect(),
+        ));
+        self.mappings.extend(mappings);
+
+        Ok(())
+    }
+
+    /// returns true if the mapping with the given path is listed in the active profile.
+    fn is_profile_mapping(&self, path: &Path) -> bool {
+        self.active_profile
+            .iter()
+            .any(|(_, mappings)| mappings.iter().any(|mapping| mapping == path))
+    }
+
+    /// returns true if the mapping with the given path is listed in an included config file.
+    fn is_included(&self, path: &Path) -> bool {
+        self.included
+            .iter()
+            .any(|included| included.mappings.iter().any(|mapping| mapping == path))
+    }
+
+    /// adds a mapping to the mappings. It is written to the writable included config file, if any.
+    pub fn add_mapping(&mut self, mapping: Mapping) {
+        if let Some(included) = self.included.iter_mut().find(|included| included.writable) {
+            included.mappings.push(mapping.path.clone());
+        }
         self.mappings.push(mapping);
     }
 
+    /// removes the mapping with the given relative path and returns it, if any.
+    /// Mappings of read-only included config files cannot be written, see `to_config_file`.
+    pub fn remove_mapping(&mut self, path: &Path) -> Option<Mapping> {
+        for included in self
+            .included
+            .iter_mut()
+            .filter(|included| included.writable)
+        {
+            included.mappings.retain(|mapping| mapping != path);
+        }
+        if let Some((_, mappings)) = &mut self.active_profile {
+            mappings.retain(|mapping| mapping != path);
+        }
+        let index = self
+            .mappings
+            .iter()
+            .position(|mapping| mapping.path == path)?;
+        Some(self.mappings.remove(index))
+    }
+
+    /// returns the conflict strategy to use: the one provided via CLI, the configured one,
+    /// `Backup` if `backup_on_conflict` is set in the settings or the default.
+    pub fn get_conflict_strategy(&self, cli_value: Option<ConflictStrategy>) -> ConflictStrategy {
+        let settings_value = match self.settings.backup_on_conflict {
+            Some(true) => Some(ConflictStrategy::Backup),
+            _ => None,
+        };
+        cli_value
+            .or(self.on_conflict)
+            .or(settings_value)
+            .unwrap_or_default()
+    }
+
+    /// returns the mapping with the given relative path, if any.
+    pub fn get_mapping(&self, path: &Path) -> Option<&Mapping> {
+        self.mappings.iter().find(|mapping| mapping.path == path)
+    }
+
+    /// returns the most specific mapping of the given relative path or one of its ancestors, if any.
+    pub fn get_covering_mapping(&self, path: &Path) -> Option<&Mapping> {
+        self.mappings
+            .iter()
+            .filter(|mapping| path.starts_with(&mapping.path))
+            .max_by_key(|mapping| mapping.path.components().count())
+    }
+
+    /// returns true if symlinked directories are traversed, see `follow_symlinks`.
+    pub fn follows_symlinks(&self) -> bool {
+        self.follow_symlinks == Some(true)
+    }
+
+    /// returns true if mappings may be nested in other mappings, see `allow_nested`.
+    pub fn allows_nested_mappings(&self) -> bool {
+        self.allow_nested == Some(true)
+    }
+
+    /// returns the sorted and deduplicated paths of all mappings,
+    /// as expected by `find_mapped_descendant` and `find_mapped_ancestor`.
+    pub fn get_sorted_mapping_paths(&self) -> Vec<&Path> {
+        let mut paths: Vec<&Path> = self.mappings.iter().map(|m| m.path.as_path()).collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
     /// Writes this configuration to the dotfiles configuration file by either overwriting the current content
     /// or creating the file if it does not yet exist.
+    /// The mappings of a writable included config file are written to this file, see `Include`.
+    /// Fails without writing anything if a mapping of a read-only included config file was removed.
     pub fn to_config_file(&self, global_args: &GlobalArgs) -> Result<(), AppError> {
-        let serialized_config =
-            toml::to_string_pretty(&self).map_err(|err| AppError::ConfigSerialize(err))?;
+        for included in self.included.iter().filter(|included| !included.writable) {
+            if let Some(removed) = included
+                .mappings
+                .iter()
+                .find(|mapping| self.get_mapping(mapping).is_none())
+            {
+                return Err(AppError::ConfigReadOnlyInclude(
+                    removed.clone(),
+                    included.path.clone(),
+                ));
+            }
+        }
+        let serialized_config = self.to_toml()?;
         let config_path = get_config_file_path(global_args)?;
+        // safe, the config file path is always an absolute path to a file, see `GlobalArgs`:
+        let config_dir = config_path.parent().unwrap();
+        // a config file provided via CLI may be located anywhere, the user chose it explicitly:
+        if global_args.config_file.is_none() {
+            validate_config_location(&config_path, &global_args.dotfiles_root)?;
+        }
+        fs::create_dir_all(config_dir).map_err(|err| match global_args.config_file {
+            Some(_) => AppError::ConfigDirUnwritable(config_path.clone(), err),
+            None => AppError::ConfigFileWrite(config_path.clone(), err),
+        })?;
 
-        fs::create_dir_all(config_path.parent().unwrap())
+        trace::event(
+            "config_written",
+            json!({ "path": config_path, "mappings": self.mappings }),
+        );
+        fs::write(&config_path, serialized_config)
             .map_err(|err| AppError::ConfigFileWrite(config_path.clone(), err))?;
 
-        fs::write(&config_path, serialized_config)
-            .map_err(|err| AppError::ConfigFileWrite(config_path.clone(), err))
+        for included in self.included.iter().filter(|included| included.writable) {
+            self.write_included_file(included)?;
+        }
+        Ok(())
     }
 
-    /// makes sure all link in mappings are relative and returns an error if an absolute path was found
-    fn validate_absolute_mappings(&self) -> Result<(), AppError> {
-        for link in &self.mappings {
-            if link.is_absolute() {
-                return Err(AppError::ConfigAbsoluteLink(link.to_owned()));
+    /// serializes this config the way it is written to the config file.
+    /// Mappings of included config files are written to their own file and therefore left out.
+    /// Mappings of the active profile are written to the profile instead.
+    fn to_toml(&self) -> Result<String, AppError> {
+        let mut config = self.clone();
+        config.mappings.retain(|mapping| {
+            !self.is_included(&mapping.path) && !self.is_profile_mapping(&mapping.path)
+        });
+        if let Some((profile, mappings)) = &self.active_profile {
+            let mappings = mappings
+                .iter()
+                .filter_map(|path| self.get_mapping(path).cloned())
+                .collect();
+            config.profiles.insert(profile.clone(), mappings);
+        }
+        if self.sort_mappings == Some(true) {
+            config.mappings.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+        if self.mappings_style != Some(MappingsStyle::Table) {
+            return toml::to_string_pretty(&config).map_err(AppError::ConfigSerialize);
+        }
+
+        let mut document = toml::Table::try_from(&config).map_err(AppError::ConfigSerialize)?;
+        document.insert("mappings".to_string(), to_mapping_tables(&config.mappings)?);
+        if let Some(toml::Value::Table(profiles)) = document.get_mut("profiles") {
+            for (name, mappings) in &config.profiles {
+                profiles.insert(name.clone(), to_mapping_tables(mappings)?);
             }
         }
+        toml::to_string_pretty(&document).map_err(AppError::ConfigSerialize)
+    }
+
+    /// replaces the mappings of the given included config file with its current mappings.
+    /// All other keys of the file are kept.
+    fn write_included_file(&self, included: &IncludedConfig) -> Result<(), AppError> {
+        let content = fs::read_to_string(&included.path)
+            .map_err(|err| AppError::ConfigFileRead(included.path.clone(), err))?;
+        let mut document: toml::Table = toml::from_str(&content)
+            .map_err(|err| AppError::ConfigParse(included.path.clone(), err))?;
+        let mut mappings: Vec<&Mapping> = included
+            .mappings
+            .iter()
+            .filter_map(|path| self.get_mapping(path))
+            .collect();
+        if self.sort_mappings == Some(true) {
+            mappings.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+        let mappings_value = match self.mappings_style {
+            Some(MappingsStyle::Table) => to_mapping_tables(mappings.iter().copied())?,
+            _ => toml::Value::try_from(&mappings).map_err(AppError::ConfigSerialize)?,
+        };
+        document.insert("mappings".to_string(), mappings_value);
+        let serialized = toml::to_string_pretty(&document).map_err(AppError::ConfigSerialize)?;
+
+        trace::event(
+            "config_written",
+            json!({ "path": included.path, "mappings": mappings }),
+        );
+        fs::write(&included.path, serialized)
+            .map_err(|err| AppError::ConfigFileWrite(included.path.clone(), err))
+    }
+
+    /// makes sure this binary understands the version of this config.
+    /// Legacy configs using version `0` are treated as the first version. Configs with an invalid version
+    /// have been migrated from the first version while loading them, see `migrate_document`,
+    /// so they use the current version.
+    fn validate_version(&mut self) -> Result<(), AppError> {
+        if self.config_version == 0 {
+            self.config_version = default_config_version();
+        }
+        if self.config_version == INVALID_CONFIG_VERSION {
+            self.config_version = CURRENT_CONFIG_VERSION;
+        }
+        if self.config_version > CURRENT_CONFIG_VERSION {
+            return Err(AppError::ConfigUnsupportedVersion(self.config_version));
+        }
 
         Ok(())
     }
 
-    /// validates that there are not nested links, ie. a directory to link containing a file to link.
-    /// Otherwise, returns an error
-    fn validate_nested_mappings(&self) -> Result<(), AppError> {
-        if self.mappings.len() == 0 {
-            return Ok(());
+    /// validates the options and mappings of this config and returns the first found error.
+    pub fn validate(&self) -> Result<(), AppError> {
+        match self.get_validation_errors().into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
         }
-        let mut mappings = self.mappings.to_vec();
-        mappings.sort();
-        for i in 0..mappings.len() - 1 {
-            let current = &mappings[i];
-            let next = &mappings[i + 1];
-            if next.starts_with(current) {
-                return Err(AppError::ConfigNestedLinks(next.clone(), current.clone()));
-            }
+    }
+
+    /// validates the options and mappings of this config and returns all found errors.
+    pub fn get_validation_errors(&self) -> Vec<AppError> {
+        let mut errors = vec![];
+        if self.preserve_order == Some(true) && self.sort_mappings == Some(true) {
+            errors.push(AppError::ConfigConflictingOptions(
+                "preserve_order".to_string(),
+                "sort_mappings".to_string(),
+            ));
+        }
+        errors.extend(self.validate_absolute_mappings());
+        errors.extend(self.validate_nested_mappings());
+
+        errors
+    }
+
+    /// returns a message for each option which is set, but has no effect.
+    pub fn get_warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if self.config_version == INVALID_CONFIG_VERSION {
+            warnings.push(format!(
+                "The `config_version` of your config is invalid, using version {} instead. Run `dotfiles repair-config` to fix it.",
+                CURRENT_CONFIG_VERSION
+            ));
+        }
+        warnings.extend(
+            self.mappings
+                .iter()
+                .filter(|mapping| mapping.template && !mapping.is_copy())
+                .map(|mapping| {
+                    format!(
+                        "The mapping {} sets `template = true`, but templates are only rendered for mappings with `mode = \"copy\"`. Symlinks always show the file of your dotfiles directory as it is.",
+                        mapping.path.display()
+                    )
+                }),
+        );
+        warnings.extend(
+            self.mappings
+                .iter()
+                .filter(|mapping| mapping.is_reverse() && mapping.mode == LinkMode::Copy)
+                .map(|mapping| {
+                    format!(
+                        "The mapping {} sets `direction = \"reverse\"` and `mode = \"copy\"`. Reverse mappings are always symlinked, its mode is ignored.",
+                        mapping.path.display()
+                    )
+                }),
+        );
+
+        warnings
+    }
+
+    /// makes sure all link in mappings are relative and returns an error for each absolute path
+    fn validate_absolute_mappings(&self) -> Vec<AppError> {
+        self.mappings
+            .iter()
+            .filter(|link| link.path.is_absolute())
+            .map(|link| AppError::ConfigAbsoluteLink(link.path.to_owned()))
+            .collect()
+    }
+
+    /// validates that there are not nested links, ie. a directory to link containing a file to link,
+    /// unless nested mappings are allowed. Otherwise, returns an error for each nested mapping
+    /// with its closest parent, sorted by the nested path.
+    fn validate_nested_mappings(&self) -> Vec<AppError> {
+        if self.allows_nested_mappings() {
+            return vec![];
         }
+        let mappings = self.get_sorted_mapping_paths();
+
+        mappings
+            .iter()
+            .filter_map(|nested| {
+                find_mapped_ancestor(&mappings, nested).map(|parent| {
+                    AppError::ConfigNestedLinks(nested.to_path_buf(), parent.to_path_buf())
+                })
+            })
+            .collect()
+    }
+}
+
+/// returns a path of the given sorted and deduplicated mapped paths which is nested in the given path, if any.
+/// Paths are sorted by their components, so all descendants of a path directly follow it,
+/// which allows to find them with a binary search instead of comparing the path with every mapping.
+pub fn find_mapped_descendant<'a>(sorted_paths: &[&'a Path], path: &Path) -> Option<&'a Path> {
+    let index = match sorted_paths.binary_search(&path) {
+        Ok(index) => index + 1,
+        Err(index) => index,
+    };
+    sorted_paths
+        .get(index)
+        .filter(|next| next.starts_with(path))
+        .copied()
+}
+
+/// returns the closest path of the given sorted and deduplicated mapped paths which contains the given path, if any.
+/// Each ancestor of the path is looked up with a binary search, see `find_mapped_descendant`.
+pub fn find_mapped_ancestor<'a>(sorted_paths: &[&'a Path], path: &Path) -> Option<&'a Path> {
+    path.ancestors()
+        .skip(1)
+        .filter(|ancestor| *ancestor != Path::new(""))
+        .find_map(|ancestor| {
+            sorted_paths
+                .binary_search(&ancestor)
+                .ok()
+                .map(|index| sorted_paths[index])
+        })
+}
+
+/// returns the given mappings as array of tables, which is written as a `[[mappings]]` table per mapping,
+/// see `MappingsStyle::Table`. Mappings using the default options are written as table with their path only.
+fn to_mapping_tables<'a, I: IntoIterator<Item = &'a Mapping>>(
+    mappings: I,
+) -> Result<toml::Value, AppError> {
+    let mut tables = vec![];
+    for mapping in mappings {
+        let table = match toml::Value::try_from(mapping).map_err(AppError::ConfigSerialize)? {
+            toml::Value::String(path) => {
+                let mut table = toml::Table::new();
+                table.insert("path".to_string(), toml::Value::String(path));
+                toml::Value::Table(table)
+            }
+            table => table,
+        };
+        tables.push(table);
+    }
 
+    Ok(toml::Value::Array(tables))
+}
+
+/// returns the path identifying the given config file when checking for circular includes.
+/// Symlinks are resolved, so a file included via different paths is detected as well.
+fn get_include_key(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .unwrap_or_else(|_| normalize_lexically(path))
+}
+
+/// makes sure the directory of the given config file resolves to a location inside the dotfiles root.
+/// Because the config directory may not exist yet, its closest existing ancestor is resolved instead.
+/// This check happens before any directory is created, so nothing is written through an unexpected symlink.
+fn validate_config_location(config_path: &Path, dotfiles_root: &Path) -> Result<(), AppError> {
+    let canonicalize = |path: &Path| {
+        path.canonicalize()
+            .map_err(|err| AppError::ConfigFileWrite(config_path.to_owned(), err))
+    };
+    let dotfiles_root = canonicalize(dotfiles_root)?;
+    let existing_ancestor = config_path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or_else(|| Path::new("/"));
+    let resolved = canonicalize(existing_ancestor)?;
+
+    if resolved.starts_with(&dotfiles_root) {
         Ok(())
+    } else {
+        Err(AppError::ConfigOutsideDotfilesRoot(
+            config_path.to_owned(),
+            resolved,
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::AppConfig;
-    use crate::errors::AppError;
-    use std::path::PathBuf;
+    use super::{
+        find_mapped_ancestor, find_mapped_descendant, validate_config_location, AppConfig,
+        ConfigSettings, ConflictStrategy, LinkMode, Manage, Mapping, MappingsStyle,
+        CURRENT_CONFIG_VERSION, MIGRATIONS,
+    };
+    use crate::{cli::GlobalArgs, errors::AppError, files::symlink};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    #[test]
+    fn from_config_file_requires_existing_dotfiles_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().join("missing"),
+            ..Default::default()
+        };
+
+        let result = AppConfig::from_config_file(&global_args, false);
+        assert!(matches!(result, Err(AppError::CliInvalidArgValue(_, _))));
+    }
+
+    #[test]
+    fn validate_config_location_rejects_symlinked_config_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dotfiles_root = tmp.path().join("dotfiles");
+        let outside = tmp.path().join("outside");
+        fs::create_dir_all(dotfiles_root.join(".config")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        let config_path = dotfiles_root.join(".config/dotfiles/config.toml");
+
+        // the config directory does not exist yet, but its parent is inside the dotfiles root:
+        assert!(validate_config_location(&config_path, &dotfiles_root).is_ok());
+
+        symlink(&outside, dotfiles_root.join(".config/dotfiles")).unwrap();
+        let result = validate_config_location(&config_path, &dotfiles_root);
+        match result {
+            Err(AppError::ConfigOutsideDotfilesRoot(path, resolved)) => {
+                assert_eq!(path, config_path);
+                assert_eq!(resolved, outside.canonicalize().unwrap());
+            }
+            other => panic!("expected ConfigOutsideDotfilesRoot, got {:?}", other),
+        }
+    }
 
     #[test]
     fn validate_nested_paths_detects_nested_paths() {
         let config = AppConfig {
             config_version: 1,
             mappings: vec![
-                PathBuf::from(".config/some-other-dir"),
-                PathBuf::from(".config/some-dir/some-file"),
-                PathBuf::from(".config/some-dir"),
+                Mapping::new(PathBuf::from(".config/some-other-dir")),
+                Mapping::new(PathBuf::from(".config/some-dir/some-file")),
+                Mapping::new(PathBuf::from(".config/some-dir")),
             ],
+            ..Default::default()
         };
 
-        let result = config.validate_nested_mappings();
+        let result = config.validate();
 
         assert!(result.is_err(), "did not detect nested paths");
         if let Err(AppError::ConfigNestedLinks(nested, parent)) = result {
@@ -156,4 +1136,647 @@ mod tests {
             assert_eq!(parent, PathBuf::from(".config/some-dir"));
         };
     }
+
+    #[test]
+    fn get_validation_errors_returns_all_errors() {
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from("/absolute")),
+                Mapping::new(PathBuf::from(".config")),
+                Mapping::new(PathBuf::from(".config/a")),
+                Mapping::new(PathBuf::from(".config/b/c")),
+            ],
+            preserve_order: Some(true),
+            sort_mappings: Some(true),
+            ..Default::default()
+        };
+
+        let errors = config.get_validation_errors();
+
+        assert_eq!(errors.len(), 4, "got {:?}", errors);
+        assert!(matches!(
+            errors[0],
+            AppError::ConfigConflictingOptions(_, _)
+        ));
+        assert!(matches!(errors[1], AppError::ConfigAbsoluteLink(_)));
+        assert!(
+            matches!(&errors[3], AppError::ConfigNestedLinks(nested, parent) if nested == Path::new(".config/b/c") && parent == Path::new(".config"))
+        );
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn from_path_collects_errors_if_requested() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        fs::write(
+            &path,
+            "config_version = 42\nmappings = [\"/absolute\", \".a\", \".a/b\"]",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            AppConfig::from_path(&path, false),
+            Err(AppError::ConfigUnsupportedVersion(42))
+        ));
+        assert!(matches!(
+            AppConfig::from_path(&path, true),
+            Err(AppError::ConfigInvalid(_, errors)) if errors.len() == 3
+        ));
+    }
+
+    #[test]
+    fn find_mapped_paths_scales_to_large_configs() {
+        // 20'000 mappings, each of them checked against all others would take 400 million comparisons:
+        let paths: Vec<PathBuf> = (0..200)
+            .flat_map(|dir| (0..100).map(move |file| PathBuf::from(format!(".d{}/f{}", dir, file))))
+            .collect();
+        let config = AppConfig {
+            mappings: paths.iter().cloned().map(Mapping::new).collect(),
+            ..Default::default()
+        };
+        let sorted = config.get_sorted_mapping_paths();
+
+        for path in &paths {
+            assert_eq!(find_mapped_ancestor(&sorted, path), None);
+            assert_eq!(find_mapped_descendant(&sorted, path), None);
+            assert_eq!(
+                find_mapped_ancestor(&sorted, &path.join("nested")),
+                Some(path.as_path())
+            );
+        }
+        assert_eq!(
+            find_mapped_descendant(&sorted, Path::new(".d42")),
+            Some(Path::new(".d42/f0"))
+        );
+        // siblings sharing a name prefix are not nested, even though they are sorted next to each other:
+        assert_eq!(
+            find_mapped_descendant(&sorted, Path::new(".d4")),
+            Some(Path::new(".d4/f0"))
+        );
+        assert_eq!(find_mapped_descendant(&sorted, Path::new(".d")), None);
+        assert_eq!(
+            find_mapped_ancestor(&sorted, Path::new(".d42-old/f0")),
+            None
+        );
+        assert!(config.get_validation_errors().is_empty());
+    }
+
+    #[test]
+    fn nested_mappings_are_valid_if_allowed() {
+        let config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".config/some-dir/some-file")),
+                Mapping::new(PathBuf::from(".config/some-dir")),
+            ],
+            allow_nested: Some(true),
+            ..Default::default()
+        };
+
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config
+                .get_covering_mapping(&PathBuf::from(".config/some-dir/some-file"))
+                .map(|mapping| &mapping.path),
+            Some(&PathBuf::from(".config/some-dir/some-file"))
+        );
+        assert_eq!(
+            config
+                .get_covering_mapping(&PathBuf::from(".config/some-dir/other-file"))
+                .map(|mapping| &mapping.path),
+            Some(&PathBuf::from(".config/some-dir"))
+        );
+    }
+
+    #[test]
+    fn mappings_support_plain_paths_and_tables() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            config_version = 1
+            mappings = [
+                "./.vimrc",
+                { path = ".bootstrap", manage = "once" },
+                { path = ".ssh", allow_external_target = true },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.mappings,
+            vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping {
+                    manage: Manage::Once,
+                    ..Mapping::new(PathBuf::from(".bootstrap"))
+                },
+                Mapping {
+                    allow_external_target: true,
+                    ..Mapping::new(PathBuf::from(".ssh"))
+                }
+            ]
+        );
+
+        let serialized = toml::to_string(&config).unwrap();
+        assert!(
+            serialized.contains(r#"[".vimrc", { path = ".bootstrap", manage = "once" }, { path = ".ssh", allow_external_target = true }]"#),
+            "unexpected serialization: {}",
+            serialized
+        );
+    }
+
+    #[test]
+    fn mappings_round_trip_in_both_styles() {
+        let mut config: AppConfig = toml::from_str(
+            r#"
+            mappings = [".vimrc", { path = ".bootstrap", manage = "once" }]
+            [profiles]
+            work = [".gitconfig"]
+            "#,
+        )
+        .unwrap();
+
+        let flat = config.to_toml().unwrap();
+        assert!(
+            flat.contains("mappings = ["),
+            "unexpected serialization: {}",
+            flat
+        );
+        config.mappings_style = Some(MappingsStyle::Table);
+        let table = config.to_toml().unwrap();
+        assert!(
+            table.contains("[[mappings]]\npath = \".vimrc\"\n\n[[mappings]]\npath = \".bootstrap\"\nmanage = \"once\"\n"),
+            "unexpected serialization: {}",
+            table
+        );
+        assert!(
+            table.contains("[[profiles.work]]\npath = \".gitconfig\""),
+            "unexpected serialization: {}",
+            table
+        );
+
+        for serialized in [flat, table] {
+            let parsed: AppConfig = toml::from_str(&serialized).unwrap();
+            assert_eq!(parsed.mappings, config.mappings);
+            assert_eq!(parsed.profiles, config.profiles);
+        }
+        // a config without mappings stays valid:
+        config.mappings.clear();
+        let parsed: AppConfig = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        assert!(parsed.mappings.is_empty());
+    }
+
+    #[test]
+    fn legacy_configs_with_mixed_mappings_are_rewritten_in_current_form() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("config.toml");
+        fs::write(
+            &config_path,
+            r#"mappings = [".vimrc", ".config/nvim", { path = ".bootstrap", manage = "once" }]"#,
+        )
+        .unwrap();
+        let config = AppConfig::from_path(&config_path, false).unwrap();
+        assert_eq!(config.config_version, CURRENT_CONFIG_VERSION);
+
+        let rewritten: AppConfig = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(rewritten.config_version, CURRENT_CONFIG_VERSION);
+        assert_eq!(rewritten.mappings, config.mappings);
+        assert_eq!(
+            rewritten.mappings[..2],
+            [
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".config/nvim"))
+            ]
+        );
+        assert_eq!(rewritten.mappings[2].manage, Manage::Once);
+    }
+
+    #[test]
+    fn templates_of_symlink_mappings_are_warned_about() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            mappings = [
+                { path = ".fontrc", mode = "copy", template = true },
+                { path = ".vimrc", template = true },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.mappings[0].mode, LinkMode::Copy);
+        let warnings = config.get_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains(".vimrc"));
+    }
+
+    #[test]
+    fn config_version_defaults_to_first_version() {
+        let mut config: AppConfig = toml::from_str(r#"mappings = [".vimrc"]"#).unwrap();
+        assert!(config.validate_version().is_ok());
+        assert_eq!(config.config_version, 1);
+
+        let mut config: AppConfig = toml::from_str("config_version = 0\nmappings = []").unwrap();
+        assert!(config.validate_version().is_ok());
+        assert_eq!(config.config_version, 1);
+    }
+
+    #[test]
+    fn settings_round_trip_and_are_skipped_if_empty() {
+        let mut config: AppConfig = toml::from_str(
+            r#"
+            mappings = [".vimrc"]
+            [settings]
+            default_profile = "work"
+            color = false
+            backup_on_conflict = true
+            "#,
+        )
+        .unwrap();
+        let settings = ConfigSettings {
+            default_profile: Some("work".to_string()),
+            color: Some(false),
+            backup_on_conflict: Some(true),
+        };
+        assert_eq!(config.settings, settings);
+
+        let serialized = config.to_toml().unwrap();
+        assert!(serialized.contains("[settings]"), "got {}", serialized);
+        let parsed: AppConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(parsed.settings, settings);
+        assert_eq!(parsed.mappings, config.mappings);
+        assert_eq!(parsed.get_conflict_strategy(None), ConflictStrategy::Backup);
+
+        config.settings = ConfigSettings::default();
+        assert!(!config.to_toml().unwrap().contains("settings"));
+    }
+
+    #[test]
+    fn config_settings_are_read_without_loading_the_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        // invalid mappings are reported when loading the config, not when reading its settings:
+        fs::write(
+            &path,
+            "mappings = [\".a\", \".a/b\"]\n[settings]\ndefault_profile = \"work\"",
+        )
+        .unwrap();
+
+        assert_eq!(
+            ConfigSettings::from_config_file(&path).default_profile,
+            Some("work".to_string())
+        );
+        assert_eq!(
+            ConfigSettings::from_config_file(&tmp.path().join("missing.toml")),
+            ConfigSettings::default()
+        );
+    }
+
+    #[test]
+    fn conflict_strategy_prefers_cli_over_config() {
+        let config: AppConfig = toml::from_str("on_conflict = \"backup\"\nmappings = []").unwrap();
+        assert_eq!(config.get_conflict_strategy(None), ConflictStrategy::Backup);
+        assert_eq!(
+            config.get_conflict_strategy(Some(ConflictStrategy::Skip)),
+            ConflictStrategy::Skip
+        );
+        assert_eq!(
+            AppConfig::default().get_conflict_strategy(None),
+            ConflictStrategy::Prompt
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            config_version = 1
+            some_future_option = true
+            mappings = [".vimrc", { path = ".bootstrap", manage = "once", future_flag = 1 }]
+
+            [future_table]
+            key = "value"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.mappings,
+            vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping {
+                    manage: Manage::Once,
+                    ..Mapping::new(PathBuf::from(".bootstrap"))
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_config_versions_are_replaced_by_current_version() {
+        for version in ["\"one\"", "-3", "1.5", "1000"] {
+            let mut config: AppConfig =
+                toml::from_str(&format!("config_version = {}\nmappings = []", version)).unwrap();
+            assert_eq!(config.get_warnings().len(), 1, "version {}", version);
+            assert!(config.validate_version().is_ok());
+            assert_eq!(config.config_version, CURRENT_CONFIG_VERSION);
+        }
+    }
+
+    #[test]
+    fn configs_with_invalid_versions_are_migrated_to_the_current_version() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("config.toml");
+        fs::write(
+            &config_path,
+            "config_version = \"one\"\nmappings = [\".vimrc\"]\n",
+        )
+        .unwrap();
+
+        let config = AppConfig::from_path(&config_path, false).unwrap();
+        assert_eq!(config.config_version, CURRENT_CONFIG_VERSION);
+        assert_eq!(config.migrated_from, None);
+
+        // the repaired config is neither invalid nor migrated again:
+        fs::write(&config_path, config.to_toml().unwrap()).unwrap();
+        let repaired = AppConfig::from_path(&config_path, false).unwrap();
+        assert_eq!(repaired.config_version, CURRENT_CONFIG_VERSION);
+        assert_eq!(repaired.migrated_from, None);
+        assert!(repaired.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn validate_version_rejects_unsupported_versions() {
+        let mut config: AppConfig = toml::from_str("config_version = 99\nmappings = []").unwrap();
+        assert!(matches!(
+            config.validate_version(),
+            Err(AppError::ConfigUnsupportedVersion(99))
+        ));
+    }
+
+    #[test]
+    fn to_toml_sorts_mappings_only_if_requested() {
+        let mut config = AppConfig {
+            mappings: vec![
+                Mapping::new(PathBuf::from(".vimrc")),
+                Mapping::new(PathBuf::from(".bashrc")),
+            ],
+            ..Default::default()
+        };
+        assert!(config.to_toml().unwrap().contains(
+            r#"".vimrc",
+    ".bashrc""#
+        ));
+
+        config.sort_mappings = Some(true);
+        assert!(config.to_toml().unwrap().contains(
+            r#"".bashrc",
+    ".vimrc""#
+        ));
+        assert_eq!(config.mappings[0].path, PathBuf::from(".vimrc"));
+    }
+
+    #[test]
+    fn validate_rejects_preserve_order_combined_with_sort_mappings() {
+        let config = AppConfig {
+            preserve_order: Some(true),
+            sort_mappings: Some(true),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(AppError::ConfigConflictingOptions(_, _))
+        ));
+    }
+
+    #[test]
+    fn from_path_merges_mappings_of_included_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("config.toml");
+        fs::create_dir_all(tmp.path().join("hosts")).unwrap();
+        fs::write(
+            &config_path,
+            "mappings = [\".vimrc\"]\ninclude = [\"hosts/work.toml\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            tmp.path().join("hosts/work.toml"),
+            "mappings = [\".gitconfig\", { path = \".ssh\", allow_external_target = true }]\n",
+        )
+        .unwrap();
+
+        let config = AppConfig::from_path(&config_path, false).unwrap();
+        let paths: Vec<&Path> = config.mappings.iter().map(|m| m.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new(".vimrc"),
+                Path::new(".gitconfig"),
+                Path::new(".ssh")
+            ]
+        );
+        assert!(
+            config
+                .get_mapping(Path::new(".ssh"))
+                .unwrap()
+                .allow_external_target
+        );
+        // included mappings are not written to the main config file:
+        assert!(!config.to_toml().unwrap().contains(".gitconfig"));
+    }
+
+    #[test]
+    fn from_path_rejects_circular_includes_and_duplicate_mappings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("config.toml");
+        fs::write(&config_path, "mappings = []\ninclude = [\"a.toml\"]\n").unwrap();
+        fs::write(
+            tmp.path().join("a.toml"),
+            "mappings = [\".vimrc\"]\ninclude = [\"./config.toml\"]\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            AppConfig::from_path(&config_path, false),
+            Err(AppError::ConfigCircularInclude(path)) if path == config_path
+        ));
+
+        fs::write(
+            &config_path,
+            "mappings = [\".vimrc\"]\ninclude = [\"b.toml\"]\n",
+        )
+        .unwrap();
+        fs::write(tmp.path().join("b.toml"), "mappings = [\".vimrc\"]\n").unwrap();
+        assert!(matches!(
+            AppConfig::from_path(&config_path, false),
+            Err(AppError::ConfigDuplicateMapping(mapping, _)) if mapping == Path::new(".vimrc")
+        ));
+    }
+
+    #[test]
+    fn to_config_file_writes_new_mappings_to_the_writable_include() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_dir = tmp.path().join(".config/dotfiles");
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().to_owned(),
+            config_file: Some(config_dir.join("config.toml")),
+            ..Default::default()
+        };
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "config_version = 1\nmappings = [\".vimrc\"]\ninclude = [\"shared.toml\", { path = \"local.toml\", writable = true }]\n",
+        )
+        .unwrap();
+        fs::write(config_dir.join("shared.toml"), "mappings = [\".bashrc\"]\n").unwrap();
+        fs::write(config_dir.join("local.toml"), "mappings = [\".zshrc\"]\n").unwrap();
+
+        let mut config = AppConfig::from_config_file(&global_args, false).unwrap();
+        config.add_mapping(Mapping::new(PathBuf::from(".tmux.conf")));
+        config.remove_mapping(Path::new(".zshrc"));
+        config.to_config_file(&global_args).unwrap();
+
+        let read = |file: &str| fs::read_to_string(config_dir.join(file)).unwrap();
+        assert!(!read("config.toml").contains(".tmux.conf"));
+        assert!(read("config.toml").contains(".vimrc"));
+        assert!(read("local.toml").contains(".tmux.conf"));
+        assert!(!read("local.toml").contains(".zshrc"));
+        assert_eq!(read("shared.toml"), "mappings = [\".bashrc\"]\n");
+
+        let mut config = AppConfig::from_config_file(&global_args, false).unwrap();
+        assert_eq!(config.mappings.len(), 3);
+        config.remove_mapping(Path::new(".bashrc"));
+        assert!(matches!(
+            config.to_config_file(&global_args),
+            Err(AppError::ConfigReadOnlyInclude(_, _))
+        ));
+    }
+
+    #[test]
+    fn to_config_file_writes_to_the_provided_config_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_file = tmp.path().join("dotfiles.toml");
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().to_owned(),
+            config_file: Some(config_file.clone()),
+            ..Default::default()
+        };
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".vimrc"))],
+            ..Default::default()
+        };
+
+        config.to_config_file(&global_args).unwrap();
+        assert!(!tmp.path().join(".config").exists());
+        let read = AppConfig::from_config_file(&global_args, false).unwrap();
+        assert_eq!(read.mappings, config.mappings);
+
+        // the parent of the config file is a file, so its directory cannot be created:
+        let global_args = GlobalArgs {
+            config_file: Some(config_file.join("config.toml")),
+            ..global_args
+        };
+        assert!(matches!(
+            config.to_config_file(&global_args),
+            Err(AppError::ConfigDirUnwritable(_, _))
+        ));
+    }
+
+    #[test]
+    fn from_path_with_profile_merges_and_validates_profile_mappings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("config.toml");
+        fs::write(
+            &config_path,
+            "mappings = [\".vimrc\", \".config/nvim\"]\n[profiles]\nlaptop = [\".config/alacritty\"]\nserver = [\".config/nvim/init.vim\"]\n",
+        )
+        .unwrap();
+
+        let config = AppConfig::from_path(&config_path, false).unwrap();
+        assert_eq!(config.mappings.len(), 2);
+
+        let config =
+            AppConfig::from_path_with_profile(&config_path, false, Some("laptop")).unwrap();
+        assert_eq!(config.mappings.len(), 3);
+        assert!(config.get_mapping(Path::new(".config/alacritty")).is_some());
+
+        assert!(matches!(
+            AppConfig::from_path_with_profile(&config_path, false, Some("server")),
+            Err(AppError::ConfigNestedLinks(_, _))
+        ));
+        assert!(matches!(
+            AppConfig::from_path_with_profile(&config_path, false, Some("desktop")),
+            Err(AppError::CliInvalidArgValue(_, _))
+        ));
+    }
+
+    #[test]
+    fn to_config_file_writes_profile_mappings_to_their_profile() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_dir = tmp.path().join(".config/dotfiles");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(
+            config_dir.join("config.toml"),
+            "config_version = 1\nmappings = [\".vimrc\"]\n[profiles]\nlaptop = [\".config/alacritty\", \".xinitrc\"]\n",
+        )
+        .unwrap();
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().to_owned(),
+            config_file: Some(config_dir.join("config.toml")),
+            profile: Some("laptop".to_string()),
+            ..Default::default()
+        };
+
+        let mut config = AppConfig::from_config_file(&global_args, false).unwrap();
+        config.remove_mapping(Path::new(".xinitrc"));
+        config.add_mapping(Mapping::new(PathBuf::from(".bashrc")));
+        config.to_config_file(&global_args).unwrap();
+
+        let config = AppConfig::from_path(&config_dir.join("config.toml"), false).unwrap();
+        let paths = |mappings: &[Mapping]| -> Vec<PathBuf> {
+            mappings.iter().map(|m| m.path.clone()).collect()
+        };
+        assert_eq!(
+            paths(&config.mappings),
+            vec![PathBuf::from(".vimrc"), PathBuf::from(".bashrc")]
+        );
+        assert_eq!(
+            paths(&config.profiles["laptop"]),
+            vec![PathBuf::from(".config/alacritty")]
+        );
+    }
+
+    #[test]
+    fn migrations_upgrade_every_older_version() {
+        assert_eq!(MIGRATIONS.len(), (CURRENT_CONFIG_VERSION - 1) as usize);
+    }
+
+    #[test]
+    fn from_config_file_upgrades_and_rewrites_older_configs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config_path = tmp.path().join("config.toml");
+        fs::write(
+            &config_path,
+            "config_version = 1\nmappings = [\".vimrc\"]\non_conflict = \"backup\"\n",
+        )
+        .unwrap();
+        let global_args = GlobalArgs {
+            dotfiles_root: tmp.path().to_owned(),
+            config_file: Some(config_path.clone()),
+            ..Default::default()
+        };
+
+        // read-only commands upgrade the config in memory only:
+        let config = AppConfig::from_config_file(&global_args, false).unwrap();
+        assert_eq!(config.migrated_from, Some(1));
+        assert_eq!(config.config_version, CURRENT_CONFIG_VERSION);
+        let config = AppConfig::from_path(&config_path, false).unwrap();
+        assert_eq!(config.migrated_from, Some(1));
+
+        let config = AppConfig::from_config_file(&global_args, true).unwrap();
+        assert_eq!(config.migrated_from, Some(1));
+        let config = AppConfig::from_path(&config_path, false).unwrap();
+        assert_eq!(config.migrated_from, None);
+        assert_eq!(config.config_version, CURRENT_CONFIG_VERSION);
+        assert_eq!(config.mappings, vec![Mapping::new(PathBuf::from(".vimrc"))]);
+        assert_eq!(config.on_conflict, Some(ConflictStrategy::Backup));
+    }
 }
@@ -1,4 +1,4 @@
-use crate::commands;
+use crate::{commands, config::CURRENT_CONFIG_VERSION};
 use std::{
     fmt::{Debug, Display},
     path::{PathBuf, StripPrefixError},
@@ -16,35 +16,81 @@ pub enum AppError {
     /// an invalid CLI argument value was provided.
     /// Consists of the name of the argument and the reason why the value is invalid.
     CliInvalidArgValue(String, String),
+    /// The user has to answer a question, but stdin is not a terminal and `--yes` was not given.
+    /// Consists of the question.
+    CliNotInteractive(String),
     /// Failed to read the config file
     /// Consists of the requested path and the underlying IO error.
     ConfigFileRead(PathBuf, std::io::Error),
     /// Failed to write the config file
     /// Consists of the requested path and the underlying IO error.
     ConfigFileWrite(PathBuf, std::io::Error),
+    /// The directory of the config file resolves to a location outside of the dotfiles root,
+    /// eg. because one of its ancestors is a symlink.
+    /// Consists of the config file path and the resolved location of its directory.
+    ConfigOutsideDotfilesRoot(PathBuf, PathBuf),
+    /// The directory of a config file provided via `-c` or `DOTFILES_CONFIG` could not be created.
+    /// Consists of the config file path and the underlying IO error.
+    ConfigDirUnwritable(PathBuf, std::io::Error),
     /// Failed to parse the config file
     /// Consists of the config file path and the underlying toml parse error
     ConfigParse(PathBuf, toml::de::Error),
     /// Failed to serialize the config
     /// Consists of the underlying toml parse error
     ConfigSerialize(toml::ser::Error),
+    /// The config file uses a version this binary does not support.
+    /// Consists of the found version.
+    ConfigUnsupportedVersion(i8),
+    /// The config file enables two options which cannot be combined.
+    /// Consists of the names of both options.
+    ConfigConflictingOptions(String, String),
     /// The configuration contains nested link entries, which is not supported
     /// Consists of the nested and parent paths
     ConfigNestedLinks(PathBuf, PathBuf),
+    /// A config file includes itself, directly or through other included files.
+    /// Consists of the path of the circularly included file.
+    ConfigCircularInclude(PathBuf),
+    /// A mapping is listed in more than one config file, see `include`.
+    /// Consists of the mapping and the included file listing it again.
+    ConfigDuplicateMapping(PathBuf, PathBuf),
+    /// A mapping of the active profile is listed in the mappings of the config already, see `--profile`.
+    /// Consists of the mapping and the name of the profile.
+    ConfigDuplicateProfileMapping(PathBuf, String),
+    /// A mapping of an included config file which is not writable was removed.
+    /// Consists of the mapping and the included file listing it.
+    ConfigReadOnlyInclude(PathBuf, PathBuf),
+    /// The ignore file in the dotfiles root contains an invalid pattern.
+    /// Consists of the path of the ignore file, the line number and the reason why the pattern is invalid.
+    ConfigIgnoreFile(PathBuf, usize, String),
     /// Found an absolute path in the mappings, which is not valid.
     /// Consists of the found absolute path.
     ConfigAbsoluteLink(PathBuf),
+    /// The config file is invalid for multiple reasons, see `--collect-errors`.
+    /// Consists of the config file path and all found errors.
+    ConfigInvalid(PathBuf, Vec<AppError>),
     /// File system error: Could not find a user file system location, such as home or config directory
     /// Consists of the name of the location, such as `home directory` or `config directory`
     FsUserLocation(String),
     /// Failed to resolve the relative location of the user config directory.
     /// TODO: remove this error and replace it with a user-friendlier version
     FsResolveConfig(StripPrefixError),
+    /// The current user is not allowed to write to a directory, eg. when creating a symlink in `~/.ssh`.
+    /// Consists of the directory which could not be written to.
+    FsPermissionDenied(PathBuf),
     /// An unspecified file system related error. Consists of a custom error message.
     FsOther(String),
     /// An error specific to the `add` sub-command occurred.
     /// Consists of the error itself.
     CmdAddError(commands::add::Error),
+    /// An error specific to the `remove` sub-command occurred.
+    /// Consists of the error itself.
+    CmdRemoveError(commands::remove::Error),
+    /// An external program such as `git` could not be run or exited unsuccessfully.
+    /// Consists of the executed command line and the reason of the failure.
+    ExternalCommand(String, String),
+    /// The command finished, but the process should exit with the given non-zero status,
+    /// eg. to signal a negative result to calling scripts. Nothing is printed for this error.
+    ExitStatus(i32),
     NotImplemented,
 }
 
@@ -59,6 +105,13 @@ impl Display for AppError {
             AppError::CliInvalidArgValue(arg, reason) => {
                 write!(f, "the provided value for <{}> is invalid: {}", arg, reason)
             }
+            AppError::CliNotInteractive(question) => {
+                write!(
+                    f,
+                    "Cannot ask \"{}\": stdin is not a terminal. Pass --yes to answer all questions with their default answer.",
+                    question
+                )
+            }
             AppError::ConfigFileRead(path, err) => {
                 write!(
                     f,
@@ -73,34 +126,126 @@ impl Display for AppError {
                     path, err
                 )
             }
+            AppError::ConfigDirUnwritable(path, err) => {
+                write!(
+                    f,
+                    "Could not create the directory of the dotfiles config file at {:?} provided via -c or DOTFILES_CONFIG: {}. Please provide a writable location.",
+                    path, err
+                )
+            }
+            AppError::ConfigOutsideDotfilesRoot(path, resolved) => {
+                write!(
+                    f,
+                    "Refusing to write dotfiles config file at {:?}: its directory resolves to {:?}, which is outside of your dotfiles directory. Please check for symlinks along this path.",
+                    path, resolved
+                )
+            }
             AppError::ConfigParse(path, err) => {
                 write!(f, "Failed to parse config file at {:?}: {}", path, err)
             }
             AppError::ConfigSerialize(err) => {
                 write!(f, "Failed to serialize config : {}", err)
             }
+            AppError::ConfigUnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "Unsupported config_version {} in your dotfiles config. This version of dotfiles supports config versions up to {}. Please update dotfiles or fix the config_version.",
+                    version,
+                    CURRENT_CONFIG_VERSION
+                )
+            }
+            AppError::ConfigConflictingOptions(first, second) => {
+                write!(
+                    f,
+                    "The options {} and {} in your dotfiles config cannot be enabled at the same time. Please remove one of them.",
+                    first, second
+                )
+            }
             AppError::ConfigNestedLinks(nested, parent) => {
                 write!(
                     f,
-                    "Invalid mappings in config: The mappings entry {:?} is nested in the entry {:?}",
+                    "Invalid mappings in config: The mappings entry {:?} is nested in the entry {:?}. Set `allow_nested = true` to allow nested mappings.",
                     nested, parent
                 )
             }
+            AppError::ConfigCircularInclude(path) => {
+                write!(
+                    f,
+                    "The config file {:?} includes itself. Please remove it from `include`.",
+                    path
+                )
+            }
+            AppError::ConfigDuplicateProfileMapping(mapping, profile) => {
+                write!(
+                    f,
+                    "The mapping {:?} of the profile {} is listed in your mappings already. Please remove one of them.",
+                    mapping, profile
+                )
+            }
+            AppError::ConfigDuplicateMapping(mapping, path) => {
+                write!(
+                    f,
+                    "The mapping {:?} is listed more than once, again in the included config file {:?}. Please remove one of them.",
+                    mapping, path
+                )
+            }
+            AppError::ConfigReadOnlyInclude(mapping, path) => {
+                write!(
+                    f,
+                    "Cannot remove the mapping {:?}, it is listed in the included config file {:?}, which is not writable. Please remove it there by hand or mark the include with `writable = true`.",
+                    mapping, path
+                )
+            }
+            AppError::ConfigIgnoreFile(path, line, reason) => {
+                write!(
+                    f,
+                    "Invalid pattern in {:?} on line {}: {}. Please fix or remove the line.",
+                    path, line, reason
+                )
+            }
             AppError::ConfigAbsoluteLink(link) => {
                 write!(f, "found an absolute path in the configured mappings: {:?}. This is not allowed. Mappings should be relative to the root of your dotfiles repository.", link)
             }
+            AppError::ConfigInvalid(path, errors) => {
+                write!(
+                    f,
+                    "Found {} problems in config file at {:?}:",
+                    errors.len(),
+                    path
+                )?;
+                for err in errors {
+                    write!(f, "\n- {}", err)?;
+                }
+                Ok(())
+            }
             AppError::FsUserLocation(location) => {
                 write!(f, "Could not find location: {}", location)
             }
             AppError::FsResolveConfig(err) => {
                 write!(f, "Could not resolve user config directory: {}", err)
             }
+            AppError::FsPermissionDenied(dir) => {
+                write!(
+                    f,
+                    "Permission denied: cannot write to {}. Please check the owner and permissions of this directory.",
+                    dir.display()
+                )
+            }
             AppError::FsOther(message) => {
                 write!(f, "A file system error occurred: {}", message)
             }
             AppError::CmdAddError(err) => {
                 write!(f, "{}", err)
             }
+            AppError::CmdRemoveError(err) => {
+                write!(f, "{}", err)
+            }
+            AppError::ExternalCommand(command, reason) => {
+                write!(f, "Failed to run `{}`: {}", command, reason)
+            }
+            AppError::ExitStatus(status) => {
+                write!(f, "exited with status {}", status)
+            }
             AppError::NotImplemented => {
                 write!(f, "Not implemented")
             }
@@ -1,13 +1,73 @@
-use crate::{cli::GlobalArgs, AppError};
+use crate::{cli::GlobalArgs, log, trace, AppError};
 use dirs::{config_dir, home_dir};
+use fs_extra::{dir, file};
+use serde_json::json;
+use walkdir::WalkDir;
 
-use std::{env::current_dir, os::unix::fs, path::PathBuf};
+use std::{
+    env::current_dir,
+    fs as std_fs, io,
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 /// returns the home directory of the current user
 pub fn get_home_dir() -> Result<PathBuf, AppError> {
     home_dir().ok_or(AppError::FsUserLocation("home directory".to_string()))
 }
 
+/// The default number of directory levels entered when scanning the home directory.
+pub const DEFAULT_HOME_SCAN_DEPTH: usize = 3;
+/// Directories relative to the home directory which are never entered when scanning it, because they are usually huge.
+const HOME_SCAN_SKIPPED_DIRS: [&str; 2] = [".cache", ".local/share"];
+/// Directory names which are never entered when scanning the home directory, independent of their location.
+const HOME_SCAN_SKIPPED_NAMES: [&str; 1] = ["node_modules"];
+
+/// returns all paths in the home directory up to the given depth, relative to the home directory and sorted by name.
+/// A depth of 1 only returns the direct children of the home directory.
+/// Symlinks are returned, but never followed. Huge directories such as `.cache` and the given excluded directories,
+/// such as the dotfiles directory, are skipped. Entries which cannot be read are silently ignored.
+pub fn scan_home_dir(home_dir: &Path, max_depth: usize, excluded: &[&Path]) -> Vec<PathBuf> {
+    WalkDir::new(home_dir)
+        .min_depth(1)
+        .max_depth(max_depth)
+        .follow_links(false)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| {
+            let path = entry.path();
+            let rel_path = path.strip_prefix(home_dir).unwrap_or(path);
+            !HOME_SCAN_SKIPPED_DIRS
+                .iter()
+                .any(|skipped| rel_path == Path::new(skipped))
+                && !HOME_SCAN_SKIPPED_NAMES
+                    .iter()
+                    .any(|skipped| entry.file_name() == *skipped)
+                && !excluded.contains(&path)
+        })
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .path()
+                .strip_prefix(home_dir)
+                .ok()
+                .map(|path| path.to_owned())
+        })
+        .collect()
+}
+
+/// returns the number of files in the given directory and its sub-directories.
+/// Symlinks are counted as files, but never followed. Entries which cannot be read are silently ignored.
+pub fn count_files(dir: &Path) -> usize {
+    WalkDir::new(dir)
+        .min_depth(1)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_type().is_dir())
+        .count()
+}
+
 /// returns the current working directory or an AppError if something went wrong.
 pub fn get_cwd() -> Result<PathBuf, AppError> {
     current_dir().map_err(|_| AppError::FsUserLocation("current directory".to_string()))
@@ -19,14 +79,18 @@ pub fn get_cwd() -> Result<PathBuf, AppError> {
 /// DOTFILES: path of dotfiles repository
 /// CONFIG:   relative path to user config from home directory, in most cases: `.config`
 /// config file path is resolved as: DOTFILES/CONFIG/dotfiles/config.toml
+/// A path provided via `-c` or `DOTFILES_CONFIG` is returned as it is instead.
 pub fn get_config_file_path(global_args: &GlobalArgs) -> Result<PathBuf, AppError> {
+    if let Some(config_file) = &global_args.config_file {
+        return Ok(config_file.to_owned());
+    }
     let home = get_home_dir()?;
     let config = config_dir().ok_or(AppError::FsUserLocation(String::from("config directory")))?;
 
     // the relative path of the user config dir (~/.config) from the home directory (=> '.config')
     let rel_config = config
         .strip_prefix(home)
-        .map_err(|err| AppError::FsResolveConfig(err))?;
+        .map_err(AppError::FsResolveConfig)?;
 
     let config_file_path = global_args
         .dotfiles_root
@@ -36,34 +100,613 @@ pub fn get_config_file_path(global_args: &GlobalArgs) -> Result<PathBuf, AppErro
     Ok(config_file_path)
 }
 
-pub fn create_symlink_for(from: &PathBuf, to: &PathBuf) -> Result<(), AppError> {
-    fs::symlink(to, from).map_err(|err| {
-        AppError::FsOther(format!(
+/// returns the path of the per-user settings file: `~/.config/dotfiles/settings.toml`.
+/// Unlike the dotfiles config, this file is read from the user config directory directly.
+pub fn get_settings_file_path() -> Result<PathBuf, AppError> {
+    let config = config_dir().ok_or(AppError::FsUserLocation(String::from("config directory")))?;
+
+    Ok(config.join("dotfiles/settings.toml"))
+}
+
+/// returns the path of the file storing the entries of the last `status --number` run, see `LastStatus`:
+/// `~/.config/dotfiles/last-status.json`, next to the settings file.
+pub fn get_last_status_file_path() -> Result<PathBuf, AppError> {
+    let config = config_dir().ok_or(AppError::FsUserLocation(String::from("config directory")))?;
+
+    Ok(config.join("dotfiles/last-status.json"))
+}
+
+/// tries to find the dotfiles repository based on the linked dotfiles config:
+/// if `~/.config/dotfiles/config.toml` resolves to `DOTFILES/.config/dotfiles/config.toml`,
+/// `DOTFILES` is returned. Returns `None` if the config file is not linked into another directory.
+pub fn discover_dotfiles_root(home: &Path, config: &Path) -> Option<PathBuf> {
+    let rel_config_file = config.strip_prefix(home).ok()?.join("dotfiles/config.toml");
+    let linked_config_file = home.join(&rel_config_file);
+    let resolved = linked_config_file.canonicalize().ok()?;
+    if resolved == linked_config_file || !resolved.ends_with(&rel_config_file) {
+        return None;
+    }
+
+    resolved
+        .ancestors()
+        .nth(rel_config_file.components().count())
+        .map(Path::to_owned)
+}
+
+/// creates a symlink at `from` pointing to `to`.
+pub fn create_symlink_for(from: &Path, to: &Path) -> Result<(), AppError> {
+    trace::event(
+        "create_symlink",
+        json!({ "from": trace::path_value(from), "to": trace::path_value(to) }),
+    );
+    symlink(to, from).map_err(|err| symlink_error(from, to, err))
+}
+
+/// creates a symlink at `link` pointing to `target`.
+/// Tests and benchmarks use it as well, so they do not depend on the symlink API of a single platform.
+#[cfg(unix)]
+pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// creates a symlink at `link` pointing to `target`.
+/// Windows distinguishes symlinks to files from symlinks to directories. Relative targets are resolved
+/// against the directory containing the link.
+#[cfg(windows)]
+pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> io::Result<()> {
+    let (target, link) = (target.as_ref(), link.as_ref());
+    let link_dir = link.parent().unwrap_or_else(|| Path::new(""));
+    if link_dir.join(target).is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+/// the Windows error code returned if the current user is not allowed to create symlinks.
+const ERROR_PRIVILEGE_NOT_HELD: i32 = 1314;
+
+/// translates an error of creating the symlink `from -> to` into an AppError.
+/// Permission errors get their own error pointing to the directory which is not writable.
+/// On Windows, they usually mean that creating symlinks is not allowed at all.
+fn symlink_error(from: &Path, to: &Path, err: io::Error) -> AppError {
+    if cfg!(windows)
+        && (err.kind() == io::ErrorKind::PermissionDenied
+            || err.raw_os_error() == Some(ERROR_PRIVILEGE_NOT_HELD))
+    {
+        return AppError::FsOther(format!(
+            "Could not create a symlink {} -> {}: {}. Creating symlinks on Windows requires the developer mode to be enabled or running dotfiles as administrator.",
+            from.display(),
+            to.display(),
+            err
+        ));
+    }
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => {
+            AppError::FsPermissionDenied(from.parent().unwrap_or(from).to_owned())
+        }
+        _ => AppError::FsOther(format!(
             "Could not create a symlink {} -> {}: {}",
-            from.clone().display(),
-            to.clone().display(),
+            from.display(),
+            to.display(),
+            err
+        )),
+    }
+}
+
+/// creates all missing parent directories of the given path.
+/// Returns the created directories, outermost first.
+pub fn create_parent_dir(path: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let mut created: Vec<PathBuf> = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    created.reverse();
+    if let Some(parent) = created.last() {
+        std_fs::create_dir_all(parent).map_err(|err| {
+            AppError::FsOther(format!(
+                "failed to create directory {}: {}",
+                parent.display(),
+                err
+            ))
+        })?;
+    }
+
+    Ok(created)
+}
+
+/// removes the given directories, innermost first, as long as they are empty.
+/// Used to clean up directories created by `create_parent_dir` if a later step failed.
+pub fn remove_empty_dirs(dirs: &[PathBuf]) {
+    for dir in dirs.iter().rev() {
+        if std_fs::remove_dir(dir).is_ok() {
+            trace::event("remove", json!({ "path": dir }));
+        }
+    }
+}
+
+/// moves the file or directory at `from` to `to`, which must not exist yet.
+pub fn move_path(from: &Path, to: &Path) -> Result<(), AppError> {
+    trace::event(
+        "move",
+        json!({ "from": trace::path_value(from), "to": trace::path_value(to) }),
+    );
+    // renaming would silently replace an existing file:
+    if to.symlink_metadata().is_ok() {
+        return Err(AppError::FsOther(format!(
+            "failed to move {} -> {}: the target already exists",
+            from.display(),
+            to.display()
+        )));
+    }
+    // renaming is atomic and supports all file names, but only works within the same file system:
+    match std_fs::rename(from, to) {
+        Ok(()) => return Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => (),
+        Err(err) => {
+            return Err(AppError::FsOther(format!(
+                "failed to move {} -> {}: {}",
+                from.display(),
+                to.display(),
+                err
+            )))
+        }
+    }
+    if from.is_dir() {
+        let mut options = dir::CopyOptions::new();
+        options.copy_inside = true;
+        dir::move_dir(from, to, &options).map_err(|err| {
+            AppError::FsOther(format!(
+                "failed to move directory {} -> {}: {}",
+                from.display(),
+                to.display(),
+                err
+            ))
+        })?;
+    } else {
+        let options = file::CopyOptions::new();
+        file::move_file(from, to, &options).map_err(|err| {
+            AppError::FsOther(format!(
+                "failed to move file {} -> {}: {}",
+                from.display(),
+                to.display(),
+                err
+            ))
+        })?;
+    }
+
+    Ok(())
+}
+
+/// writes the given content to a new or existing regular file at the given path.
+pub fn write_file(path: &Path, content: &[u8]) -> Result<(), AppError> {
+    trace::event("write", json!({ "path": trace::path_value(path) }));
+    std_fs::write(path, content)
+        .map_err(|err| AppError::FsOther(format!("failed to write {}: {}", path.display(), err)))
+}
+
+/// sets the permissions of the file at the given path to the ones of `source`,
+/// eg. to keep the executable bit of a file copied using `write_file`.
+pub fn copy_permissions(source: &Path, path: &Path) -> Result<(), AppError> {
+    let permissions = std_fs::metadata(source)
+        .map_err(|err| AppError::FsOther(format!("failed to read {}: {}", source.display(), err)))?
+        .permissions();
+    std_fs::set_permissions(path, permissions).map_err(|err| {
+        AppError::FsOther(format!(
+            "failed to set the permissions of {}: {}",
+            path.display(),
             err
         ))
     })
 }
 
+/// deletes the file, directory or symlink at the given path.
+/// Directories are removed recursively, symlinks are never followed.
+pub fn remove_path(path: &Path) -> Result<(), AppError> {
+    trace::event("remove", json!({ "path": trace::path_value(path) }));
+    let result = match path.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => std_fs::remove_dir_all(path),
+        Ok(meta) if is_dir_symlink(&meta) => std_fs::remove_dir(path),
+        _ => std_fs::remove_file(path),
+    };
+
+    result.map_err(|err| AppError::FsOther(format!("failed to remove {}: {}", path.display(), err)))
+}
+
+/// returns true if the given metadata of a path, which is not followed, belongs to a symlink to a directory.
+/// Windows removes them like directories, without touching the directory they point to.
+#[cfg(windows)]
+fn is_dir_symlink(meta: &std_fs::Metadata) -> bool {
+    use std::os::windows::fs::FileTypeExt;
+    meta.file_type().is_symlink_dir()
+}
+
+/// symlinks to directories are removed like any other file on all other platforms.
+#[cfg(not(windows))]
+fn is_dir_symlink(_meta: &std_fs::Metadata) -> bool {
+    false
+}
+
+/// returns a path which can be used to back up the given path in the given home directory, eg:
+/// `~/.vimrc => ~/.vimrc.bak.1618000000`
+/// If a backup directory is given, the backup is located there instead, keeping the path relative to the home directory:
+/// `~/.config/nvim => <backup_dir>/.config/nvim.bak.1618000000`
+pub fn get_backup_path(path: &Path, home_dir: &Path, backup_dir: Option<&Path>) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(format!(".bak.{}", timestamp));
+
+    match backup_dir {
+        Some(backup_dir) => {
+            let rel_path = path
+                .strip_prefix(home_dir)
+                .unwrap_or_else(|_| Path::new(path.file_name().unwrap_or_default()));
+            backup_dir.join(rel_path).with_file_name(file_name)
+        }
+        None => path.with_file_name(file_name),
+    }
+}
+
+/// returns the target of the given symlink as an absolute path.
+/// Relative targets such as `../dotfiles/.vimrc` are resolved against the directory containing the symlink.
+/// Symlinks along the resolved path are *not* followed.
+pub fn resolve_symlink_target(link: &Path) -> io::Result<PathBuf> {
+    let target = std_fs::read_link(link)?;
+    if target.is_absolute() {
+        return Ok(target);
+    }
+    let link_dir = link.parent().unwrap_or_else(|| Path::new(""));
+
+    Ok(normalize_lexically(&link_dir.join(target)))
+}
+
+/// returns the closest ancestor of the given relative mapped path in the home directory, which is a symlink
+/// into the dotfiles directory pointing somewhere else than its own counterpart, eg. `~/.config -> $DOTFILES/config`.
+/// Files linked or moved to the given path would end up inside the dotfiles directory through this symlink.
+/// Ancestors linked to their own counterpart, such as mapped parent directories, do not shadow the path.
+pub fn get_shadowing_ancestor(
+    home_dir: &Path,
+    dotfiles_root: &Path,
+    rel_path: &Path,
+) -> Option<PathBuf> {
+    rel_path.ancestors().skip(1).find_map(|rel_ancestor| {
+        if rel_ancestor.as_os_str().is_empty() {
+            return None;
+        }
+        let ancestor = home_dir.join(rel_ancestor);
+        let target = resolve_symlink_target(&ancestor).ok()?;
+        if target.starts_with(dotfiles_root) && target != dotfiles_root.join(rel_ancestor) {
+            Some(ancestor)
+        } else {
+            None
+        }
+    })
+}
+
+/// removes all `.` and `..` components of the given path without accessing the file system.
+pub fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    normalized
+}
+
+/// returns the path leading from the given absolute directory to the given absolute path,
+/// eg. `/home/me/.config` and `/home/me/dotfiles/.vimrc => ../dotfiles/.vimrc`.
+/// The paths are compared without accessing the file system.
+pub fn get_relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from_dir = normalize_lexically(from_dir);
+    let to = normalize_lexically(to);
+    let common = from_dir
+        .components()
+        .zip(to.components())
+        .take_while(|(from, to)| from == to)
+        .count();
+
+    from_dir
+        .components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .chain(to.components().skip(common))
+        .collect()
+}
+
+/// returns a shorter representation of the given absolute path for display purposes.
+/// Paths inside the dotfiles root are prefixed with `$DOTFILES`, paths inside the home directory with `~`,
+/// eg. `/home/me/dotfiles/.vimrc => $DOTFILES/.vimrc`. All other paths are returned unchanged.
+pub fn abbreviate_path(path: &Path, dotfiles_root: &Path, home_dir: &Path) -> String {
+    // the dotfiles root is checked first, because it is usually located inside the home directory:
+    if let Ok(rel_path) = path.strip_prefix(dotfiles_root) {
+        return Path::new("$DOTFILES").join(rel_path).display().to_string();
+    }
+    if let Ok(rel_path) = path.strip_prefix(home_dir) {
+        return Path::new("~").join(rel_path).display().to_string();
+    }
+
+    path.display().to_string()
+}
+
+/// returns the absolute form of a path provided by the user, eg. via environment variable, which is not expanded
+/// by the shell: a leading `~` is replaced with the given home directory, relative paths are resolved
+/// relative to the given current directory, see `normalize_paths`. Symlinks are not resolved.
+pub fn resolve_user_path(
+    path: &Path,
+    home_dir: impl FnOnce() -> Result<PathBuf, AppError>,
+    cwd: impl FnOnce() -> Result<PathBuf, AppError>,
+) -> Result<PathBuf, AppError> {
+    if let Ok(rel_path) = path.strip_prefix("~") {
+        return Ok(home_dir()?.join(rel_path));
+    }
+    if path.is_absolute() {
+        return Ok(path.to_owned());
+    }
+    let cwd = cwd()?;
+    match path.file_name() {
+        Some(_) => normalize_paths(&cwd, path),
+        // paths such as `.` or `..` do not have a name to keep, so they are resolved entirely:
+        None => cwd.join(path).canonicalize().map_err(|err| {
+            AppError::FsOther(format!(
+                "Could not canonicalize path {}: {}",
+                cwd.join(path).display(),
+                err
+            ))
+        }),
+    }
+}
+
 /// returns a canonicalized paths of the two given paths joined together.
 /// The joined path must exists.
 /// This method does *not* resolve symlinks.
-pub fn normalize_paths(p1: &PathBuf, p2: &PathBuf) -> Result<PathBuf, AppError> {
-    match p2.parent() {
-        None => Ok(p1.join(p2)),
-        Some(parent) => {
-            let first_part = p1.join(parent).canonicalize().map_err(|err| {
-                AppError::FsOther(format!(
-                    "Could not canonicalize path {}: {}",
-                    p1.join(parent).display(),
-                    err
-                ))
-            })?;
-            // should be safe
-            let file_name = p2.file_name().unwrap();
-            Ok(first_part.join(file_name))
+pub fn normalize_paths(p1: &Path, p2: &Path) -> Result<PathBuf, AppError> {
+    let canonicalize = |path: PathBuf| {
+        path.canonicalize().map_err(|err| {
+            AppError::FsOther(format!(
+                "Could not canonicalize path {}: {}",
+                path.display(),
+                err
+            ))
+        })
+    };
+    let normalized = match (p2.parent(), p2.file_name()) {
+        (None, _) => p1.join(p2),
+        (Some(parent), Some(file_name)) => canonicalize(p1.join(parent))?.join(file_name),
+        // paths such as `.` or `..` do not have a name to keep, so they are resolved entirely:
+        (Some(_), None) => canonicalize(p1.join(p2))?,
+    };
+    log::debug(format_args!(
+        "{} relative to {} is normalized to {}",
+        p2.display(),
+        p1.display(),
+        normalized.display()
+    ));
+
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        abbreviate_path, count_files, create_symlink_for, discover_dotfiles_root, get_backup_path,
+        get_relative_path, move_path, normalize_paths, resolve_user_path, scan_home_dir, symlink,
+        symlink_error,
+    };
+    use crate::AppError;
+    use std::{
+        fs, io,
+        path::{Path, PathBuf},
+    };
+
+    #[test]
+    fn resolve_user_path_expands_home_and_relative_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join("work/dotfiles")).unwrap();
+        let cwd = tmp.path().join("work").canonicalize().unwrap();
+        let resolve = |path: &str| {
+            resolve_user_path(
+                Path::new(path),
+                || Ok(PathBuf::from("/home/me")),
+                || Ok(cwd.clone()),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(resolve("~/dotfiles"), Path::new("/home/me/dotfiles"));
+        assert_eq!(resolve("~"), Path::new("/home/me"));
+        assert_eq!(resolve("/opt/dotfiles"), Path::new("/opt/dotfiles"));
+        assert_eq!(resolve("./dotfiles"), cwd.join("dotfiles"));
+        assert_eq!(resolve("dotfiles/../dotfiles"), cwd.join("dotfiles"));
+        assert_eq!(resolve("."), cwd);
+        // only a leading `~` component refers to the home directory:
+        assert_eq!(resolve("~dotfiles"), cwd.join("~dotfiles"));
+    }
+
+    /// file names which are not valid UTF-8 can only be created on unix.
+    #[test]
+    #[cfg(unix)]
+    fn create_symlink_for_and_move_path_support_unusual_file_names() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join("my home");
+        let dotfiles = tmp.path().join("dötfiles ✓");
+        fs::create_dir_all(home.join("Application Support/日本語")).unwrap();
+        fs::create_dir_all(&dotfiles).unwrap();
+        fs::write(
+            home.join("Application Support/日本語/settings file.json"),
+            "{}",
+        )
+        .unwrap();
+        // file names do not have to be valid UTF-8 on unix:
+        let invalid_utf8 = PathBuf::from(OsStr::from_bytes(b"caf\xe9 rc"));
+        fs::write(home.join(&invalid_utf8), "").unwrap();
+
+        for name in [Path::new("Application Support"), &invalid_utf8] {
+            move_path(&home.join(name), &dotfiles.join(name)).unwrap();
+            create_symlink_for(&home.join(name), &dotfiles.join(name)).unwrap();
+            assert_eq!(fs::read_link(home.join(name)).unwrap(), dotfiles.join(name));
         }
+        assert_eq!(
+            fs::read_to_string(home.join("Application Support/日本語/settings file.json")).unwrap(),
+            "{}"
+        );
+        assert_eq!(
+            normalize_paths(&home, Path::new("Application Support/日本語")).unwrap(),
+            dotfiles
+                .canonicalize()
+                .unwrap()
+                .join("Application Support/日本語")
+        );
+        assert_eq!(
+            normalize_paths(&dotfiles.join("Application Support"), Path::new("..")).unwrap(),
+            dotfiles.canonicalize().unwrap()
+        );
+
+        // error messages show both paths readably:
+        let err = move_path(
+            &dotfiles.join(&invalid_utf8),
+            &dotfiles.join("Application Support"),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("caf\u{FFFD} rc -> ")
+                && err.to_string().contains("dötfiles ✓/Application Support"),
+            "got {}",
+            err
+        );
+    }
+
+    #[test]
+    fn get_relative_path_walks_up_to_the_common_ancestor() {
+        let target = Path::new("/home/me/dotfiles/.vimrc");
+        assert_eq!(
+            get_relative_path(Path::new("/home/me"), target),
+            PathBuf::from("dotfiles/.vimrc")
+        );
+        assert_eq!(
+            get_relative_path(Path::new("/home/me/.config/nvim"), target),
+            PathBuf::from("../../dotfiles/.vimrc")
+        );
+        assert_eq!(
+            get_relative_path(Path::new("/opt/dotfiles"), target),
+            PathBuf::from("../../home/me/dotfiles/.vimrc")
+        );
+    }
+
+    /// permission errors have their own message on Windows.
+    #[test]
+    #[cfg(unix)]
+    fn symlink_error_reports_permission_denied_directory() {
+        let from = Path::new("/home/me/.ssh/config");
+        let to = Path::new("/home/me/dotfiles/.ssh/config");
+
+        let err = symlink_error(from, to, io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(
+            matches!(err, AppError::FsPermissionDenied(ref dir) if dir == Path::new("/home/me/.ssh")),
+            "got {:?}",
+            err
+        );
+
+        let err = symlink_error(from, to, io::Error::from(io::ErrorKind::AlreadyExists));
+        assert!(matches!(err, AppError::FsOther(_)), "got {:?}", err);
+    }
+
+    #[test]
+    fn discover_dotfiles_root_follows_linked_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let tmp = tmp.path().canonicalize().unwrap();
+        let home = tmp.join("home");
+        let config = home.join(".config");
+        let dotfiles = tmp.join("dotfiles");
+        fs::create_dir_all(dotfiles.join(".config/dotfiles")).unwrap();
+        fs::write(dotfiles.join(".config/dotfiles/config.toml"), "").unwrap();
+        fs::create_dir_all(&config).unwrap();
+
+        // nothing linked yet:
+        assert_eq!(discover_dotfiles_root(&home, &config), None);
+
+        symlink(dotfiles.join(".config/dotfiles"), config.join("dotfiles")).unwrap();
+        assert_eq!(discover_dotfiles_root(&home, &config), Some(dotfiles));
+    }
+
+    #[test]
+    fn abbreviate_path_prefers_dotfiles_root_over_home() {
+        let home = Path::new("/home/me");
+        let dotfiles = Path::new("/home/me/dotfiles");
+
+        let abbreviate = |path: &str| abbreviate_path(Path::new(path), dotfiles, home);
+        assert_eq!(abbreviate("/home/me/dotfiles/.vimrc"), "$DOTFILES/.vimrc");
+        assert_eq!(abbreviate("/home/me/.bashrc"), "~/.bashrc");
+        assert_eq!(abbreviate("/etc/hosts"), "/etc/hosts");
+    }
+
+    #[test]
+    fn get_backup_path_keeps_structure_in_backup_dir() {
+        let home = Path::new("/home/me");
+        let backup = get_backup_path(&home.join(".config/nvim"), home, None);
+        assert!(backup.starts_with("/home/me/.config"));
+        assert!(backup
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("nvim.bak."));
+
+        let backup_dir = Path::new("/backups");
+        let backup = get_backup_path(&home.join(".config/nvim"), home, Some(backup_dir));
+        assert_eq!(backup.parent(), Some(Path::new("/backups/.config")));
+        assert!(backup
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("nvim.bak."));
+    }
+
+    #[test]
+    fn count_files_counts_nested_files_and_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::create_dir_all(dir.join("empty")).unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+        fs::write(dir.join("a/b/file"), "").unwrap();
+        symlink(dir.join("a"), dir.join("link")).unwrap();
+
+        assert_eq!(count_files(dir), 3);
+        assert_eq!(count_files(&dir.join("empty")), 0);
+    }
+
+    #[test]
+    fn scan_home_dir_respects_depth_and_skipped_dirs() {
+        let home = tempfile::tempdir().unwrap();
+        for dir in &[
+            ".cache/huge",
+            ".config/nvim/lua/deep",
+            ".config/app/node_modules/dep",
+            "dotfiles/.vimrc",
+        ] {
+            fs::create_dir_all(home.path().join(dir)).unwrap();
+        }
+        let dotfiles = home.path().join("dotfiles");
+
+        let paths = scan_home_dir(home.path(), 3, &[&dotfiles]);
+
+        let expected: Vec<PathBuf> =
+            vec![".config", ".config/app", ".config/nvim", ".config/nvim/lua"]
+                .into_iter()
+                .map(PathBuf::from)
+                .collect();
+        assert_eq!(paths, expected);
     }
 }
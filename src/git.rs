@@ -0,0 +1,29 @@
+use crate::AppError;
+use std::{path::Path, process::Command};
+
+/// runs git with the given arguments inside the given directory and returns its stdout.
+/// Returns an error if git could not be started or exited with a non-zero status.
+pub fn run_git(dir: &Path, args: &[&str]) -> Result<String, AppError> {
+    let command_line = format!("git {}", args.join(" "));
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| AppError::ExternalCommand(command_line.clone(), err.to_string()))?;
+
+    if !output.status.success() {
+        return Err(AppError::ExternalCommand(
+            command_line,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// returns true if the given directory is part of a git work tree.
+pub fn is_git_repo(dir: &Path) -> bool {
+    run_git(dir, &["rev-parse", "--is-inside-work-tree"])
+        .map(|output| output.trim() == "true")
+        .unwrap_or(false)
+}
@@ -0,0 +1,368 @@
+use crate::AppError;
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// The name of the file in the dotfiles root listing the entries which are never reported as unmapped.
+pub const IGNORE_FILE_NAME: &str = ".dotfilesignore";
+/// Patterns which are always applied before the ones of the ignore file.
+/// They can be negated in the ignore file, eg. `!/.git`.
+const DEFAULT_PATTERNS: [&str; 2] = ["/.git", "/.dotfilesignore"];
+
+/// A single pattern of an ignore file, see `IgnoreRules`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IgnorePattern {
+    /// the glob to match, without a leading `!`, leading `/` or trailing `/`.
+    glob: Vec<char>,
+    /// if true, matching paths are not ignored even if a previous pattern ignores them.
+    negated: bool,
+    /// if true, only directories match this pattern.
+    dir_only: bool,
+    /// if true, the glob is matched against the whole relative path, otherwise only against the file name.
+    anchored: bool,
+}
+
+/// The patterns of the ignore file in the dotfiles root, using a subset of the gitignore syntax:
+/// - empty lines and lines starting with `#` are skipped
+/// - `*` matches anything but `/`, `?` a single character except `/`, `[a-z]` one of the given characters
+///   and `**` any number of directories
+/// - patterns containing a `/` are matched against the path relative to the dotfiles root,
+///   all others against the file name only
+/// - patterns ending with `/` only match directories
+/// - patterns starting with `!` include paths excluded by a previous pattern again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        IgnoreRules {
+            patterns: DEFAULT_PATTERNS
+                .iter()
+                // safe, the default patterns are valid:
+                .map(|line| parse_pattern(line).unwrap().unwrap())
+                .collect(),
+        }
+    }
+}
+
+impl IgnoreRules {
+    /// reads the ignore file in the given dotfiles root. Only the default patterns are used if it does not exist.
+    pub fn from_dotfiles_root(dotfiles_root: &Path) -> Result<IgnoreRules, AppError> {
+        let path = dotfiles_root.join(IGNORE_FILE_NAME);
+        match fs::read_to_string(&path) {
+            Ok(content) => IgnoreRules::parse(&content, &path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(IgnoreRules::default()),
+            Err(err) => Err(AppError::FsOther(format!(
+                "Could not read {}: {}",
+                path.display(),
+                err
+            ))),
+        }
+    }
+
+    /// parses the given content of the ignore file at the given path, see `IgnoreRules`.
+    fn parse(content: &str, path: &Path) -> Result<IgnoreRules, AppError> {
+        let mut rules = IgnoreRules::default();
+        for (index, line) in content.lines().enumerate() {
+            let pattern = parse_pattern(line).map_err(|reason| {
+                AppError::ConfigIgnoreFile(path.to_path_buf(), index + 1, reason)
+            })?;
+            rules.patterns.extend(pattern);
+        }
+
+        Ok(rules)
+    }
+
+    /// returns true if the given path relative to the dotfiles root is ignored.
+    /// `is_dir` is only called if a pattern matching directories only matches the path.
+    pub fn is_ignored<F: Fn() -> bool>(&self, rel_path: &Path, is_dir: F) -> bool {
+        let path: Vec<char> = to_slash_path(rel_path).chars().collect();
+        let file_name: Vec<char> = rel_path
+            .file_name()
+            .map(|name| name.to_string_lossy().chars().collect())
+            .unwrap_or_default();
+        let mut ignored = false;
+        for pattern in &self.patterns {
+            // the last matching pattern wins, so only patterns which could change the result are checked:
+            if pattern.negated != ignored {
+                continue;
+            }
+            let text = if pattern.anchored { &path } else { &file_name };
+            if !glob_matches(&pattern.glob, text) {
+                continue;
+            }
+            if pattern.dir_only && !is_dir() {
+                continue;
+            }
+            ignored = !pattern.negated;
+        }
+
+        ignored
+    }
+}
+
+/// returns true if the given path contains a glob pattern, ie. `*`, `?` or `[`.
+pub fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// returns the existing paths matching the given absolute glob pattern, sorted by path.
+/// Each component containing a pattern is matched against the entries of the directories matched so far,
+/// using the syntax of `IgnoreRules`. Hidden entries are matched as well.
+/// Returns the reason why the pattern is invalid, if any.
+pub fn expand_glob(pattern: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut matches = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let glob: Vec<char> = component.as_os_str().to_string_lossy().chars().collect();
+        if !matches!(component, Component::Normal(_)) || !is_glob(Path::new(component.as_os_str()))
+        {
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        }
+        validate_glob(&glob)?;
+        matches = matches
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name: Vec<char> = entry.file_name().to_string_lossy().chars().collect();
+                glob_matches(&glob, &name)
+            })
+            .map(|entry| entry.path())
+            .collect();
+    }
+    matches.retain(|path| path.symlink_metadata().is_ok());
+    matches.sort();
+
+    Ok(matches)
+}
+
+/// returns the given relative path with all components separated by `/`, on all platforms.
+fn to_slash_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// parses a single line of an ignore file. Returns `None` for empty lines and comments
+/// or the reason why the line is not a valid pattern.
+fn parse_pattern(line: &str) -> Result<Option<IgnorePattern>, String> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let anchored = line.contains('/');
+    let line = line.strip_prefix('/').unwrap_or(line);
+    if line.is_empty() {
+        return Err("the pattern does not match any path".to_string());
+    }
+    let glob: Vec<char> = line.chars().collect();
+    validate_glob(&glob)?;
+
+    Ok(Some(IgnorePattern {
+        glob,
+        negated,
+        dir_only,
+        anchored,
+    }))
+}
+
+/// makes sure all character classes of the given glob are closed and escapes are followed by a character.
+fn validate_glob(glob: &[char]) -> Result<(), String> {
+    let mut index = 0;
+    while index < glob.len() {
+        match glob[index] {
+            '\\' if index + 1 == glob.len() => {
+                return Err("the pattern ends with an unescaped `\\`".to_string())
+            }
+            '\\' => index += 1,
+            '[' => match find_class_end(glob, index) {
+                Some(end) => index = end,
+                None => return Err("the pattern contains an unclosed `[`".to_string()),
+            },
+            _ => (),
+        }
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// returns the index of the `]` closing the character class starting at the given index, if any.
+/// A `]` directly after the opening `[` (or `[!`) is part of the class.
+fn find_class_end(glob: &[char], start: usize) -> Option<usize> {
+    let mut index = start + 1;
+    if matches!(glob.get(index), Some('!') | Some('^')) {
+        index += 1;
+    }
+    if glob.get(index) == Some(&']') {
+        index += 1;
+    }
+    glob[index.min(glob.len())..]
+        .iter()
+        .position(|c| *c == ']')
+        .map(|offset| index + offset)
+}
+
+/// returns true if the given character matches the given character class, ie. the characters between `[` and `]`.
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut matched = false;
+    let mut index = 0;
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            matched |= class[index] <= c && c <= class[index + 2];
+            index += 3;
+        } else {
+            matched |= class[index] == c;
+            index += 1;
+        }
+    }
+
+    matched != negated
+}
+
+/// returns true if the given glob matches the whole given text, see `IgnoreRules` for the syntax.
+fn glob_matches(glob: &[char], text: &[char]) -> bool {
+    match glob.first() {
+        None => text.is_empty(),
+        Some('*') if glob.get(1) == Some(&'*') => {
+            let rest = &glob[2..];
+            match rest.first() {
+                // `**/` matches no directory at all or any number of whole directories:
+                Some('/') => {
+                    let rest = &rest[1..];
+                    glob_matches(rest, text)
+                        || (0..text.len())
+                            .filter(|index| text[*index] == '/')
+                            .any(|index| glob_matches(rest, &text[index + 1..]))
+                }
+                _ => (0..=text.len()).any(|index| glob_matches(rest, &text[index..])),
+            }
+        }
+        Some('*') => {
+            let rest = &glob[1..];
+            (0..=text.len())
+                .take_while(|index| *index == 0 || text[index - 1] != '/')
+                .any(|index| glob_matches(rest, &text[index..]))
+        }
+        Some('?') => match text.first() {
+            Some(c) if *c != '/' => glob_matches(&glob[1..], &text[1..]),
+            _ => false,
+        },
+        Some('[') => {
+            // safe, the glob has been validated when parsing it:
+            let end = find_class_end(glob, 0).unwrap();
+            match text.first() {
+                Some(c) if *c != '/' && class_matches(&glob[1..end], *c) => {
+                    glob_matches(&glob[end + 1..], &text[1..])
+                }
+                _ => false,
+            }
+        }
+        Some('\\') => text.first() == glob.get(1) && glob_matches(&glob[2..], &text[1..]),
+        Some(c) => text.first() == Some(c) && glob_matches(&glob[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_glob, IgnoreRules};
+    use crate::AppError;
+    use std::{fs, path::Path};
+
+    fn rules(content: &str) -> IgnoreRules {
+        IgnoreRules::parse(content, Path::new(".dotfilesignore")).unwrap()
+    }
+
+    #[test]
+    fn is_ignored_matches_gitignore_style_patterns() {
+        let rules = rules("# docs\nREADME.md\n/install.sh\n*.bak\nscripts/\n.config/**/cache\n");
+        let is_ignored = |path: &str, is_dir: bool| rules.is_ignored(Path::new(path), || is_dir);
+
+        assert!(is_ignored(".git", true));
+        assert!(is_ignored("README.md", false));
+        assert!(is_ignored(".config/nvim/README.md", false));
+        assert!(is_ignored("install.sh", false));
+        assert!(!is_ignored(".config/install.sh", false));
+        assert!(is_ignored(".vimrc.bak", false));
+        assert!(is_ignored("scripts", true));
+        assert!(!is_ignored("scripts", false));
+        assert!(is_ignored(".config/cache", true));
+        assert!(is_ignored(".config/app/sub/cache", false));
+        assert!(!is_ignored(".vimrc", false));
+    }
+
+    #[test]
+    fn is_ignored_lets_later_negated_patterns_win() {
+        let rules = rules("*.md\n!CHANGELOG.md\n!/.git\n[a-c]?.txt\n");
+        let is_ignored = |path: &str| rules.is_ignored(Path::new(path), || false);
+
+        assert!(is_ignored("README.md"));
+        assert!(!is_ignored("CHANGELOG.md"));
+        assert!(!is_ignored(".git"));
+        assert!(is_ignored("b1.txt"));
+        assert!(!is_ignored("d1.txt"));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_patterns() {
+        let result = IgnoreRules::parse("README.md\n\n[abc\n", Path::new(".dotfilesignore"));
+        assert!(
+            matches!(result, Err(AppError::ConfigIgnoreFile(_, 3, _))),
+            "got {:?}",
+            result
+        );
+        assert!(IgnoreRules::parse("/\n", Path::new(".dotfilesignore")).is_err());
+    }
+
+    #[test]
+    fn expand_glob_returns_existing_matches_of_every_component() {
+        let tmp = tempfile::tempdir().unwrap();
+        for dir in [".config/nvim", ".config/fish", ".local/share"] {
+            fs::create_dir_all(tmp.path().join(dir)).unwrap();
+        }
+        fs::write(tmp.path().join(".config/starship.toml"), "").unwrap();
+
+        assert_eq!(
+            expand_glob(&tmp.path().join(".config/*")).unwrap(),
+            vec![
+                tmp.path().join(".config/fish"),
+                tmp.path().join(".config/nvim"),
+                tmp.path().join(".config/starship.toml"),
+            ]
+        );
+        assert_eq!(
+            expand_glob(&tmp.path().join(".*/[ns]*")).unwrap(),
+            vec![
+                tmp.path().join(".config/nvim"),
+                tmp.path().join(".config/starship.toml"),
+                tmp.path().join(".local/share"),
+            ]
+        );
+        assert!(expand_glob(&tmp.path().join(".config/*.vim"))
+            .unwrap()
+            .is_empty());
+        assert!(expand_glob(&tmp.path().join(".config/[abc")).is_err());
+    }
+}
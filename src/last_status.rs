@@ -0,0 +1,144 @@
+use crate::{
+    cli::GlobalArgs,
+    config::AppConfig,
+    errors::AppError,
+    files::{create_parent_dir, get_last_status_file_path, write_file},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The entries printed by the last `status --number` run in the order they were numbered, starting at 1.
+/// It is stored outside of the dotfiles directory, see `get_last_status_file_path`, and lets commands
+/// accept the number of an entry instead of its path, eg. `dotfiles unlink 3`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct LastStatus {
+    /// the dotfiles directory the entries are relative to.
+    pub dotfiles_root: PathBuf,
+    /// the printed entries relative to the dotfiles directory.
+    pub entries: Vec<PathBuf>,
+}
+
+impl LastStatus {
+    /// reads the last status from the given file. Returns `None` if it does not exist.
+    pub fn from_file(path: &Path) -> Result<Option<LastStatus>, AppError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(AppError::ConfigFileRead(path.to_owned(), err)),
+        };
+        serde_json::from_str(&content).map(Some).map_err(|err| {
+            AppError::FsOther(format!("Could not parse {}: {}", path.display(), err))
+        })
+    }
+
+    /// writes this status to the given file, replacing the previous one.
+    pub fn to_file(&self, path: &Path) -> Result<(), AppError> {
+        let content = serde_json::to_string(self).map_err(|err| {
+            AppError::FsOther(format!("Failed to serialize the last status: {}", err))
+        })?;
+        create_parent_dir(path)?;
+        write_file(path, content.as_bytes())
+    }
+
+    /// returns the mapped path of the entry with the given number.
+    /// Fails with the reason if the number is out of range or the entry is not mapped (anymore), eg. because
+    /// the config changed since the last status, so a stale number never refers to another mapping.
+    pub fn resolve(
+        &self,
+        number: usize,
+        dotfiles_root: &Path,
+        config: &AppConfig,
+    ) -> Result<PathBuf, String> {
+        if self.dotfiles_root != dotfiles_root {
+            return Err(format!(
+                "The last numbered status was printed for {}. Run `dotfiles status --number` again.",
+                self.dotfiles_root.display()
+            ));
+        }
+        let path = number
+            .checked_sub(1)
+            .and_then(|index| self.entries.get(index))
+            .ok_or_else(|| {
+                format!(
+                    "There is no entry {}, the last numbered status listed {} entries.",
+                    number,
+                    self.entries.len()
+                )
+            })?;
+        match config.get_mapping(path) {
+            Some(mapping) => Ok(mapping.path.to_owned()),
+            None => Err(format!(
+                "Entry {} ({}) is not a mapping (anymore). If your config changed, run `dotfiles status --number` again.",
+                number,
+                path.display()
+            )),
+        }
+    }
+}
+
+/// returns the mapped path the given value of the given CLI argument refers to if it is the number of an entry
+/// of the last `status --number` run, see `LastStatus`. Returns `None` if the value is not a number
+/// or a mapping with exactly this path exists, which takes precedence.
+pub fn resolve_entry_number(
+    value: &str,
+    arg_name: &str,
+    global_args: &GlobalArgs,
+    config: &AppConfig,
+) -> Result<Option<PathBuf>, AppError> {
+    let number = match value.parse::<usize>() {
+        Ok(number) if config.get_mapping(Path::new(value)).is_none() => number,
+        _ => return Ok(None),
+    };
+    let invalid = |reason: String| AppError::CliInvalidArgValue(arg_name.to_string(), reason);
+    let last_status = LastStatus::from_file(&get_last_status_file_path()?)?.ok_or_else(|| {
+        invalid(format!(
+            "There is no numbered status to refer to entry {}. Run `dotfiles status --number` first.",
+            number
+        ))
+    })?;
+
+    last_status
+        .resolve(number, &global_args.dotfiles_root, config)
+        .map(Some)
+        .map_err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LastStatus;
+    use crate::config::{AppConfig, Mapping};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn resolve_rejects_stale_numbers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("dotfiles/last-status.json");
+        LastStatus {
+            dotfiles_root: PathBuf::from("/dotfiles"),
+            entries: vec![PathBuf::from(".bashrc"), PathBuf::from(".vimrc")],
+        }
+        .to_file(&file)
+        .unwrap();
+        let last_status = LastStatus::from_file(&file).unwrap().unwrap();
+        let config = AppConfig {
+            mappings: vec![Mapping::new(PathBuf::from(".vimrc"))],
+            ..Default::default()
+        };
+        let resolve =
+            |number: usize, root: &str| last_status.resolve(number, Path::new(root), &config);
+
+        assert_eq!(resolve(2, "/dotfiles"), Ok(PathBuf::from(".vimrc")));
+        // the mapping has been removed since:
+        assert!(resolve(1, "/dotfiles").is_err());
+        assert!(resolve(0, "/dotfiles").is_err());
+        assert!(resolve(3, "/dotfiles").is_err());
+        assert!(resolve(2, "/other").is_err());
+        assert_eq!(
+            LastStatus::from_file(&tmp.path().join("missing.json")).unwrap(),
+            None
+        );
+    }
+}
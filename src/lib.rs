@@ -1,4 +1,4 @@
-use cli::build_cli;
+use clap::ArgMatches;
 use errors::AppError;
 
 pub mod cli;
@@ -6,9 +6,16 @@ pub mod commands;
 pub mod config;
 pub mod errors;
 pub mod files;
+pub mod git;
+pub mod ignore;
+pub mod last_status;
+pub mod log;
+pub mod settings;
+pub mod template;
+pub mod trace;
 
-/// runs the application. Reads all process arguments and calls the appropriate command handler
-pub fn run() -> Result<(), AppError> {
-    let cli_args = build_cli().get_matches();
-    commands::run_command(&cli_args)
+/// runs the application. Calls the appropriate command handler for the given process arguments,
+/// see `cli::build_cli`.
+pub fn run(cli_args: &ArgMatches) -> Result<(), AppError> {
+    commands::run_command(cli_args)
 }
@@ -0,0 +1,32 @@
+use colored::*;
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// Human-readable debug output of the decisions a command takes, printed to stderr.
+/// It is enabled per level via the repeatable `-v` flag, without it all calls to `debug` and `trace` are no-ops.
+/// In contrast to `trace.rs`, it is meant to be read while running a command, not attached to bug reports.
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// sets the verbosity of this run, ie. the number of `-v` flags.
+pub fn init(verbosity: u8) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+}
+
+/// prints the given message if at least one `-v` flag was provided.
+/// Use `format_args!` to only format the message if it is printed.
+pub fn debug<D: Display>(message: D) {
+    print(1, "debug", message);
+}
+
+/// prints the given message if at least two `-v` flags were provided, eg. for details logged per path.
+pub fn trace<D: Display>(message: D) {
+    print(2, "trace", message);
+}
+
+fn print<D: Display>(level: u8, label: &str, message: D) {
+    if VERBOSITY.load(Ordering::Relaxed) >= level {
+        eprintln!("{} {}", format!("{}:", label).dimmed(), message);
+    }
+}
@@ -1,4 +1,10 @@
 use colored::*;
+use dotfiles::{
+    cli::{build_cli, is_json},
+    errors::AppError,
+};
+use serde_json::json;
+use std::process::exit;
 
 /// Entry point of this binary. Use `dotfiles --help` to get an overview of how to use it.
 ///
@@ -9,6 +15,12 @@ use colored::*;
 /// cli.rs     : CLI interface definitions
 /// config.rs  : everything related to reading and writing configurations
 /// files.rs   : file system abstractions commonly used in this binary
+/// git.rs     : helpers for running git inside the dotfiles repository
+/// log.rs     : optional debug output of the decisions taken, see `-v`
+/// last_status.rs: the entries of the last numbered status, see `status --number`
+/// settings.rs: optional per-user settings stored outside of the dotfiles repository
+/// template.rs: minimal template engine used for copied mappings
+/// trace.rs   : optional machine-readable trace of a single run, see `--trace-file`
 ///
 /// Error Handling:
 /// This binary declares its own error enum `AppError` in `lib.rs`. All functions which return a `Result`
@@ -16,14 +28,25 @@ use colored::*;
 /// are passed up to the `main` entrypoint where they are printed to the user. There should be no usage of
 /// `panic!`, unhandled `unwrap` call and similar constructs in this app. This should improve the readability
 /// and usefulness of errors to the user in front of the screen.
+/// The process exits with status 1 if an error occurred. Commands may return `AppError::ExitStatus` to exit
+/// with a specific status without printing an error.
 ///
 /// Further notes (mostly to myself):
 /// 1. Avoid over-abstraction: remember KISS and don't be scared of repeating yourself here and there.
 /// 2. This binary is human-first: All output including errors should be human-readable and helpful.
 ///    It makes use of special flags such as `--json` to provide a machine-readable output.
 fn main() {
-    if let Err(msg) = dotfiles::run() {
-        // TODO: should we print errors differently when `--json` has been provided?
-        eprintln!("{}: {}", "Error".red().bold(), msg);
+    let cli_args = build_cli().get_matches();
+    match dotfiles::run(&cli_args) {
+        Ok(()) => (),
+        Err(AppError::ExitStatus(status)) => exit(status),
+        Err(msg) if is_json(&cli_args) => {
+            eprintln!("{}", json!({ "error": msg.to_string() }));
+            exit(1);
+        }
+        Err(msg) => {
+            eprintln!("{}: {}", "Error".red().bold(), msg);
+            exit(1);
+        }
     }
 }
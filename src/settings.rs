@@ -0,0 +1,132 @@
+use crate::{files::write_file, AppError};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Describes the optional per-user settings file located at `~/.config/dotfiles/settings.toml`.
+/// In contrast to the dotfiles configuration, this file is *not* part of the dotfiles repository,
+/// so it can be used to locate the repository itself:
+/// ```toml
+/// dotfiles_root = "/home/me/dotfiles"
+/// backup_dir = ".dotfiles-backups"
+/// theme = "high-contrast"
+/// use_symbols = true
+/// [colors]
+/// conflict = "bright magenta"
+/// [symbols]
+/// conflict = "⚡"
+/// ```
+#[derive(Deserialize, Debug, Default)]
+pub struct Settings {
+    /// the absolute path of the dotfiles repository, used if neither `-r` nor `DOTFILES_ROOT` is provided.
+    pub dotfiles_root: Option<PathBuf>,
+    /// the directory backups of conflicting files are moved to, see `files::get_backup_path`.
+    /// Relative paths are resolved against the home directory. Backups are stored next to the original files if missing.
+    pub backup_dir: Option<PathBuf>,
+    /// the name of the built-in color theme used by `status`, see `status --theme`.
+    pub theme: Option<String>,
+    /// colors overriding the theme per state name, eg. `conflict = "bright magenta"`.
+    #[serde(default)]
+    pub colors: HashMap<String, String>,
+    /// if true, `status` shows symbols instead of text labels for the states, see `status --symbols`.
+    pub use_symbols: Option<bool>,
+    /// symbols overriding the default symbols per state name, eg. `conflict = "⚡"`.
+    #[serde(default)]
+    pub symbols: HashMap<String, String>,
+}
+impl Settings {
+    /// returns the absolute path of the configured backup directory, if any.
+    pub fn get_backup_dir(&self, home_dir: &Path) -> Option<PathBuf> {
+        self.backup_dir.as_ref().map(|dir| home_dir.join(dir))
+    }
+
+    /// reads the settings file at the given path.
+    /// Returns the default settings if the file does not exist, it is never created.
+    pub fn from_file(path: &Path) -> Result<Settings, AppError> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                toml::from_str(&content).map_err(|err| AppError::ConfigParse(path.to_owned(), err))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(err) => Err(AppError::ConfigFileRead(path.to_owned(), err)),
+        }
+    }
+
+    /// sets `dotfiles_root` in the settings file at the given path, keeping all other settings.
+    /// The file is created if it does not exist yet.
+    pub fn save_dotfiles_root(path: &Path, dotfiles_root: &Path) -> Result<(), AppError> {
+        let mut settings = match fs::read_to_string(path) {
+            Ok(content) => content
+                .parse::<toml::Table>()
+                .map_err(|err| AppError::ConfigParse(path.to_owned(), err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+            Err(err) => return Err(AppError::ConfigFileRead(path.to_owned(), err)),
+        };
+        settings.insert(
+            String::from("dotfiles_root"),
+            toml::Value::String(dotfiles_root.display().to_string()),
+        );
+        let content = toml::to_string_pretty(&settings).map_err(AppError::ConfigSerialize)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| {
+                AppError::FsOther(format!("failed to create {}: {}", dir.display(), err))
+            })?;
+        }
+        write_file(path, content.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    #[test]
+    fn save_dotfiles_root_keeps_other_settings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("dotfiles/settings.toml");
+
+        Settings::save_dotfiles_root(&path, Path::new("/home/me/dotfiles")).unwrap();
+        fs::write(
+            &path,
+            "dotfiles_root = \"/old\"\ntheme = \"high-contrast\"\n",
+        )
+        .unwrap();
+        Settings::save_dotfiles_root(&path, Path::new("/home/me/dotfiles")).unwrap();
+
+        let settings = Settings::from_file(&path).unwrap();
+        assert_eq!(
+            settings.dotfiles_root,
+            Some(PathBuf::from("/home/me/dotfiles"))
+        );
+        assert_eq!(settings.theme.as_deref(), Some("high-contrast"));
+    }
+
+    #[test]
+    fn from_file_reads_dotfiles_root_and_tolerates_missing_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("settings.toml");
+
+        let settings = Settings::from_file(&path).unwrap();
+        assert_eq!(settings.dotfiles_root, None);
+        assert!(!path.exists());
+
+        fs::write(
+            &path,
+            "dotfiles_root = \"/home/me/dotfiles\"\n[colors]\nlinked = \"blue\"",
+        )
+        .unwrap();
+        let settings = Settings::from_file(&path).unwrap();
+        assert_eq!(
+            settings.dotfiles_root,
+            Some(PathBuf::from("/home/me/dotfiles"))
+        );
+        assert_eq!(settings.colors["linked"], "blue");
+    }
+}
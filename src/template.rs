@@ -0,0 +1,91 @@
+use crate::{cli::GlobalArgs, config::Mapping};
+use std::{env, fs, io, path::Path};
+
+/// returns the content a copied mapping should have in the home directory:
+/// the content of the given source file, rendered as template if the mapping enables it.
+pub fn render_mapping(
+    global_args: &GlobalArgs,
+    home_dir: &Path,
+    mapping: &Mapping,
+    source: &Path,
+) -> io::Result<Vec<u8>> {
+    let content = fs::read(source)?;
+    if !mapping.template {
+        return Ok(content);
+    }
+
+    let template = String::from_utf8(content).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("template {} is not valid UTF-8", source.display()),
+        )
+    })?;
+    let rendered = render(&template, |name| get_variable(global_args, home_dir, name));
+
+    Ok(rendered.into_bytes())
+}
+
+/// returns the value of the template variable with the given name:
+/// `hostname`, `home`, `dotfiles_root` or `env.<NAME>` for the environment variable `NAME`.
+fn get_variable(global_args: &GlobalArgs, home_dir: &Path, name: &str) -> Option<String> {
+    match name {
+        "hostname" => global_args.hostname.clone(),
+        "home" => Some(home_dir.to_string_lossy().into_owned()),
+        "dotfiles_root" => Some(global_args.dotfiles_root.to_string_lossy().into_owned()),
+        _ => name
+            .strip_prefix("env.")
+            .and_then(|variable| env::var(variable).ok()),
+    }
+}
+
+/// replaces all placeholders such as `{{hostname}}` or `{{ env.FONT_SIZE }}` in the given template
+/// with the value returned by `lookup`. Placeholders of unknown variables are kept as they are,
+/// so the rendered file shows what is missing.
+pub fn render(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + length + 2];
+        rendered.push_str(&rest[..start]);
+        match lookup(placeholder[2..placeholder.len() - 2].trim()) {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(placeholder),
+        }
+        rest = &rest[start + length + 2..];
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "hostname" => Some("laptop".to_string()),
+            "env.FONT_SIZE" => Some("12".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn render_replaces_known_placeholders() {
+        assert_eq!(
+            render("host={{hostname}}\nsize = {{ env.FONT_SIZE }}px", lookup),
+            "host=laptop\nsize = 12px"
+        );
+    }
+
+    #[test]
+    fn render_keeps_unknown_and_unclosed_placeholders() {
+        assert_eq!(
+            render("{{unknown}} {{hostname}} {{hostname", lookup),
+            "{{unknown}} laptop {{hostname"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use crate::AppError;
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Writes a machine-readable trace of everything a single run did into a file, one JSON object per line.
+/// The trace is meant to be attached to bug reports. It is only written if `--trace-file` was provided,
+/// otherwise all calls to `event` are no-ops.
+struct Tracer {
+    writer: BufWriter<File>,
+    /// if set, all occurrences of this path in the trace are replaced with `~`
+    redacted_home: Option<PathBuf>,
+}
+
+static TRACER: Mutex<Option<Tracer>> = Mutex::new(None);
+
+/// starts tracing into the given file. The file is truncated if it exists already.
+pub fn init(path: &Path, redacted_home: Option<PathBuf>) -> Result<(), AppError> {
+    let file = File::create(path).map_err(|err| {
+        AppError::FsOther(format!(
+            "Could not create trace file {}: {}",
+            path.display(),
+            err
+        ))
+    })?;
+    if let Ok(mut tracer) = TRACER.lock() {
+        *tracer = Some(Tracer {
+            writer: BufWriter::new(file),
+            redacted_home,
+        });
+    }
+
+    Ok(())
+}
+
+/// records a single event with the given name and data if tracing is enabled.
+/// Tracing must never let a command fail, therefore write errors are ignored.
+pub fn event(name: &str, data: Value) {
+    if let Ok(mut guard) = TRACER.lock() {
+        if let Some(tracer) = guard.as_mut() {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or(0);
+            let mut line = json!({ "timestamp": timestamp, "event": name, "data": data });
+            if let Some(home) = &tracer.redacted_home {
+                redact(&mut line, &home.display().to_string());
+            }
+            let _ = writeln!(tracer.writer, "{}", line);
+            let _ = tracer.writer.flush();
+        }
+    }
+}
+
+/// returns the given path as JSON string to be used in events.
+/// Unlike serializing the path itself, this never fails for paths which are not valid UTF-8:
+/// their invalid characters are replaced instead.
+pub fn path_value(path: &Path) -> Value {
+    Value::String(path.to_string_lossy().into_owned())
+}
+
+/// replaces the given home path with `~` in all strings of the given JSON value
+fn redact(value: &mut Value, home: &str) {
+    match value {
+        Value::String(text) => *text = text.replace(home, "~"),
+        Value::Array(values) => values.iter_mut().for_each(|value| redact(value, home)),
+        Value::Object(map) => map.values_mut().for_each(|value| redact(value, home)),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redact;
+    use serde_json::json;
+
+    #[test]
+    fn redact_replaces_home_in_nested_values() {
+        let mut value = json!({ "path": "/home/me/.vimrc", "list": ["/home/me/a", 1] });
+        redact(&mut value, "/home/me");
+        assert_eq!(value, json!({ "path": "~/.vimrc", "list": ["~/a", 1] }));
+    }
+}